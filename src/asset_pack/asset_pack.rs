@@ -1,134 +1,777 @@
 use log::{debug, info, warn};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
 
-use anyhow::{bail, Context};
-use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use anyhow::{anyhow, bail, Context};
+use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt, BE, LE};
+use serde::{Deserialize, Serialize};
 
+use crate::asset_pack::classification::ClassificationRules;
+use crate::asset_pack::compression::decompress_file;
+use crate::asset_pack::deflate::zlib_compress;
 use crate::asset_pack::file_meta_data::FileMetaData;
 use crate::asset_pack::godot_version::GodotVersion;
-use crate::asset_pack::pack_meta::PackMeta;
+use crate::asset_pack::pack_layout::PackLayout;
+use crate::asset_pack::pack_meta::{JsonValue, PackMeta};
 use crate::asset_pack::path_utils::*;
+use crate::asset_pack::read_options::ReadOptions;
 use crate::asset_pack::tags::Tags;
 use crate::asset_pack::utils::*;
+use crate::asset_pack::write_options::WriteOptions;
 
 #[derive(Debug)]
 pub struct AssetPack {
     pub godot_version: GodotVersion,
+    pub layout: PackLayout,
     pub meta: PackMeta,
     pub tags: Tags,
     pub object_files: HashMap<String, Vec<u8>>,
     pub other_files: HashMap<String, Vec<u8>>,
+    /// The md5 hashes stored in the file table of the pack that was read, by path.
+    /// Dungeondraft leaves them zeroed, and packs that were not read from a file have none.
+    pub stored_md5: HashMap<String, [u8; MD5_BYTES]>,
+    /// Paths of the object and other files in the order they were stored in the pack that
+    /// was read. Used by `WriteOptions::preserve_file_order`.
+    pub file_order: Vec<String>,
+    /// Bytes after the last file, like alignment padding or a signature block.
+    /// Written back after the files, so such packs survive a read and write unchanged.
+    pub trailer: Vec<u8>,
+    /// The metadata and tags files that were saved with a byte order mark or Windows
+    /// line endings, by path. Both are tolerated, but reported by `validate`.
+    pub json_encodings: BTreeMap<String, JsonEncoding>,
+}
+
+/// How a json file was saved, see `AssetPack::json_encodings`.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct JsonEncoding {
+    /// Starts with a utf-8 byte order mark, which is stripped when reading.
+    pub bom: bool,
+    /// Has `\r\n` line endings.
+    pub crlf: bool,
+}
+
+impl JsonEncoding {
+    pub fn of(file_data: &[u8]) -> Self {
+        JsonEncoding {
+            bom: file_data.starts_with(UTF8_BOM),
+            crlf: file_data.windows(2).any(|pair| pair == b"\r\n"),
+        }
+    }
+
+    /// Neither a byte order mark nor Windows line endings.
+    pub fn is_clean(&self) -> bool {
+        *self == JsonEncoding::default()
+    }
+}
+
+/// What `AssetPack::clean_tags` removed.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct CleanReport {
+    /// Names of the removed tags, sorted.
+    pub removed_tags: Vec<String>,
+    /// Names of the removed tag sets, sorted.
+    pub removed_sets: Vec<String>,
+    /// Per tag, the files that were removed from it because they don't exist, sorted.
+    pub removed_references: BTreeMap<String, Vec<String>>,
+    /// Per tag, the files that don't exist but were kept because of
+    /// `CleanOptions::keep_external_refs`, sorted.
+    pub kept_references: BTreeMap<String, Vec<String>>,
+}
+
+/// Options for `AssetPack::clean_tags_with_options`.
+#[derive(Debug, Default, Clone)]
+pub struct CleanOptions {
+    /// Keep references to files the pack does not have, if they look like object files,
+    /// see `is_well_formed_object_path`. For tags that refer to the objects of a companion pack.
+    /// Only references that are obviously broken are removed.
+    pub keep_external_refs: bool,
+}
+
+/// A file that `AssetPack::from_read_lenient` could not read.
+#[derive(Debug)]
+pub struct SkippedFile {
+    /// Position of the file in the file table, after sorting by offset.
+    pub index: usize,
+    pub path: String,
+    pub error: anyhow::Error,
 }
 
 impl AssetPack {
     pub fn from_read<R: Read + Seek>(data: &mut R) -> anyhow::Result<Self> {
-        let mut magic_file_number = [0; 4];
-        data.read_exact(&mut magic_file_number)?;
+        Self::from_read_with_options(data, &ReadOptions::default())
+    }
 
-        if magic_file_number != ASSET_PACK_MAGIC_FILE_HEADER {
-            warn!(
-                "First bytes of file do not indicate this is an asset pack. \
-            Reading might not work correctly, attempting anyway."
-            );
-        }
+    /// Like `from_read`, but with custom rules for which files are object files.
+    pub fn from_read_with_rules<R: Read + Seek>(
+        data: &mut R,
+        rules: &ClassificationRules,
+    ) -> anyhow::Result<Self> {
+        let options = ReadOptions {
+            classification_rules: rules.clone(),
+            ..Default::default()
+        };
+
+        Self::from_read_with_options(data, &options)
+    }
+
+    /// Like `from_read`, for a pack with a header of `header_len` bytes instead of
+    /// the magic number. Pass 0 for a pack whose header was stripped off.
+    pub fn from_read_at<R: Read + Seek>(data: &mut R, header_len: usize) -> anyhow::Result<Self> {
+        let options = ReadOptions {
+            header_len: Some(header_len),
+            ..Default::default()
+        };
+
+        Self::from_read_with_options(data, &options)
+    }
 
-        data.seek(SeekFrom::Start(ASSET_PACK_MAGIC_FILE_HEADER.len() as u64))?;
+    /// Like `from_read`, with all the options in `ReadOptions`.
+    pub fn from_read_with_options<R: Read + Seek>(
+        data: &mut R,
+        options: &ReadOptions,
+    ) -> anyhow::Result<Self> {
+        Ok(Self::read_files(data, options, |_, _, e| Err(e))?.0)
+    }
+
+    /// Like `from_read`, but also returns the file table as it was read, sorted by offset.
+    /// The entries keep their offsets in the pack file, so a caller can find where each file
+    /// is stored, e.g. to patch it in place. Their paths are relative to the pack,
+    /// like the keys of `object_files` and `other_files`.
+    pub fn from_read_with_table<R: Read + Seek>(
+        data: &mut R,
+    ) -> anyhow::Result<(Self, Vec<FileMetaData>)> {
+        Self::read_files(data, &ReadOptions::default(), |_, _, e| Err(e))
+    }
+
+    /// Reads only the header of a pack: the godot version and the pack format version.
+    /// The file table and files are not read at all.
+    pub fn read_version<R: Read + Seek>(data: &mut R) -> anyhow::Result<(GodotVersion, u32)> {
+        let header_len = match Self::locate_header(data)? {
+            Some(header_len) => header_len,
+            None => bail!("First bytes of file do not indicate this is an asset pack"),
+        };
+        data.seek(SeekFrom::Start(header_len as u64))?;
 
         let godot_version =
             GodotVersion::from_read(data).context("Could not read godot version")?;
-        data.read_exact(&mut [0; GODOT_METADATA_RESERVED_SPACE])?;
+        let format_version = godot_version.format_version();
 
-        let nr_of_files = data.read_i32::<LE>()? as usize;
+        Ok((godot_version, format_version))
+    }
 
-        let mut files_meta = vec![];
+    /// Reads the contents of a single file, by its path relative to the pack,
+    /// e.g. `textures/objects/rock.png`. Only the file table and that file are read.
+    /// Returns `None` if the pack does not contain the file.
+    pub fn read_single_file<R: Read + Seek>(
+        data: &mut R,
+        path: &str,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let (_, files_meta) = Self::read_file_table(data, None)?;
+
+        let meta = match files_meta.iter().find(|meta| meta.path == path) {
+            Some(meta) => meta,
+            None => return Ok(None),
+        };
+
+        let pack_length = data.seek(SeekFrom::End(0))?;
+        let file_data = Self::read_file_data(data, meta, pack_length)?;
+        Ok(Some(
+            decompress_file(meta, &file_data)?.unwrap_or(file_data),
+        ))
+    }
 
-        for i in 0..nr_of_files {
-            let file_meta = FileMetaData::from_read(data).context(format!(
-                "Could not read file metadata of file {} from {}",
-                i + 1,
-                nr_of_files
-            ))?;
+    /// Like `from_read`, but a file that can't be read or parsed is skipped instead of failing
+    /// the whole pack. Meant for salvaging what is left of damaged packs.
+    /// The file table and the metadata file are still required.
+    pub fn from_read_lenient<R: Read + Seek>(
+        data: &mut R,
+    ) -> anyhow::Result<(Self, Vec<SkippedFile>)> {
+        let mut skipped = vec![];
 
-            files_meta.push(file_meta);
+        let (pack, _) = Self::read_files(data, &ReadOptions::default(), |index, meta, error| {
+            warn!(
+                "Skipping file {} ('{}'), it could not be read:\n{:#}",
+                index + 1,
+                meta.path,
+                error
+            );
+            skipped.push(SkippedFile {
+                index,
+                path: meta.path.clone(),
+                error,
+            });
+            Ok(())
+        })?;
+
+        Ok((pack, skipped))
+    }
+
+    /// Reads the pack and its file table, passing errors in individual files to `on_error`.
+    /// The read is aborted if `on_error` returns an error.
+    fn read_files<R, F>(
+        data: &mut R,
+        options: &ReadOptions,
+        mut on_error: F,
+    ) -> anyhow::Result<(Self, Vec<FileMetaData>)>
+    where
+        R: Read + Seek,
+        F: FnMut(usize, &FileMetaData, anyhow::Error) -> anyhow::Result<()>,
+    {
+        let (godot_version, files_meta) = Self::read_file_table(data, options.header_len)?;
+
+        let table_end = data.stream_position()?;
+        let pack_length = data.seek(SeekFrom::End(0))?;
+        data.seek(SeekFrom::Start(table_end))?;
+
+        Self::check_overlapping_files(&files_meta, pack_length)?;
+
+        if let Some(max_memory) = options.max_memory {
+            let total_size: u64 = files_meta.iter().map(|meta| meta.size as u64).sum();
+
+            if total_size > max_memory {
+                bail!(
+                    "The files in the pack add up to {} bytes, more than the maximum of {} bytes. \
+                    Use `extract-file` to get single files out of it instead.",
+                    total_size,
+                    max_memory
+                );
+            }
         }
 
-        files_meta.sort();
+        // The files are read one after the other, so this is where it is noticed
+        // if the file table was not read the way it was written.
+        if let Some(first_file) = files_meta.first() {
+            let position = data.stream_position()?;
+
+            if position != first_file.offset {
+                warn!(
+                    "File table ends at byte {}, but the first file starts at byte {}. \
+                    Continuing from the first file.",
+                    position, first_file.offset
+                );
+                data.seek(SeekFrom::Start(first_file.offset))?;
+            }
+        }
 
         let mut object_files = HashMap::new();
         let mut other_files = HashMap::new();
+        let mut stored_md5 = HashMap::new();
+        let mut file_order = vec![];
         let mut maybe_meta = None;
+        let mut maybe_pack_json_meta = None;
         let mut maybe_tags = None;
-
-        for meta in files_meta {
-            let mut file_data = vec![0; meta.size];
-            data.read_exact(&mut file_data)?;
+        let mut layout = PackLayout::default();
+        let mut root_json_name = None;
+        let mut json_encodings = BTreeMap::new();
+
+        for (index, meta) in files_meta.iter().enumerate() {
+            let file_data = match Self::read_file_data(data, meta, pack_length) {
+                Ok(file_data) => file_data,
+                Err(e) => {
+                    on_error(index, meta, e)?;
+                    continue;
+                }
+            };
+
+            let (file_data, md5) = match decompress_file(meta, &file_data) {
+                // The stored hash is of the compressed bytes.
+                Ok(Some(decompressed)) => (decompressed, [0; MD5_BYTES]),
+                Ok(None) => (file_data, meta.md5),
+                Err(e) => {
+                    on_error(index, meta, e)?;
+                    continue;
+                }
+            };
 
             let pathbuf = &PathBuf::from(meta.path.clone());
 
+            if is_pack_file(pathbuf) || is_root_json_file(pathbuf) || is_tags_file(&meta.path) {
+                record_json_encoding(&mut json_encodings, &meta.path, &file_data);
+            }
+
             // A dungeondraft asset pack for some reason has two json files with identical contents
             // one is the root json file `packs/<pack-id>.json` and the other
             // is `packs/<pack-id>/pack.json`. This is why whe ignore the second one
-            // (via `is_pack_file()`)
-            if is_root_json_file(pathbuf) {
-                let data_string = String::from_utf8(file_data)?;
-
-                maybe_meta = match json5::from_str(&data_string) {
-                    Ok(meta) => Some(meta),
-                    Err(e) => {
-                        display_file_as_info(&data_string);
-                        bail!("Could not parse pack metadata file:\n{}", e)
+            // (via `is_pack_file()`), apart from remembering where it was,
+            // unless asked to check whether the two agree.
+            if is_pack_file(pathbuf) {
+                layout.pack_json_path = meta.path.clone();
+
+                if options.check_meta_consistency {
+                    match parse_meta_file(file_data) {
+                        Ok(pack_meta) => maybe_pack_json_meta = Some(pack_meta),
+                        Err(e) => warn!("Could not parse '{}', ignoring it:\n{}", meta.path, e),
+                    }
+                }
+            } else if is_root_json_file(pathbuf) {
+                match parse_meta_file(file_data) {
+                    Ok(pack_meta) => {
+                        maybe_meta = Some(pack_meta);
+                        root_json_name = Some(meta.path.clone());
                     }
-                };
+                    Err(e) => on_error(index, meta, e)?,
+                }
             } else if is_tags_file(&meta.path) {
-                let data_string = String::from_utf8(file_data)?;
-
-                maybe_tags = match json5::from_str(&data_string) {
-                    Ok(tags) => Some(tags),
-                    Err(e) => {
-                        display_file_as_info(&data_string);
-                        bail!("Could not parse object tags file:\n{}", e)
+                match parse_tags_file(file_data) {
+                    Ok(tags) => {
+                        maybe_tags = Some(tags);
+                        layout.tags_file_path = meta.path.clone();
                     }
-                };
-            } else if is_objects_file(&meta.path) {
+                    Err(e) => on_error(index, meta, e)?,
+                }
+            } else if options.classification_rules.is_object_file(&meta.path) {
                 object_files.insert(meta.path.clone(), file_data);
-            } else if !is_pack_file(pathbuf) {
+                stored_md5.insert(meta.path.clone(), md5);
+                file_order.push(meta.path.clone());
+            } else if meta.path.is_empty() {
+                debug!("Skipping directory entry of size {}.", meta.size);
+            } else {
                 other_files.insert(meta.path.clone(), file_data);
+                stored_md5.insert(meta.path.clone(), md5);
+                file_order.push(meta.path.clone());
             }
         }
 
+        let files_end = files_meta
+            .iter()
+            .map(|meta| meta.offset + meta.size as u64)
+            .max()
+            .unwrap_or(table_end);
+        let mut trailer = vec![];
+        if files_end < pack_length {
+            data.seek(SeekFrom::Start(files_end))?;
+            data.read_to_end(&mut trailer)?;
+            debug!("Keeping {} bytes after the last file.", trailer.len());
+        }
+
+        let mut meta: PackMeta = match maybe_meta {
+            Some(meta) => meta,
+            None => bail!("Pack does not contain a metadata file"),
+        };
         // Some packs don't include any object files, and therefore also don't have a tags file.
-        let tags = maybe_tags.unwrap_or(Tags::new());
+        // Some nonstandard packs store their tags in the metadata file instead.
+        let tags = match maybe_tags {
+            Some(tags) => tags,
+            None => take_embedded_tags(&mut meta).unwrap_or_default(),
+        };
+
+        if let Some(pack_json_meta) = maybe_pack_json_meta {
+            let differences = meta.differing_fields(&pack_json_meta);
+
+            if !differences.is_empty() {
+                warn!(
+                    "The root json file and '{}' differ (root json != pack.json):\n    {}\n\
+                    Using the {}.",
+                    layout.pack_json_path,
+                    differences.join("\n    "),
+                    if options.prefer_pack_json {
+                        "pack.json"
+                    } else {
+                        "root json file"
+                    }
+                );
+
+                if options.prefer_pack_json {
+                    meta = pack_json_meta;
+                }
+            }
+        }
 
-        Ok(AssetPack {
+        // Only remember the root json name if it is unusual, so changing the id renames it.
+        if root_json_name != Some(meta.id.clone() + ".json") {
+            layout.root_json_name = root_json_name;
+        }
+
+        let pack = AssetPack {
             godot_version,
-            meta: maybe_meta.unwrap(),
+            layout,
+            meta,
             tags,
             object_files,
             other_files,
-        })
+            stored_md5,
+            file_order,
+            json_encodings,
+            trailer,
+        };
+
+        Ok((pack, files_meta))
+    }
+
+    /// Fails if any two files in the table, sorted by offset, claim the same bytes.
+    /// Two entries for exactly the same bytes are fine, see
+    /// `WriteOptions::share_metadata_offset`.
+    /// Files running past the end of the pack are left to `read_file_data` to report.
+    fn check_overlapping_files(
+        files_meta: &[FileMetaData],
+        pack_length: u64,
+    ) -> anyhow::Result<()> {
+        let in_pack = files_meta.iter().filter(|meta| {
+            meta.size > 0
+                && meta
+                    .offset
+                    .checked_add(meta.size as u64)
+                    .is_some_and(|end| end <= pack_length)
+        });
+
+        let mut overlapping = vec![];
+        let mut previous: Option<&FileMetaData> = None;
+
+        for meta in in_pack {
+            if let Some(previous) = previous {
+                let shared = previous.offset == meta.offset && previous.size == meta.size;
+
+                if !shared && previous.offset + previous.size as u64 > meta.offset {
+                    overlapping.push(format!(
+                        "'{}' (bytes {} to {}) and '{}' (bytes {} to {})",
+                        previous.path,
+                        previous.offset,
+                        previous.offset + previous.size as u64,
+                        meta.path,
+                        meta.offset,
+                        meta.offset + meta.size as u64
+                    ));
+                }
+            }
+            previous = Some(meta);
+        }
+
+        if !overlapping.is_empty() {
+            bail!(
+                "The file table is corrupt, these files overlap:\n{}",
+                overlapping.join("\n")
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Reads the contents of a single file. Seeks to the file if it does not directly follow
+    /// the previous one, and refuses sizes that run past the end of the pack.
+    pub(crate) fn read_file_data<R: Read + Seek>(
+        data: &mut R,
+        meta: &FileMetaData,
+        pack_length: u64,
+    ) -> anyhow::Result<Vec<u8>> {
+        if meta
+            .offset
+            .checked_add(meta.size as u64)
+            .is_none_or(|end| end > pack_length)
+        {
+            bail!(
+                "File of {} bytes at offset {} does not fit in the pack of {} bytes",
+                meta.size,
+                meta.offset,
+                pack_length
+            );
+        }
+
+        if data.stream_position()? != meta.offset {
+            data.seek(SeekFrom::Start(meta.offset))?;
+        }
+
+        let mut file_data = vec![0; meta.size];
+        data.read_exact(&mut file_data)?;
+
+        Ok(file_data)
+    }
+
+    /// Reads the header and the metadata of all the files, sorted by offset.
+    /// Leaves `data` positioned at the end of the file table.
+    /// Packs are little-endian, but if the file table only makes sense as big-endian,
+    /// it is read that way. Writing such a pack converts it to little-endian.
+    /// See `ReadOptions::header_len` for `header_len`.
+    pub(crate) fn read_file_table<R: Read + Seek>(
+        data: &mut R,
+        header_len: Option<usize>,
+    ) -> anyhow::Result<(GodotVersion, Vec<FileMetaData>)> {
+        let header_len = match header_len {
+            Some(header_len) => header_len,
+            None => Self::locate_header(data)?.unwrap_or_else(|| {
+                warn!(
+                    "First bytes of file do not indicate this is an asset pack. \
+                    Reading might not work correctly, attempting anyway."
+                );
+                ASSET_PACK_MAGIC_FILE_HEADER.len()
+            }),
+        };
+
+        let start = data.stream_position()?;
+
+        let little_endian_error = match Self::read_file_table_with_order::<R, LE>(data, header_len)
+        {
+            Ok(table) => return Ok(table),
+            Err(e) => e,
+        };
+
+        data.seek(SeekFrom::Start(start))?;
+
+        match Self::read_file_table_with_order::<R, BE>(data, header_len) {
+            Ok(table) => {
+                warn!(
+                    "The file table could not be read as little-endian, but it can as big-endian. \
+                    The pack was probably written with the wrong byte order. \
+                    Reading it as big-endian."
+                );
+                Ok(table)
+            }
+            Err(_) => Err(little_endian_error),
+        }
+    }
+
+    /// Like `read_file_table`, with the numbers in byte order `B`.
+    fn read_file_table_with_order<R: Read + Seek, B: ByteOrder>(
+        data: &mut R,
+        header_len: usize,
+    ) -> anyhow::Result<(GodotVersion, Vec<FileMetaData>)> {
+        data.seek(SeekFrom::Start(header_len as u64))?;
+
+        let godot_version = GodotVersion::from_read_with_order::<R, B>(data)
+            .context("Could not read godot version")?;
+        if godot_version.format_version() > MAX_PACK_FORMAT_VERSION {
+            bail!(
+                "Pack format {} is not supported, only formats up to {}",
+                godot_version.format_version(),
+                MAX_PACK_FORMAT_VERSION
+            );
+        }
+
+        let file_base = if godot_version.has_file_base() {
+            let pack_flags = data.read_u32::<B>()?;
+            if pack_flags & PACK_DIR_ENCRYPTED != 0 {
+                bail!("The file table of the pack is encrypted, which is not supported");
+            }
+            data.read_u64::<B>()?
+        } else {
+            0
+        };
+        data.read_exact(&mut [0; GODOT_METADATA_RESERVED_SPACE])?;
+
+        let nr_of_files = data.read_i32::<B>()?;
+        let remaining_bytes = remaining_length(data)?;
+
+        // Every file needs at least its metadata entry, so a corrupt header claiming
+        // more files than could possibly fit is rejected before allocating anything.
+        if nr_of_files < 0
+            || nr_of_files as u64 * FileMetaData::MIN_BINARY_SIZE as u64 > remaining_bytes
+        {
+            bail!(
+                "Implausible file count {} for the remaining {} bytes of the pack",
+                nr_of_files,
+                remaining_bytes
+            );
+        }
+        let nr_of_files = nr_of_files as usize;
+
+        let mut files_meta = vec![];
+
+        for i in 0..nr_of_files {
+            let file_meta =
+                FileMetaData::from_read_with_order::<R, B>(data, godot_version.has_entry_flags())
+                    .context(format!(
+                    "Could not read file metadata of file {} from {}",
+                    i + 1,
+                    nr_of_files
+                ))?;
+
+            if file_meta.is_removed() {
+                warn!(
+                    "Skipping '{}', it is marked as removed by a patch pack.",
+                    file_meta.path
+                );
+                continue;
+            }
+
+            files_meta.push(file_meta);
+        }
+
+        if file_base != 0 {
+            for meta in files_meta.iter_mut() {
+                meta.offset = meta.offset.saturating_add(file_base);
+            }
+        }
+
+        files_meta.sort();
+
+        // The offsets count the standard header, which this pack might not have.
+        if header_len != ASSET_PACK_MAGIC_FILE_HEADER.len() {
+            for meta in files_meta.iter_mut() {
+                meta.offset = (meta.offset.saturating_add(header_len as u64))
+                    .saturating_sub(ASSET_PACK_MAGIC_FILE_HEADER.len() as u64);
+            }
+        }
+
+        let pack_id = Self::read_pack_id(data, &files_meta)?;
+        let pack_id = match (pack_id, Self::most_common_path_pack_id(&files_meta)) {
+            (Some(meta_id), Some(path_id)) if meta_id != path_id => {
+                warn!(
+                    "The files are stored under pack id '{}', but the metadata has id '{}'. \
+                    Using '{}' to find the files.",
+                    path_id, meta_id, path_id
+                );
+                Some(path_id)
+            }
+            (Some(meta_id), _) => Some(meta_id),
+            (None, path_id) => path_id,
+        };
+
+        for meta in files_meta.iter_mut() {
+            meta.strip_pack_prefix(pack_id.as_deref());
+        }
+
+        Ok((godot_version, files_meta))
+    }
+
+    /// Number of bytes up to and including the magic number, or `None` if there is none.
+    /// Normally the pack starts with a recognized magic number. Otherwise the first
+    /// `MAGIC_SEARCH_LENGTH` bytes are searched for `GDPC`, for packs wrapped in a custom header.
+    fn locate_header<R: Read + Seek>(data: &mut R) -> anyhow::Result<Option<usize>> {
+        let mut start = vec![];
+        data.seek(SeekFrom::Start(0))?;
+        data.take(MAGIC_SEARCH_LENGTH as u64)
+            .read_to_end(&mut start)?;
+
+        let magic_len = ASSET_PACK_MAGIC_FILE_HEADER.len();
+
+        if start.len() >= magic_len
+            && RECOGNIZED_MAGIC_FILE_HEADERS.contains(&[start[0], start[1], start[2], start[3]])
+        {
+            return Ok(Some(magic_len));
+        }
+
+        let found = start
+            .windows(magic_len)
+            .position(|window| window == ASSET_PACK_MAGIC_FILE_HEADER);
+
+        if let Some(position) = found {
+            info!(
+                "Found the asset pack header at byte {}, skipping the bytes before it.",
+                position
+            );
+        }
+
+        Ok(found.map(|position| position + magic_len))
+    }
+
+    /// The pack id most files are stored under, in `res://packs/<pack-id>/`.
+    /// Normally the same as the id in the metadata, but re-exported packs don't always agree.
+    fn most_common_path_pack_id(files_meta: &[FileMetaData]) -> Option<String> {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+
+        for meta in files_meta {
+            if let Some(id) = path_pack_id(&meta.path) {
+                *counts.entry(id).or_default() += 1;
+            }
+        }
+
+        // On a tie the first id alphabetically wins, so the result does not depend on hashing.
+        counts
+            .into_iter()
+            .rev()
+            .max_by_key(|(_, count)| *count)
+            .map(|(id, _)| id.to_owned())
+    }
+
+    /// The pack directory is named after the pack id, which is stored in the root json file.
+    /// That file is the only file outside of the pack directory, so it can be found by its path.
+    /// Falls back on the name of the root json file if it can't be parsed.
+    fn read_pack_id<R: Read + Seek>(
+        data: &mut R,
+        files_meta: &[FileMetaData],
+    ) -> anyhow::Result<Option<String>> {
+        let root_json = match files_meta
+            .iter()
+            .find(|meta| root_json_pack_id(&meta.path).is_some())
+        {
+            Some(root_json) => root_json,
+            None => return Ok(None),
+        };
+
+        let table_end = data.stream_position()?;
+        let pack_length = data.seek(SeekFrom::End(0))?;
+
+        let file_data = Self::read_file_data(data, root_json, pack_length)
+            .context(format!("Could not read '{}'", root_json.path))?;
+        data.seek(SeekFrom::Start(table_end))?;
+
+        let parsed_id = String::from_utf8(file_data)
+            .ok()
+            .and_then(|text| json5::from_str::<PackMeta>(&text).ok())
+            .map(|meta| meta.id);
+
+        Ok(parsed_id.or_else(|| root_json_pack_id(&root_json.path).map(str::to_owned)))
+    }
+
+    /// Fails if the pack can't be written with these options: with `WriteOptions::strict_tags`
+    /// if a tag references a missing file, and with `WriteOptions::compress` if the pack
+    /// format has no entry flags to mark the compressed files with.
+    pub fn check_write_options(&self, options: &WriteOptions) -> anyhow::Result<()> {
+        if options.strict_tags {
+            self.check_tag_references()?;
+        }
+
+        if options.compress && !self.godot_version.has_entry_flags() {
+            bail!(
+                "Can't compress the files of a pack of format {}, \
+                only packs of format 2 and up (Godot 4) can mark files as compressed",
+                self.godot_version.format_version()
+            );
+        }
+
+        Ok(())
     }
 
     pub fn to_write<W: Write>(&self, data: &mut W) -> anyhow::Result<()> {
-        data.write_all(&ASSET_PACK_MAGIC_FILE_HEADER)?;
-        self.godot_version.to_write(data)?;
-        data.write_all(&[0; GODOT_METADATA_RESERVED_SPACE])?;
+        self.to_write_with_options(data, &WriteOptions::default())
+    }
+
+    pub fn to_write_with_options<W: Write>(
+        &self,
+        data: &mut W,
+        options: &WriteOptions,
+    ) -> anyhow::Result<()> {
+        self.check_write_options(options)?;
 
         let file_path_prefix =
             RESOURCE_PATH_PREFIX.to_owned() + ASSET_PACK_PREFIX + self.meta.id.as_str();
 
-        let pack_meta_file = json5::to_string(&self.meta)?.as_bytes().to_vec();
-        let root_pack_file_metadata =
-            FileMetaData::new(file_path_prefix.clone() + ".json", pack_meta_file.len());
+        let to_json = |json: String| {
+            if options.pretty_json {
+                pretty_json(&json).into_bytes()
+            } else {
+                json.into_bytes()
+            }
+        };
+
+        let pack_meta_file = to_json(if options.embed_tags {
+            let mut meta = self.meta.clone();
+            meta.extra.insert(
+                EMBEDDED_TAGS_KEY.to_owned(),
+                json5::from_str(&self.tags.to_sorted_json()?)?,
+            );
+            json5::to_string(&meta)?
+        } else {
+            json5::to_string(&self.meta)?
+        });
+        let root_pack_file_metadata = FileMetaData::new(
+            RESOURCE_PATH_PREFIX.to_owned()
+                + ASSET_PACK_PREFIX
+                + &self.layout.root_json_name(&self.meta.id),
+            pack_meta_file.len(),
+        );
         let pack_file_metadata = FileMetaData::new(
-            file_path_prefix.clone() + "/" + PACK_FILE_NAME,
+            file_path_prefix.clone() + "/" + &self.layout.pack_json_path,
             pack_meta_file.len(),
         );
 
-        let tags_file = json5::to_string(&self.tags)?.as_bytes().to_vec();
+        let tags_file = to_json(if options.sort_tags || self.layout.canonical_order {
+            self.tags.to_sorted_json()?
+        } else {
+            json5::to_string(&self.tags)?
+        });
         let tags_metadata = FileMetaData::new(
-            file_path_prefix.clone() + "/" + TAGS_FILE_NAME,
+            file_path_prefix.clone() + "/" + &self.layout.tags_file_path,
             tags_file.len(),
         );
 
@@ -139,40 +782,137 @@ impl AssetPack {
         // This is why we add two files with the same content here.
         files.push((root_pack_file_metadata, &pack_meta_file));
         files.push((pack_file_metadata, &pack_meta_file));
-        files.push((tags_metadata, &tags_file));
+        if !options.embed_tags {
+            files.push((tags_metadata, &tags_file));
+        }
+
+        let mut pack_files: Vec<_> = self
+            .object_files
+            .iter()
+            .chain(self.other_files.iter())
+            .collect();
+
+        if options.sort_files_by_path || self.layout.canonical_order {
+            pack_files.sort_by_key(|(file_path, _)| *file_path);
+        } else if options.preserve_file_order {
+            let positions: HashMap<&String, usize> = self
+                .file_order
+                .iter()
+                .enumerate()
+                .map(|(position, path)| (path, position))
+                .collect();
+
+            pack_files.sort_by_key(|(file_path, _)| {
+                (
+                    positions.get(file_path).copied().unwrap_or(usize::MAX),
+                    *file_path,
+                )
+            });
+        }
 
-        for (file_path, data) in self.object_files.iter().chain(self.other_files.iter()) {
+        let compressed_files: HashMap<&String, Vec<u8>> = if options.compress {
+            pack_files
+                .iter()
+                .filter_map(|(file_path, data)| {
+                    let compressed = zlib_compress(data);
+                    (compressed.len() < data.len()).then_some((*file_path, compressed))
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        for (file_path, data) in pack_files {
             let path_with_prefix = file_path_prefix.clone() + "/" + file_path;
 
-            files.push((FileMetaData::new(path_with_prefix, data.len()), data));
+            match compressed_files.get(file_path) {
+                Some(compressed) => {
+                    let mut meta = FileMetaData::new(path_with_prefix, compressed.len());
+                    meta.flags = Some(FileMetaData::FLAG_COMPRESSED);
+                    files.push((meta, compressed));
+                }
+                None => files.push((FileMetaData::new(path_with_prefix, data.len()), data)),
+            }
+        }
+
+        if let Some(threads) = options.md5_threads {
+            let contents: Vec<&[u8]> = files.iter().map(|(_, data)| data.as_slice()).collect();
+            let hashes = md5_hashes(&contents, threads);
+
+            for ((meta, _), md5) in files.iter_mut().zip(hashes) {
+                meta.md5 = md5;
+            }
         }
 
+        if self.godot_version.has_entry_flags() {
+            for (meta, _) in files.iter_mut() {
+                meta.flags.get_or_insert(0);
+            }
+        }
+
+        let alignment = options.alignment.unwrap_or(1);
+        let files_block_offset =
+            Self::calculate_files_block_starting_offset(&self.godot_version, &files);
+        let mut file_offset = files_block_offset;
+
+        data.write_all(&ASSET_PACK_MAGIC_FILE_HEADER)?;
+        self.godot_version.to_write(data)?;
+        if self.godot_version.has_file_base() {
+            // No pack flags, and the offsets count from the start of the pack.
+            data.write_u32::<LE>(0)?;
+            data.write_u64::<LE>(0)?;
+        }
+        data.write_all(&[0; GODOT_METADATA_RESERVED_SPACE])?;
         data.write_i32::<LE>(files.len() as i32)?;
 
-        let mut file_offset = Self::calculate_files_block_starting_offset(&files);
+        for index in 0..files.len() {
+            // Normally only the root json file and `pack.json` right after it are the same.
+            let same_as_previous =
+                index > 0 && options.share_metadata_offset && files[index - 1].1 == files[index].1;
+            if same_as_previous {
+                files[index].0.offset = files[index - 1].0.offset;
+                continue;
+            }
 
-        for (meta, _) in files.iter_mut() {
-            meta.offset = file_offset as u64;
-            file_offset += meta.size;
+            file_offset = align_offset(file_offset, alignment);
+            files[index].0.offset = file_offset as u64;
+            file_offset += files[index].0.size;
         }
 
         for (meta, _) in files.iter() {
             meta.to_write(data)?;
         }
 
-        for (_, file_data) in files.iter() {
+        let mut position = files_block_offset;
+        let mut previous: Option<(u64, &[u8])> = None;
+        for (meta, file_data) in files.iter() {
+            if (meta.offset as usize) < position {
+                assert_eq!(
+                    previous,
+                    Some((meta.offset, file_data.as_slice())),
+                    "'{}' shares its offset with a file that has other contents",
+                    meta.path
+                );
+                continue;
+            }
+            previous = Some((meta.offset, file_data.as_slice()));
+
+            let padding = meta.offset as usize - position;
+            data.write_all(&vec![0; padding])?;
             data.write_all(file_data)?;
+            position = meta.offset as usize + meta.size;
         }
 
+        data.write_all(&self.trailer)?;
+
         Ok(())
     }
 
-    fn calculate_files_block_starting_offset(files: &Vec<(FileMetaData, &Vec<u8>)>) -> usize {
-        // The i32 is where the amount of files is kept.
-        let mut file_offset = ASSET_PACK_MAGIC_FILE_HEADER.len()
-            + GodotVersion::size_in_bytes()
-            + GODOT_METADATA_RESERVED_SPACE
-            + I32;
+    fn calculate_files_block_starting_offset(
+        godot_version: &GodotVersion,
+        files: &Vec<(FileMetaData, &Vec<u8>)>,
+    ) -> usize {
+        let mut file_offset = ASSET_PACK_MAGIC_FILE_HEADER.len() + godot_version.header_size();
 
         for (meta, _) in files.iter() {
             file_offset += meta.calculate_binary_size();
@@ -186,26 +926,59 @@ impl AssetPack {
     /// - Removes empty tags.
     /// - Removes non existing tags from tag sets.
     /// - Removes empty tag sets.
-    pub fn clean_tags(&mut self) {
+    ///
+    /// The sets are cleaned after the tags, and removing a set never empties a tag,
+    /// so one call fully cleans the pack: calling it again changes nothing.
+    ///
+    /// Returns what was removed.
+    pub fn clean_tags(&mut self) -> CleanReport {
+        self.clean_tags_with_options(&CleanOptions::default())
+    }
+
+    /// Like `clean_tags`, but can keep references to objects of other packs.
+    pub fn clean_tags_with_options(&mut self, options: &CleanOptions) -> CleanReport {
         info!("Cleaning empty tags and tag groups.");
 
         let mut empty_tags = vec![];
+        let mut removed_references = BTreeMap::new();
+        let mut kept_references = BTreeMap::new();
 
         for (tag, files) in self.tags.tags.iter_mut() {
             let mut not_existing_files = vec![];
+            let mut external_files = vec![];
 
             for file in files.iter() {
-                if !self.object_files.contains_key(file) {
+                if self.object_files.contains_key(file) {
+                    continue;
+                }
+
+                if options.keep_external_refs && is_well_formed_object_path(file) {
+                    debug!(
+                        "Keeping file '{}' in tag '{}', it might be in another pack.",
+                        file, tag
+                    );
+                    external_files.push(file.clone());
+                } else {
                     not_existing_files.push(file.clone());
                 }
             }
 
-            for file in not_existing_files {
+            if !external_files.is_empty() {
+                external_files.sort();
+                kept_references.insert(tag.clone(), external_files);
+            }
+
+            for file in not_existing_files.iter() {
                 debug!(
                     "Removing file '{}' from tag '{}' because it does not exist.",
                     file, tag
                 );
-                files.remove(&file);
+                files.remove(file);
+            }
+
+            if !not_existing_files.is_empty() {
+                not_existing_files.sort();
+                removed_references.insert(tag.clone(), not_existing_files);
             }
 
             if files.is_empty() {
@@ -252,16 +1025,322 @@ impl AssetPack {
             empty_tags.len(),
             empty_sets.len()
         );
+
+        empty_tags.sort();
+        empty_sets.sort();
+
+        CleanReport {
+            removed_tags: empty_tags,
+            removed_sets: empty_sets,
+            removed_references,
+            kept_references,
+        }
+    }
+
+    /// Makes every following write of the pack produce the same bytes for the same contents:
+    /// the metadata files first, then the other files sorted by path, and the tags sorted by name.
+    /// `to_write` works fine without this, but the order of the files and tags then depends
+    /// on how the pack was built. Call this after editing a pack, before comparing or hashing
+    /// what it writes.
+    pub fn normalize(&mut self) {
+        self.layout.canonical_order = true;
     }
 
-    fn get_files_in_tag(&self, tag: &str) -> Option<&HashSet<String>> {
+    /// A file name for the pack, made from its name: spaces become underscores
+    /// and anything but letters, digits, `-` and `_` is left out.
+    /// `My Cool Pack!` becomes `My_Cool_Pack.dungeondraft_pack`.
+    /// Falls back to the pack id if nothing of the name is left.
+    pub fn suggested_filename(&self) -> String {
+        let mut stem = String::new();
+
+        for c in self.meta.name.trim().chars() {
+            if c.is_whitespace() {
+                if !stem.ends_with('_') {
+                    stem.push('_');
+                }
+            } else if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                stem.push(c);
+            }
+        }
+
+        let stem = stem.trim_matches('_');
+        let stem = if stem.is_empty() {
+            self.meta.id.as_str()
+        } else {
+            stem
+        };
+
+        format!("{}.dungeondraft_pack", stem)
+    }
+
+    pub fn get_files_in_tag(&self, tag: &str) -> Option<&HashSet<String>> {
         self.tags.tags.get(tag)
     }
+
+    /// Names of the tag sets the tag is in, sorted.
+    pub fn sets_containing_tag(&self, tag: &str) -> Vec<&str> {
+        let mut sets: Vec<&str> = self
+            .tags
+            .sets
+            .iter()
+            .filter(|(_, tags)| tags.contains(tag))
+            .map(|(set, _)| set.as_str())
+            .collect();
+        sets.sort_unstable();
+        sets
+    }
+
+    /// Total size of the object files in the tag, in bytes. Files the pack does not have
+    /// are not counted. Returns `None` if there is no such tag.
+    pub fn tag_byte_size(&self, tag: &str) -> Option<u64> {
+        let files = self.get_files_in_tag(tag)?;
+
+        Some(
+            files
+                .iter()
+                .filter_map(|path| self.object_files.get(path))
+                .map(|data| data.len() as u64)
+                .sum(),
+        )
+    }
+
+    /// Removes the tag, and every object file in it that is in no other tag.
+    /// Files that are also in another tag are kept, with a warning.
+    /// The tag is removed from the tag sets too. Returns the number of removed files.
+    pub fn exclude_tag(&mut self, tag: &str) -> usize {
+        let files = match self.tags.tags.remove(tag) {
+            Some(files) => files,
+            None => {
+                warn!("Can't exclude tag '{}', the pack does not have it.", tag);
+                return 0;
+            }
+        };
+
+        for tags in self.tags.sets.values_mut() {
+            tags.remove(tag);
+        }
+
+        let mut removed = 0;
+
+        for path in files {
+            if self.tags.tags.values().any(|other| other.contains(&path)) {
+                warn!(
+                    "Keeping '{}' of excluded tag '{}', it is also in another tag.",
+                    path, tag
+                );
+                continue;
+            }
+
+            if self.remove_file(&path).is_some() {
+                removed += 1;
+            }
+        }
+
+        debug!("Excluded tag '{}', removing {} files.", tag, removed);
+
+        removed
+    }
+
+    /// Puts `prefix` in front of the name of every tag, and renames them in the tag sets too.
+    /// Set names are left alone. Used to keep the tags of packs apart when merging them.
+    pub fn prefix_tags(&mut self, prefix: &str) {
+        self.tags.tags = self
+            .tags
+            .tags
+            .drain()
+            .map(|(tag, files)| (format!("{}{}", prefix, tag), files))
+            .collect();
+
+        for tags in self.tags.sets.values_mut() {
+            *tags = tags
+                .drain()
+                .map(|tag| format!("{}{}", prefix, tag))
+                .collect();
+        }
+
+        debug!("Prefixed all tags with '{}'.", prefix);
+    }
+
+    /// Removes a file from the pack, and returns its contents if it was there.
+    /// Warns if a wall, tileset or path data file refers to it, because that data file
+    /// will not work without it. Tags that refer to it are left to `clean_tags`.
+    pub fn remove_file(&mut self, path: &str) -> Option<Vec<u8>> {
+        let data = self
+            .object_files
+            .remove(path)
+            .or_else(|| self.other_files.remove(path))?;
+        self.stored_md5.remove(path);
+        self.file_order.retain(|stored_path| stored_path != path);
+
+        for data_file in self.data_files_referring_to(path) {
+            warn!(
+                "Removed '{}', but '{}' still refers to it.",
+                path, data_file
+            );
+        }
+
+        Some(data)
+    }
+
+    /// Moves a file to a different path in the pack, updating the tags and the wall, tileset
+    /// and path data files that refer to it.
+    /// Moving a file into or out of `textures/objects/` makes it an object or other file.
+    pub fn move_file(&mut self, from: &str, to: &str) -> anyhow::Result<()> {
+        if self.object_files.contains_key(to) || self.other_files.contains_key(to) {
+            bail!("Can't move '{}' to '{}', it already exists.", from, to);
+        }
+
+        let data = match self
+            .object_files
+            .remove(from)
+            .or_else(|| self.other_files.remove(from))
+        {
+            Some(data) => data,
+            None => bail!("Can't move '{}', it does not exist.", from),
+        };
+
+        if is_objects_file(to) {
+            self.object_files.insert(to.to_owned(), data);
+        } else {
+            self.other_files.insert(to.to_owned(), data);
+        }
+
+        for files in self.tags.tags.values_mut() {
+            if files.remove(from) {
+                files.insert(to.to_owned());
+            }
+        }
+
+        if let Some(md5) = self.stored_md5.remove(from) {
+            self.stored_md5.insert(to.to_owned(), md5);
+        }
+
+        if let Some(path) = self.file_order.iter_mut().find(|path| *path == from) {
+            *path = to.to_owned();
+        }
+
+        let updated = self.update_data_file_references(from, to);
+        if updated > 0 {
+            debug!("Updated {} data files referring to '{}'.", updated, from);
+        }
+
+        debug!("Moved '{}' to '{}'.", from, to);
+
+        Ok(())
+    }
+
+    /// Paths of the files whose contents don't match the md5 hash stored in the file table,
+    /// sorted. Files with a zeroed hash are skipped, because Dungeondraft does not fill them in.
+    pub fn verify_checksums(&self) -> Vec<String> {
+        let mut mismatches: Vec<String> = self
+            .object_files
+            .iter()
+            .chain(self.other_files.iter())
+            .filter(|(path, data)| match self.stored_md5.get(*path) {
+                Some(md5) => *md5 != [0; MD5_BYTES] && *md5 != md5_hash(data),
+                None => false,
+            })
+            .map(|(path, _)| path.clone())
+            .collect();
+        mismatches.sort();
+
+        mismatches
+    }
+}
+
+/// Rounds the offset up to the next multiple of `alignment`.
+fn align_offset(offset: usize, alignment: usize) -> usize {
+    match offset % alignment {
+        0 => offset,
+        remainder => offset + alignment - remainder,
+    }
+}
+
+/// Key in the metadata file under which some nonstandard packs store their tags,
+/// laid out like the tags file.
+pub const EMBEDDED_TAGS_KEY: &str = "tags";
+
+/// Removes the tags stored in the metadata file, if there are any, see `EMBEDDED_TAGS_KEY`.
+/// Only an object with `tags` or `sets` in it is taken to be tags. Any other value, or one
+/// that can't be read as tags, is an unrelated field, and is kept with a warning.
+pub(crate) fn take_embedded_tags(meta: &mut PackMeta) -> Option<Tags> {
+    let embedded = meta.extra.get(EMBEDDED_TAGS_KEY)?;
+
+    let tags = match embedded {
+        JsonValue::Object(fields) if fields.contains_key("tags") || fields.contains_key("sets") => {
+            json5::to_string(embedded)
+                .map_err(anyhow::Error::from)
+                .and_then(|json| parse_tags_file(json.into_bytes()))
+                .context("it can't be read as tags")
+        }
+        _ => Err(anyhow!("it is not an object with tags and sets")),
+    };
+
+    match tags {
+        Ok(tags) => {
+            info!("Using the tags stored in the metadata file.");
+            meta.extra.remove(EMBEDDED_TAGS_KEY);
+            Some(tags)
+        }
+        Err(e) => {
+            warn!(
+                "The metadata file has a '{}' field, but {:#}. Keeping it as an unknown field.",
+                EMBEDDED_TAGS_KEY, e
+            );
+            None
+        }
+    }
+}
+
+/// Some editors start utf-8 files with this, which json parsers don't accept.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Adds the encoding of the json file to `json_encodings` if it is not clean.
+pub(crate) fn record_json_encoding(
+    json_encodings: &mut BTreeMap<String, JsonEncoding>,
+    path: &str,
+    file_data: &[u8],
+) {
+    let encoding = JsonEncoding::of(file_data);
+    if !encoding.is_clean() {
+        json_encodings.insert(path.to_owned(), encoding);
+    }
+}
+
+fn without_bom(mut file_data: Vec<u8>) -> Vec<u8> {
+    if file_data.starts_with(UTF8_BOM) {
+        file_data.drain(..UTF8_BOM.len());
+    }
+    file_data
+}
+
+pub(crate) fn parse_meta_file(file_data: Vec<u8>) -> anyhow::Result<PackMeta> {
+    let data_string = String::from_utf8(without_bom(file_data))?;
+
+    match json5::from_str(&data_string) {
+        Ok(meta) => Ok(meta),
+        Err(e) => {
+            display_file_as_info(&data_string);
+            bail!("Could not parse pack metadata file:\n{}", e)
+        }
+    }
+}
+
+pub(crate) fn parse_tags_file(file_data: Vec<u8>) -> anyhow::Result<Tags> {
+    let data_string = String::from_utf8(without_bom(file_data))?;
+
+    match json5::from_str(&data_string) {
+        Ok(tags) => Ok(tags),
+        Err(e) => {
+            display_file_as_info(&data_string);
+            bail!("Could not parse object tags file:\n{}", e)
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashSet;
+    use std::collections::{BTreeMap, HashSet};
     use std::io::{Cursor, Write};
     use std::iter::FromIterator;
     use std::path::PathBuf;
@@ -288,7 +1367,7 @@ mod test {
         let mut cursor = Cursor::new(data);
         let file = FileMetaData::from_read(&mut cursor).unwrap();
 
-        assert_eq!(file.path, "test/bla.txt");
+        assert_eq!(file.path, path);
         assert_eq!(file.offset, offset as u64);
         assert_eq!(file.size, size as usize);
         assert_eq!(file.md5, md5);
@@ -301,6 +1380,39 @@ mod test {
         assert!(!is_root_json_file(&PathBuf::from("8UWKyQPf.txt")));
     }
 
+    #[test]
+    fn test_strip_pack_prefix() {
+        let id = Some("X3DLFK");
+
+        assert_eq!(
+            strip_pack_prefix("res://packs/X3DLFK/textures/a.png", id),
+            "textures/a.png"
+        );
+        assert_eq!(
+            strip_pack_prefix("res://packs/X3DLFK.json", id),
+            "X3DLFK.json"
+        );
+        assert_eq!(strip_pack_prefix("res://packs/X3DLFK", id), "");
+        // Without `packs/`.
+        assert_eq!(
+            strip_pack_prefix("res://X3DLFK/textures/a.png", id),
+            "textures/a.png"
+        );
+        assert_eq!(
+            strip_pack_prefix("res://textures/a.png", None),
+            "textures/a.png"
+        );
+        // First directory is not the pack id.
+        assert_eq!(
+            strip_pack_prefix("res://packs/textures/a.png", id),
+            "textures/a.png"
+        );
+        assert_eq!(
+            strip_pack_prefix("res://packs/other/textures/a.png", id),
+            "other/textures/a.png"
+        );
+    }
+
     #[test]
     fn test_clean_tags() {
         let rock_file = "textures/objects/rock.png".to_string();
@@ -324,7 +1436,14 @@ mod test {
 
         pack.object_files.insert(rock_file.clone(), vec![]);
 
-        pack.clean_tags();
+        let report = pack.clean_tags();
+
+        assert_eq!(report.removed_tags, vec!["empty".to_string()]);
+        assert_eq!(report.removed_sets, vec!["empty", "will_be_empty"]);
+        assert_eq!(
+            report.removed_references,
+            BTreeMap::from([("rocks".to_string(), vec!["does_not_exist.jpg".to_string()])])
+        );
 
         assert!(!pack.tags.tags.contains_key("empty"));
         assert!(pack.tags.tags.contains_key("rocks"));
@@ -341,9 +1460,242 @@ mod test {
         assert!(one_tag_set.contains("rocks"));
     }
 
+    #[test]
+    fn test_clean_tags_is_idempotent() {
+        let mut pack = new_empty_pack();
+        pack.object_files
+            .insert("textures/objects/rock.png".to_string(), vec![]);
+        pack.tags.tags.insert(
+            "rocks".to_string(),
+            HashSet::from_iter(vec!["textures/objects/rock.png".to_string()]),
+        );
+        // Only becomes empty once its file is removed, and then empties the set.
+        pack.tags.tags.insert(
+            "gone".to_string(),
+            HashSet::from_iter(vec!["textures/objects/gone.png".to_string()]),
+        );
+        pack.tags.sets.insert(
+            "only_gone".to_string(),
+            HashSet::from_iter(vec!["gone".to_string()]),
+        );
+        pack.tags.sets.insert(
+            "mixed".to_string(),
+            HashSet::from_iter(vec!["gone".to_string(), "rocks".to_string()]),
+        );
+
+        let first_report = pack.clean_tags();
+        assert_eq!(first_report.removed_tags, vec!["gone"]);
+        assert_eq!(first_report.removed_sets, vec!["only_gone"]);
+        let cleaned_tags = pack.tags.clone();
+
+        let second_report = pack.clean_tags();
+
+        assert_eq!(pack.tags, cleaned_tags);
+        assert_eq!(second_report, CleanReport::default());
+    }
+
+    #[test]
+    fn test_clean_tags_keep_external_refs() {
+        let mut pack = new_empty_pack();
+        pack.object_files
+            .insert("textures/objects/rock.png".to_string(), vec![]);
+        pack.tags.tags.insert(
+            "rocks".to_string(),
+            HashSet::from_iter(vec![
+                "textures/objects/rock.png".to_string(),
+                "textures/objects/companion/boulder.png".to_string(),
+                "textures/objects/boulder".to_string(),
+                "textures\\objects\\pebble.png".to_string(),
+            ]),
+        );
+
+        let options = CleanOptions {
+            keep_external_refs: true,
+        };
+        let report = pack.clean_tags_with_options(&options);
+
+        assert_eq!(
+            pack.tags.tags["rocks"],
+            HashSet::from_iter(vec![
+                "textures/objects/rock.png".to_string(),
+                "textures/objects/companion/boulder.png".to_string(),
+            ])
+        );
+        assert_eq!(
+            report.kept_references["rocks"],
+            vec!["textures/objects/companion/boulder.png"]
+        );
+        assert_eq!(
+            report.removed_references["rocks"],
+            vec!["textures/objects/boulder", "textures\\objects\\pebble.png"]
+        );
+    }
+
+    #[test]
+    fn test_tag_byte_size() {
+        let mut pack = new_empty_pack();
+        pack.object_files
+            .insert("textures/objects/oak.png".to_string(), vec![0; 100]);
+        pack.object_files
+            .insert("textures/objects/pine.png".to_string(), vec![0; 20]);
+        pack.object_files
+            .insert("textures/objects/rock.png".to_string(), vec![0; 5]);
+        pack.tags.tags.insert(
+            "trees".to_string(),
+            HashSet::from_iter(vec![
+                "textures/objects/oak.png".to_string(),
+                "textures/objects/pine.png".to_string(),
+                "textures/objects/gone.png".to_string(),
+            ]),
+        );
+
+        assert_eq!(pack.tag_byte_size("trees"), Some(120));
+        assert_eq!(pack.tag_byte_size("rocks"), None);
+    }
+
+    #[test]
+    fn test_sets_containing_tag() {
+        let mut pack = new_empty_pack();
+        pack.tags.sets.insert(
+            "Nature".to_string(),
+            HashSet::from_iter(vec!["trees".to_string(), "rocks".to_string()]),
+        );
+        pack.tags.sets.insert(
+            "Forest".to_string(),
+            HashSet::from_iter(vec!["trees".to_string()]),
+        );
+        pack.tags.sets.insert(
+            "Dungeon".to_string(),
+            HashSet::from_iter(vec!["rocks".to_string()]),
+        );
+
+        assert_eq!(pack.sets_containing_tag("trees"), vec!["Forest", "Nature"]);
+        assert!(pack.sets_containing_tag("barrels").is_empty());
+    }
+
+    #[test]
+    fn test_prefix_tags() {
+        let mut pack = new_empty_pack();
+        pack.tags.tags.insert(
+            "Rocks".to_string(),
+            HashSet::from_iter(vec!["textures/objects/rock.png".to_string()]),
+        );
+        pack.tags.tags.insert("Trees".to_string(), HashSet::new());
+        pack.tags.sets.insert(
+            "Nature".to_string(),
+            HashSet::from_iter(vec!["Rocks".to_string(), "Trees".to_string()]),
+        );
+
+        pack.prefix_tags("Dungeon ");
+
+        assert_eq!(pack.tags.tags.len(), 2);
+        assert!(pack
+            .get_files_in_tag("Dungeon Rocks")
+            .unwrap()
+            .contains("textures/objects/rock.png"));
+        assert!(pack.get_files_in_tag("Rocks").is_none());
+        assert_eq!(
+            pack.tags.sets["Nature"],
+            HashSet::from_iter(vec![
+                "Dungeon Rocks".to_string(),
+                "Dungeon Trees".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn exclude_tag_removes_exclusive_objects() {
+        let mut pack = new_empty_pack();
+        for path in &["a.png", "b.png", "shared.png"] {
+            pack.object_files
+                .insert(format!("textures/objects/{}", path), vec![1]);
+        }
+        pack.tags.tags.insert(
+            "Excluded".to_string(),
+            HashSet::from_iter(vec![
+                "textures/objects/a.png".to_string(),
+                "textures/objects/shared.png".to_string(),
+            ]),
+        );
+        pack.tags.tags.insert(
+            "Kept".to_string(),
+            HashSet::from_iter(vec![
+                "textures/objects/b.png".to_string(),
+                "textures/objects/shared.png".to_string(),
+            ]),
+        );
+        pack.tags.sets.insert(
+            "Set".to_string(),
+            HashSet::from_iter(vec!["Excluded".to_string(), "Kept".to_string()]),
+        );
+
+        assert_eq!(pack.exclude_tag("Excluded"), 1);
+
+        assert!(!pack.object_files.contains_key("textures/objects/a.png"));
+        assert!(pack.object_files.contains_key("textures/objects/b.png"));
+        assert!(pack
+            .object_files
+            .contains_key("textures/objects/shared.png"));
+        assert!(pack.get_files_in_tag("Excluded").is_none());
+        assert_eq!(
+            pack.tags.sets["Set"],
+            HashSet::from_iter(vec!["Kept".to_string()])
+        );
+
+        assert_eq!(pack.exclude_tag("Missing"), 0);
+    }
+
+    #[test]
+    fn take_embedded_tags_only_takes_tags() {
+        let mut meta: PackMeta = json5::from_str(
+            r#"{"name": "a", "id": "ABCDEFGH", "version": "1", "author": "me",
+            "tags": {"tags": {"Rocks": ["textures/objects/rock.png"]}, "sets": {}}}"#,
+        )
+        .unwrap();
+        let tags = take_embedded_tags(&mut meta).unwrap();
+        assert!(tags.tags["Rocks"].contains("textures/objects/rock.png"));
+        assert!(meta.extra.is_empty());
+
+        let unrelated_values = [
+            r#"["x", null, true, 1.5]"#,
+            r#""rocks, stones""#,
+            r#"{"genre": "fantasy"}"#,
+            r#"{"tags": ["Rocks"]}"#,
+        ];
+        for value in unrelated_values.iter() {
+            let mut meta: PackMeta = json5::from_str(&format!(
+                r#"{{"name": "a", "id": "ABCDEFGH", "version": "1", "author": "me", "tags": {}}}"#,
+                value
+            ))
+            .unwrap();
+
+            assert!(take_embedded_tags(&mut meta).is_none(), "{}", value);
+            assert!(meta.extra.contains_key(EMBEDDED_TAGS_KEY), "{}", value);
+        }
+    }
+
+    #[test]
+    fn suggested_filename() {
+        let mut pack = new_empty_pack();
+        pack.meta.id = "ABCDEFGH".to_string();
+
+        pack.meta.name = "My Cool Pack!".to_string();
+        assert_eq!(pack.suggested_filename(), "My_Cool_Pack.dungeondraft_pack");
+
+        pack.meta.name = " Rocks & Stones / v2 ".to_string();
+        assert_eq!(
+            pack.suggested_filename(),
+            "Rocks_Stones_v2.dungeondraft_pack"
+        );
+
+        pack.meta.name = "???".to_string();
+        assert_eq!(pack.suggested_filename(), "ABCDEFGH.dungeondraft_pack");
+    }
+
     fn new_empty_pack() -> AssetPack {
         AssetPack {
             godot_version: GodotVersion::new(0, 0, 0, 0),
+            layout: Default::default(),
             meta: PackMeta {
                 name: "".to_string(),
                 id: "".to_string(),
@@ -355,6 +1707,7 @@ mod test {
                     min_saturation: 0.0,
                     red_tolerance: 0.0,
                 }),
+                extra: Default::default(),
             },
             tags: Tags {
                 tags: Default::default(),
@@ -362,6 +1715,10 @@ mod test {
             },
             object_files: Default::default(),
             other_files: Default::default(),
+            stored_md5: Default::default(),
+            file_order: vec![],
+            json_encodings: Default::default(),
+            trailer: vec![],
         }
     }
 }
@@ -0,0 +1,77 @@
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::write_options::WriteOptions;
+
+/// What writing a pack would produce, see `AssetPack::change_set`.
+/// Shown instead of writing the pack when previewing an operation.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct ChangeSet {
+    /// Number of files in the pack, including the metadata and tags files.
+    pub files: usize,
+    /// Size of the pack file, in bytes.
+    pub bytes: u64,
+}
+
+impl Display for ChangeSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} files, {} bytes", self.files, self.bytes)
+    }
+}
+
+impl AssetPack {
+    /// Serializes the pack without storing it, to find out what
+    /// `to_write_with_options` would write.
+    pub fn change_set(&self, options: &WriteOptions) -> anyhow::Result<ChangeSet> {
+        let mut counter = ByteCounter(0);
+        self.to_write_with_options(&mut counter, options)?;
+
+        // The root json file and `pack.json`, and the tags file unless it is embedded.
+        let json_files = if options.embed_tags { 2 } else { 3 };
+
+        Ok(ChangeSet {
+            files: self.object_files.len() + self.other_files.len() + json_files,
+            bytes: counter.0,
+        })
+    }
+}
+
+/// Discards everything written to it, only counting the bytes.
+struct ByteCounter(u64);
+
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+    use crate::asset_pack::write_options::WriteOptions;
+
+    #[test]
+    fn change_set_matches_written_pack() {
+        let pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+
+        let mut written_pack = vec![];
+        pack.to_write(&mut written_pack).unwrap();
+
+        let change_set = pack.change_set(&WriteOptions::default()).unwrap();
+        assert_eq!(change_set.bytes, written_pack.len() as u64);
+        assert_eq!(change_set.files, 5);
+        assert_eq!(
+            change_set.to_string(),
+            format!("5 files, {} bytes", written_pack.len())
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use crate::asset_pack::path_utils::OBJECT_FILES_PREFIX;
+
+/// Decides which files of a pack are object files, when reading it.
+/// Only object files can be tagged, so tags referring to other files are cleaned away.
+#[derive(Debug, Clone)]
+pub struct ClassificationRules {
+    /// Files whose path starts with one of these are object files.
+    pub object_prefixes: Vec<String>,
+}
+
+impl Default for ClassificationRules {
+    fn default() -> Self {
+        ClassificationRules {
+            object_prefixes: vec![OBJECT_FILES_PREFIX.to_owned()],
+        }
+    }
+}
+
+impl ClassificationRules {
+    /// The default rules, with extra object file prefixes, e.g. `textures/materials/`.
+    pub fn with_object_prefixes<I: IntoIterator<Item = String>>(prefixes: I) -> Self {
+        let mut rules = Self::default();
+        rules.object_prefixes.extend(prefixes);
+        rules
+    }
+
+    pub fn is_object_file(&self, path: &str) -> bool {
+        self.object_prefixes
+            .iter()
+            .any(|prefix| path.starts_with(prefix.as_str()))
+    }
+}
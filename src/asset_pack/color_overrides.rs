@@ -1,9 +1,21 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct ColorOverrides {
     pub enabled: bool,
     pub min_redness: f32,
     pub min_saturation: f32,
     pub red_tolerance: f32,
 }
+
+/// The values Dungeondraft puts in the `pack.json` of a new pack.
+impl Default for ColorOverrides {
+    fn default() -> Self {
+        ColorOverrides {
+            enabled: false,
+            min_redness: 0.1,
+            min_saturation: 0.0,
+            red_tolerance: 0.04,
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt::{Display, Formatter};
+
+use anyhow::{anyhow, bail, Context};
+use log::{debug, warn};
+
+use crate::asset_pack::deflate::{gzip_decompress, zlib_decompress};
+use crate::asset_pack::file_meta_data::FileMetaData;
+
+/// A compression format, recognized by the magic bytes a file starts with.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Compression {
+    Zstd,
+    Gzip,
+    Zlib,
+}
+
+impl Display for Compression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Compression::Zstd => write!(f, "zstd"),
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Zlib => write!(f, "zlib"),
+        }
+    }
+}
+
+/// The compression format the data starts with, by its magic bytes.
+/// Raw deflate streams have no header, so they can't be recognized.
+pub fn detect_compression(data: &[u8]) -> Option<Compression> {
+    match data {
+        [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Compression::Zstd),
+        [0x1f, 0x8b, 0x08, ..] => Some(Compression::Gzip),
+        // Deflate with a window of at most 32 KiB, and a header checksum.
+        [cmf, flg, ..]
+            if cmf & 0x0f == 8
+                && cmf >> 4 <= 7
+                && (*cmf as u16 * 256 + *flg as u16).is_multiple_of(31) =>
+        {
+            Some(Compression::Zlib)
+        }
+        _ => None,
+    }
+}
+
+/// Decompresses a file as it is stored in a pack, or returns `None` if its entry does not have
+/// `FileMetaData::FLAG_COMPRESSED`. Files written with `WriteOptions::compress` are zlib
+/// streams, but gzip streams are read too.
+///
+/// Files without the flag are kept as they are, even if they look compressed: a `.gz` file
+/// in a pack is meant to stay one, and a zlib header is easily matched by chance.
+/// They only get a warning.
+pub(crate) fn decompress_file(meta: &FileMetaData, data: &[u8]) -> anyhow::Result<Option<Vec<u8>>> {
+    let compression = detect_compression(data);
+
+    if !meta.is_compressed() {
+        if let Some(compression) = compression {
+            warn!(
+                "'{}' looks {}-compressed, but it is not marked as compressed. \
+                Keeping it as it is.",
+                meta.path, compression
+            );
+        }
+        return Ok(None);
+    }
+
+    let decompressed = match compression {
+        Some(Compression::Zlib) => zlib_decompress(data),
+        Some(Compression::Gzip) => gzip_decompress(data),
+        Some(Compression::Zstd) => Err(anyhow!("zstd is not supported")),
+        None => bail!(
+            "'{}' is marked as compressed, but it is not a zlib or gzip stream",
+            meta.path
+        ),
+    }
+    .context(format!("Could not decompress '{}'", meta.path))?;
+
+    debug!("Decompressed '{}'.", meta.path);
+    Ok(Some(decompressed))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::asset_pack::compression::{decompress_file, detect_compression, Compression};
+    use crate::asset_pack::deflate::zlib_compress;
+    use crate::asset_pack::file_meta_data::FileMetaData;
+
+    #[test]
+    fn detect_compressed_payloads() {
+        assert_eq!(
+            detect_compression(&[0x28, 0xb5, 0x2f, 0xfd, 0x24, 0x05]),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(
+            detect_compression(&[0x1f, 0x8b, 0x08, 0x00, 0x00]),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            detect_compression(&[0x78, 0x9c, 0x4b, 0x4c]),
+            Some(Compression::Zlib)
+        );
+        assert_eq!(
+            detect_compression(&[0x78, 0xda, 0x4b, 0x4c]),
+            Some(Compression::Zlib)
+        );
+
+        assert_eq!(
+            detect_compression(&[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a]),
+            None
+        );
+        assert_eq!(detect_compression(b"{\"path\": \"textures\"}"), None);
+        assert_eq!(detect_compression(b"xy"), None);
+        assert_eq!(detect_compression(&[]), None);
+    }
+
+    #[test]
+    fn decompress_only_what_is_marked_compressed() {
+        let mut meta = FileMetaData::new("textures/objects/rock.png".to_string(), 0);
+        let rock = b"rock ".repeat(100);
+        let compressed_rock = zlib_compress(&rock);
+
+        // Looks compressed, but is kept as it is without the flag.
+        assert_eq!(decompress_file(&meta, &compressed_rock).unwrap(), None);
+        assert_eq!(decompress_file(&meta, &rock).unwrap(), None);
+        assert_eq!(decompress_file(&meta, b"x lines").unwrap(), None);
+
+        meta.flags = Some(FileMetaData::FLAG_COMPRESSED);
+        assert_eq!(
+            decompress_file(&meta, &compressed_rock).unwrap(),
+            Some(rock.clone())
+        );
+        assert!(decompress_file(&meta, &rock).is_err());
+        assert!(decompress_file(&meta, b"x lines").is_err());
+    }
+}
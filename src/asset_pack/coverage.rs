@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::path_utils::object_folder;
+use crate::asset_pack::utils::pretty_json;
+
+/// Row name of the object files directly in `textures/objects/`.
+pub const ROOT_OBJECT_FOLDER: &str = ".";
+
+/// How many objects in each folder under `textures/objects/` carry each tag,
+/// see `AssetPack::tag_coverage`. Meant to be loaded in a spreadsheet to spot
+/// folders with few tagged objects.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize)]
+pub struct TagCoverage {
+    /// The row names, sorted.
+    pub folders: Vec<String>,
+    /// The column names, sorted.
+    pub tags: Vec<String>,
+    /// Number of objects in each folder, tagged or not.
+    pub objects: Vec<usize>,
+    /// `counts[row][column]` is the number of objects in `folders[row]` in `tags[column]`.
+    pub counts: Vec<Vec<usize>>,
+}
+
+impl TagCoverage {
+    pub fn count(&self, folder: &str, tag: &str) -> Option<usize> {
+        let row = self.folders.iter().position(|f| f == folder)?;
+        let column = self.tags.iter().position(|t| t == tag)?;
+        Some(self.counts[row][column])
+    }
+
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(pretty_json(&json5::to_string(self)?))
+    }
+
+    /// One row per folder, with the folder, its number of objects, and a column per tag.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+
+        let header: Vec<&str> = ["folder", "objects"]
+            .iter()
+            .copied()
+            .chain(self.tags.iter().map(String::as_str))
+            .collect();
+        push_csv_row(&mut csv, &header);
+
+        for ((folder, objects), counts) in self
+            .folders
+            .iter()
+            .zip(self.objects.iter())
+            .zip(self.counts.iter())
+        {
+            let row: Vec<String> = [folder.clone(), objects.to_string()]
+                .iter()
+                .cloned()
+                .chain(counts.iter().map(usize::to_string))
+                .collect();
+            let row: Vec<&str> = row.iter().map(String::as_str).collect();
+            push_csv_row(&mut csv, &row);
+        }
+
+        csv
+    }
+}
+
+fn push_csv_row(csv: &mut String, fields: &[&str]) {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+
+    csv.push_str(&fields.join(","));
+    csv.push('\n');
+}
+
+impl AssetPack {
+    /// Counts the tagged objects per folder under `textures/objects/` and tag.
+    /// Every tag is a column, also tags without objects, and every folder with objects a row.
+    pub fn tag_coverage(&self) -> TagCoverage {
+        let folder_of = |path: &str| object_folder(path).unwrap_or(ROOT_OBJECT_FOLDER).to_owned();
+
+        let mut objects: BTreeMap<String, usize> = BTreeMap::new();
+        for path in self.object_files.keys() {
+            *objects.entry(folder_of(path)).or_default() += 1;
+        }
+
+        let folders: Vec<String> = objects.keys().cloned().collect();
+        let mut tags: Vec<String> = self.tags.tags.keys().cloned().collect();
+        tags.sort();
+
+        let mut counts = vec![vec![0; tags.len()]; folders.len()];
+
+        for (column, tag) in tags.iter().enumerate() {
+            for path in self.tags.tags[tag].iter() {
+                if !self.object_files.contains_key(path) {
+                    continue;
+                }
+
+                let folder = folder_of(path);
+                if let Ok(row) = folders.binary_search(&folder) {
+                    counts[row][column] += 1;
+                }
+            }
+        }
+
+        TagCoverage {
+            folders,
+            tags,
+            objects: objects.into_values().collect(),
+            counts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn coverage_grid_of_example_pack() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        for name in ["chair", "table"].iter() {
+            let path = format!("textures/objects/furniture/{}.png", name);
+            pack.object_files.insert(path.clone(), vec![1, 2, 3]);
+            pack.tags
+                .tags
+                .entry("Furniture".to_string())
+                .or_default()
+                .insert(path);
+        }
+        pack.tags
+            .tags
+            .get_mut("MyTag")
+            .unwrap()
+            .insert("textures/objects/furniture/chair.png".to_string());
+
+        let coverage = pack.tag_coverage();
+
+        assert_eq!(coverage.folders, vec![".", "furniture"]);
+        assert_eq!(coverage.tags, vec!["Colorable", "Furniture", "MyTag"]);
+        assert_eq!(coverage.objects, vec![1, 2]);
+        assert_eq!(coverage.counts.len(), 2);
+        assert!(coverage.counts.iter().all(|row| row.len() == 3));
+        assert_eq!(coverage.count("furniture", "Furniture"), Some(2));
+        assert_eq!(coverage.count("furniture", "MyTag"), Some(1));
+        assert_eq!(coverage.count(".", "MyTag"), Some(1));
+        assert_eq!(coverage.count(".", "Colorable"), Some(0));
+
+        assert_eq!(
+            coverage.to_csv(),
+            "folder,objects,Colorable,Furniture,MyTag\n.,1,0,0,1\nfurniture,2,0,2,1\n"
+        );
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use anyhow::bail;
+use log::{debug, info, warn};
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::path_utils::*;
+
+/// Every string value found anywhere in a json document, no matter how deeply nested.
+/// Used to find the texture paths the Dungeondraft data files refer to,
+/// without having to model each of their formats.
+#[derive(Debug, Default)]
+pub struct JsonStrings(pub Vec<String>);
+
+impl<'de> Deserialize<'de> for JsonStrings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut strings = vec![];
+        deserializer.deserialize_any(StringCollector(&mut strings))?;
+        Ok(JsonStrings(strings))
+    }
+}
+
+struct StringCollector<'a>(&'a mut Vec<String>);
+
+impl<'de> serde::de::DeserializeSeed<'de> for StringCollector<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for StringCollector<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "any json value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<(), E> {
+        self.0.push(value.to_owned());
+        Ok(())
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while seq
+            .next_element_seed(StringCollector(&mut *self.0))?
+            .is_some()
+        {}
+        Ok(())
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while map.next_key::<serde::de::IgnoredAny>()?.is_some() {
+            map.next_value_seed(StringCollector(&mut *self.0))?;
+        }
+        Ok(())
+    }
+}
+
+impl AssetPack {
+    /// Returns the textures of the categories that are defined through data files
+    /// (walls, tilesets and paths), that none of the data files refer to.
+    /// Textures of other categories are picked up by Dungeondraft based on their directory alone,
+    /// so they are never reported.
+    /// Fails if any of the data files can't be parsed, because then it is unknown which
+    /// textures it refers to.
+    pub fn find_unreferenced_files(&self) -> anyhow::Result<Vec<String>> {
+        let referenced = self.data_file_references()?;
+
+        let mut unreferenced: Vec<String> = self
+            .other_files
+            .keys()
+            .filter(|path| {
+                texture_category(path).is_some_and(|c| DATA_DEFINED_CATEGORIES.contains(&c))
+            })
+            .filter(|path| !referenced.contains(path.as_str()))
+            .cloned()
+            .collect();
+
+        unreferenced.sort();
+        Ok(unreferenced)
+    }
+
+    /// Removes the files returned by `find_unreferenced_files`.
+    /// Returns the number of files removed. Nothing is removed if a data file can't be parsed.
+    pub fn remove_unreferenced_files(&mut self) -> anyhow::Result<usize> {
+        let unreferenced = self.find_unreferenced_files()?;
+
+        for path in unreferenced.iter() {
+            debug!(
+                "Removing file '{}' because no data file refers to it.",
+                path
+            );
+            self.remove_file(path);
+        }
+
+        info!("Removed {} unreferenced files.", unreferenced.len());
+
+        Ok(unreferenced.len())
+    }
+
+    /// Paths of the wall, tileset and path data files that refer to the file at `path`, sorted.
+    pub fn data_files_referring_to(&self, path: &str) -> Vec<String> {
+        let mut data_files: Vec<String> = self
+            .other_files
+            .keys()
+            .filter(|data_path| is_texture_data_file(data_path))
+            .filter(|data_path| {
+                self.parse_data_file(data_path).is_some_and(|strings| {
+                    strings.0.iter().any(|value| self.refers_to(value, path))
+                })
+            })
+            .cloned()
+            .collect();
+
+        data_files.sort();
+        data_files
+    }
+
+    /// Points the references in the data files from the file at `from` to `to`,
+    /// so moving a wall, tileset or path texture does not break its data file.
+    /// Returns the number of data files changed.
+    pub(crate) fn update_data_file_references(&mut self, from: &str, to: &str) -> usize {
+        let data_files = self.data_files_referring_to(from);
+
+        let resource_path = |path: &str| {
+            format!(
+                "{}{}{}/{}",
+                RESOURCE_PATH_PREFIX, ASSET_PACK_PREFIX, self.meta.id, path
+            )
+        };
+        // Json may escape the slashes, so both ways of writing the path are replaced.
+        let quoted = |path: &str| format!("\"{}\"", path);
+        let escaped = |path: &str| quoted(&path.replace('/', "\\/"));
+        let replacements = [
+            (quoted(&resource_path(from)), quoted(&resource_path(to))),
+            (escaped(&resource_path(from)), escaped(&resource_path(to))),
+            (quoted(from), quoted(to)),
+            (escaped(from), escaped(to)),
+        ];
+
+        for data_path in data_files.iter() {
+            let data = self.other_files.get_mut(data_path).unwrap();
+            let mut text = String::from_utf8_lossy(data).into_owned();
+
+            for (old, new) in replacements.iter() {
+                text = text.replace(old.as_str(), new);
+            }
+
+            debug!("Pointed '{}' from '{}' to '{}'.", data_path, from, to);
+            *data = text.into_bytes();
+        }
+
+        data_files.len()
+    }
+
+    /// The `data/*.dungeondraft_*` files that are not valid json, with the parse error,
+    /// sorted by path. Dungeondraft can't load a pack with such a file.
+    pub fn find_invalid_data_files(&self) -> Vec<(String, String)> {
+        let mut invalid: Vec<(String, String)> = self
+            .other_files
+            .iter()
+            .filter(|(path, _)| is_dungeondraft_data_file(path))
+            .filter_map(|(path, data)| {
+                parse_json_strings(data)
+                    .err()
+                    .map(|e| (path.clone(), e.to_string()))
+            })
+            .collect();
+
+        invalid.sort();
+        invalid
+    }
+
+    fn refers_to(&self, value: &str, path: &str) -> bool {
+        strip_pack_prefix(value, Some(&self.meta.id)) == path
+    }
+
+    fn parse_data_file(&self, path: &str) -> Option<JsonStrings> {
+        let data = self.other_files.get(path)?;
+
+        match parse_json_strings(data) {
+            Ok(strings) => Some(strings),
+            Err(e) => {
+                warn!("Could not parse data file '{}':\n{}", path, e);
+                None
+            }
+        }
+    }
+
+    /// All pack-relative paths referred to by the wall, tileset and path data files.
+    /// Fails with the data files that can't be parsed, if there are any.
+    fn data_file_references(&self) -> anyhow::Result<HashSet<String>> {
+        let mut references = HashSet::new();
+        let mut unparsable = vec![];
+
+        for (path, data) in self.other_files.iter() {
+            if !is_texture_data_file(path) {
+                continue;
+            }
+
+            match parse_json_strings(data) {
+                Ok(strings) => {
+                    for value in strings.0 {
+                        references
+                            .insert(strip_pack_prefix(&value, Some(&self.meta.id)).to_owned());
+                    }
+                }
+                Err(e) => unparsable.push(format!("'{}': {}", path, e)),
+            }
+        }
+
+        if !unparsable.is_empty() {
+            unparsable.sort();
+            bail!(
+                "Can't tell which textures are referenced, these data files can't be parsed:\n{}",
+                unparsable.join("\n")
+            );
+        }
+
+        Ok(references)
+    }
+}
+
+fn parse_json_strings(data: &[u8]) -> anyhow::Result<JsonStrings> {
+    let text = std::str::from_utf8(data)?;
+    Ok(json5::from_str::<JsonStrings>(text)?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn find_unreferenced_wall_texture() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.other_files.insert(
+            "data/walls/stone.dungeondraft_wall".to_string(),
+            br#"{"path": "res://packs/12345678/textures/walls/stone.png", "color": "ffffff"}"#
+                .to_vec(),
+        );
+        pack.other_files
+            .insert("textures/walls/stone.png".to_string(), vec![1]);
+        pack.other_files
+            .insert("textures/walls/orphan.png".to_string(), vec![2]);
+        pack.stored_md5
+            .insert("textures/walls/orphan.png".to_string(), [0; 16]);
+        pack.file_order
+            .push("textures/walls/orphan.png".to_string());
+
+        assert_eq!(
+            pack.find_unreferenced_files().unwrap(),
+            vec!["textures/walls/orphan.png".to_string()]
+        );
+
+        assert_eq!(pack.remove_unreferenced_files().unwrap(), 1);
+        assert!(pack.other_files.contains_key("textures/walls/stone.png"));
+        assert!(!pack.other_files.contains_key("textures/walls/orphan.png"));
+        assert!(!pack.stored_md5.contains_key("textures/walls/orphan.png"));
+        assert!(!pack
+            .file_order
+            .contains(&"textures/walls/orphan.png".to_string()));
+    }
+
+    #[test]
+    fn unparsable_data_file_prevents_pruning() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.other_files.insert(
+            "data/walls/broken.dungeondraft_wall".to_string(),
+            br#"{"path": "res://packs/12345678/textures/walls/stone.png", "#.to_vec(),
+        );
+        pack.other_files
+            .insert("textures/walls/stone.png".to_string(), vec![1]);
+
+        assert!(pack.find_unreferenced_files().is_err());
+        let error = pack.remove_unreferenced_files().unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("'data/walls/broken.dungeondraft_wall'"));
+        assert!(pack.other_files.contains_key("textures/walls/stone.png"));
+    }
+
+    #[test]
+    fn move_tileset_texture_updates_data_file() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.other_files.insert(
+            "data/tilesets/grass.dungeondraft_tileset".to_string(),
+            br#"{"path": "res://packs/12345678/textures/tilesets/smart/grass.png", "name": "Grass"}"#
+                .to_vec(),
+        );
+        pack.other_files
+            .insert("textures/tilesets/smart/grass.png".to_string(), vec![1]);
+
+        assert_eq!(
+            pack.data_files_referring_to("textures/tilesets/smart/grass.png"),
+            vec!["data/tilesets/grass.dungeondraft_tileset"]
+        );
+
+        pack.move_file(
+            "textures/tilesets/smart/grass.png",
+            "textures/tilesets/smart/meadow.png",
+        )
+        .unwrap();
+
+        assert_eq!(
+            pack.other_files["data/tilesets/grass.dungeondraft_tileset"],
+            br#"{"path": "res://packs/12345678/textures/tilesets/smart/meadow.png", "name": "Grass"}"#
+                .to_vec()
+        );
+        assert!(pack.find_unreferenced_files().unwrap().is_empty());
+
+        assert_eq!(
+            pack.remove_file("textures/tilesets/smart/meadow.png"),
+            Some(vec![1])
+        );
+        assert_eq!(pack.remove_file("textures/tilesets/smart/meadow.png"), None);
+    }
+
+    #[test]
+    fn malformed_data_file_is_invalid() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        let data_files = [
+            (
+                "data/walls/broken.dungeondraft_wall",
+                br#"{"path": "res://packs/12345678/textures/walls/stone.png", "#.to_vec(),
+            ),
+            (
+                "data/walls/stone.dungeondraft_wall",
+                br#"{"path": "res://packs/12345678/textures/walls/stone.png"}"#.to_vec(),
+            ),
+            (
+                "data/materials/stone.dungeondraft_material",
+                br#"{"colors": ["ffffff", "000000"]}"#.to_vec(),
+            ),
+        ];
+        for (path, data) in data_files.iter() {
+            pack.other_files.insert(path.to_string(), data.clone());
+        }
+
+        let invalid = pack.find_invalid_data_files();
+        assert_eq!(invalid.len(), 1);
+        assert_eq!(invalid[0].0, "data/walls/broken.dungeondraft_wall");
+
+        // Data files are kept as they are, even the broken one.
+        let mut raw_pack = vec![];
+        pack.to_write(&mut raw_pack).unwrap();
+        let read_pack = AssetPack::from_read(&mut Cursor::new(raw_pack)).unwrap();
+        for (path, data) in data_files.iter() {
+            assert_eq!(&read_pack.other_files[*path], data);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use log::{debug, info};
+use std::collections::HashMap;
+
+use crate::asset_pack::asset_pack::AssetPack;
+
+impl AssetPack {
+    /// Groups of object files with exactly the same contents, each sorted by path,
+    /// and the groups sorted by their first path. Files without duplicates are left out.
+    pub fn find_duplicate_objects(&self) -> Vec<Vec<String>> {
+        let mut paths_by_contents: HashMap<&[u8], Vec<String>> = HashMap::new();
+
+        for (path, data) in self.object_files.iter() {
+            paths_by_contents
+                .entry(data.as_slice())
+                .or_default()
+                .push(path.clone());
+        }
+
+        let mut duplicates: Vec<Vec<String>> = paths_by_contents
+            .into_values()
+            .filter(|paths| paths.len() > 1)
+            .map(|mut paths| {
+                paths.sort();
+                paths
+            })
+            .collect();
+        duplicates.sort();
+
+        duplicates
+    }
+
+    /// Keeps only the first path, alphabetically, of every group of identical object files.
+    /// The tags that referred to one of the removed duplicates refer to the kept file instead.
+    /// Returns the number of removed files.
+    pub fn dedupe_objects(&mut self) -> usize {
+        let mut removed = 0;
+
+        for paths in self.find_duplicate_objects() {
+            let (kept, duplicates) = paths.split_first().unwrap();
+
+            for duplicate in duplicates {
+                self.object_files.remove(duplicate);
+                self.stored_md5.remove(duplicate);
+
+                for files in self.tags.tags.values_mut() {
+                    if files.remove(duplicate) {
+                        files.insert(kept.clone());
+                    }
+                }
+
+                debug!("Removed '{}', it is identical to '{}'.", duplicate, kept);
+                removed += 1;
+            }
+        }
+
+        info!("Removed {} duplicate object files.", removed);
+
+        removed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::io::Cursor;
+    use std::iter::FromIterator;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn dedupe_identical_objects() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.object_files
+            .insert("textures/objects/b_copy.png".to_string(), vec![1, 2, 3]);
+        pack.object_files
+            .insert("textures/objects/a_original.png".to_string(), vec![1, 2, 3]);
+        pack.object_files
+            .insert("textures/objects/different.png".to_string(), vec![1, 2, 4]);
+        pack.tags.tags.insert(
+            "Copies".to_string(),
+            HashSet::from_iter(vec![
+                "textures/objects/b_copy.png".to_string(),
+                "textures/objects/different.png".to_string(),
+            ]),
+        );
+
+        assert_eq!(
+            pack.find_duplicate_objects(),
+            vec![vec![
+                "textures/objects/a_original.png".to_string(),
+                "textures/objects/b_copy.png".to_string()
+            ]]
+        );
+
+        assert_eq!(pack.dedupe_objects(), 1);
+
+        assert!(!pack
+            .object_files
+            .contains_key("textures/objects/b_copy.png"));
+        assert!(pack
+            .object_files
+            .contains_key("textures/objects/a_original.png"));
+        assert!(pack
+            .object_files
+            .contains_key("textures/objects/different.png"));
+        assert_eq!(
+            pack.get_files_in_tag("Copies").unwrap(),
+            &HashSet::from_iter(vec![
+                "textures/objects/a_original.png".to_string(),
+                "textures/objects/different.png".to_string()
+            ])
+        );
+
+        assert!(pack.find_duplicate_objects().is_empty());
+    }
+}
@@ -0,0 +1,86 @@
+use std::io::{Read, Write};
+
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+/// Compresses `data` into a zlib stream (RFC 1950), at the default compression level.
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    // Writing to a `Vec` can't fail.
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Decompresses a zlib stream, and checks it against its checksum.
+pub fn zlib_decompress(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    read_all(ZlibDecoder::new(data))
+}
+
+/// Decompresses the first member of a gzip stream (RFC 1952), and checks it against
+/// its checksum and size.
+pub fn gzip_decompress(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    read_all(GzDecoder::new(data))
+}
+
+/// Decompresses a raw deflate stream (RFC 1951), as found in zip archives.
+pub fn inflate(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    read_all(DeflateDecoder::new(data))
+}
+
+fn read_all<R: Read>(mut decoder: R) -> anyhow::Result<Vec<u8>> {
+    let mut output = vec![];
+    decoder.read_to_end(&mut output)?;
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::asset_pack::deflate::{gzip_decompress, inflate, zlib_compress, zlib_decompress};
+
+    #[test]
+    fn zlib_round_trip() {
+        let input = b"rock ".repeat(1000);
+        let mut compressed = zlib_compress(&input);
+        assert!(compressed.len() < 100);
+        assert_eq!(zlib_decompress(&compressed).unwrap(), input);
+
+        let last = compressed.len() - 1;
+        compressed[last] ^= 0xFF;
+        assert!(zlib_decompress(&compressed).is_err());
+        assert!(zlib_decompress(&compressed[..last]).is_err());
+        assert!(zlib_decompress(b"{}").is_err());
+    }
+
+    #[test]
+    fn gzip_with_file_name() {
+        assert_eq!(gzip_decompress(&GZIP_STREAM).unwrap(), b"rock ".repeat(20));
+
+        let mut corrupt = GZIP_STREAM;
+        corrupt[GZIP_STREAM.len() - 5] ^= 0xFF;
+        assert!(gzip_decompress(&corrupt).is_err());
+        assert!(gzip_decompress(&GZIP_STREAM[..8]).is_err());
+    }
+
+    #[test]
+    fn inflate_stored_and_fixed_blocks() {
+        // A stored block that is not the last, then an empty fixed block.
+        let stored = [0x00, 0x03, 0x00, 0xfc, 0xff, b'a', b'b', b'c', 0x03, 0x00];
+        assert_eq!(inflate(&stored).unwrap(), b"abc");
+
+        // `b"hello hello hello hello"` in a fixed code block, as written by zlib.
+        let fixed = [0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x27, 0x01];
+        assert_eq!(inflate(&fixed).unwrap(), b"hello hello hello hello");
+        assert!(inflate(&fixed[..6]).is_err());
+        // Block type 3 does not exist.
+        assert!(inflate(&[0x07]).is_err());
+    }
+
+    /// `b"rock ".repeat(20)` as written by Python's `gzip` module, with the file name `rock.png`.
+    const GZIP_STREAM: [u8; 37] = [
+        0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0x72, 0x6f, 0x63, 0x6b, 0x2e,
+        0x70, 0x6e, 0x67, 0x00, 0x2b, 0xca, 0x4f, 0xce, 0x56, 0x28, 0xa2, 0x2d, 0x01, 0x00, 0x4f,
+        0x08, 0x0e, 0x2c, 0x64, 0x00, 0x00, 0x00,
+    ];
+}
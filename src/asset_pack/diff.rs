@@ -0,0 +1,195 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::asset_pack::asset_pack::AssetPack;
+
+/// What changed between two versions of a pack, see `AssetPack::diff`.
+/// Everything is sorted by path or name.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct PackDiff {
+    pub added_objects: Vec<String>,
+    pub removed_objects: Vec<String>,
+    /// Object files that are in both versions, with different contents.
+    pub changed_objects: Vec<String>,
+    pub added_tags: Vec<String>,
+    pub removed_tags: Vec<String>,
+    /// Object files that are in both versions, but in different tags:
+    /// the tags in the old version and the tags in the new version.
+    pub retagged_objects: BTreeMap<String, (Vec<String>, Vec<String>)>,
+}
+
+impl PackDiff {
+    pub fn is_empty(&self) -> bool {
+        *self == PackDiff::default()
+    }
+
+    /// Describes the changes as a Markdown list, for release notes.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+
+        if self.is_empty() {
+            markdown.push_str("No changes.\n");
+            return markdown;
+        }
+
+        write_section(&mut markdown, "Added", "objects", &self.added_objects);
+        write_section(&mut markdown, "Removed", "objects", &self.removed_objects);
+        write_section(&mut markdown, "Changed", "objects", &self.changed_objects);
+        write_section(&mut markdown, "New", "tags", &self.added_tags);
+        write_section(&mut markdown, "Removed", "tags", &self.removed_tags);
+
+        if !self.retagged_objects.is_empty() {
+            let _ = writeln!(markdown, "- Retagged {} files", self.retagged_objects.len());
+            for (path, (old_tags, new_tags)) in self.retagged_objects.iter() {
+                let _ = writeln!(
+                    markdown,
+                    "  - `{}`: {} -> {}",
+                    path,
+                    tag_list(old_tags),
+                    tag_list(new_tags)
+                );
+            }
+        }
+
+        markdown
+    }
+}
+
+fn write_section(markdown: &mut String, verb: &str, noun: &str, items: &[String]) {
+    if items.is_empty() {
+        return;
+    }
+
+    let _ = writeln!(markdown, "- {} {} {}", verb, items.len(), noun);
+    for item in items {
+        let _ = writeln!(markdown, "  - `{}`", item);
+    }
+}
+
+fn tag_list(tags: &[String]) -> String {
+    if tags.is_empty() {
+        "no tags".to_owned()
+    } else {
+        tags.join(", ")
+    }
+}
+
+impl AssetPack {
+    /// Compares this pack with a newer version of it.
+    pub fn diff(&self, newer: &AssetPack) -> PackDiff {
+        let old_paths: BTreeSet<&String> = self.object_files.keys().collect();
+        let new_paths: BTreeSet<&String> = newer.object_files.keys().collect();
+        let old_tags: BTreeSet<&String> = self.tags.tags.keys().collect();
+        let new_tags: BTreeSet<&String> = newer.tags.tags.keys().collect();
+
+        let old_tags_by_file = self.tags_by_object();
+        let new_tags_by_file = newer.tags_by_object();
+
+        let kept_paths: Vec<&String> = old_paths.intersection(&new_paths).cloned().collect();
+
+        PackDiff {
+            added_objects: new_paths
+                .difference(&old_paths)
+                .map(|p| p.to_string())
+                .collect(),
+            removed_objects: old_paths
+                .difference(&new_paths)
+                .map(|p| p.to_string())
+                .collect(),
+            changed_objects: kept_paths
+                .iter()
+                .filter(|path| self.object_files[**path] != newer.object_files[**path])
+                .map(|path| path.to_string())
+                .collect(),
+            added_tags: new_tags
+                .difference(&old_tags)
+                .map(|t| t.to_string())
+                .collect(),
+            removed_tags: old_tags
+                .difference(&new_tags)
+                .map(|t| t.to_string())
+                .collect(),
+            retagged_objects: kept_paths
+                .iter()
+                .filter_map(|path| {
+                    let old = old_tags_by_file.get(*path).cloned().unwrap_or_default();
+                    let new = new_tags_by_file.get(*path).cloned().unwrap_or_default();
+                    if old == new {
+                        None
+                    } else {
+                        Some((path.to_string(), (old, new)))
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// The sorted tags of each object file that has any.
+    fn tags_by_object(&self) -> BTreeMap<&String, Vec<String>> {
+        let mut tags_by_file: BTreeMap<&String, Vec<String>> = BTreeMap::new();
+
+        for (tag, files) in self.tags.tags.iter() {
+            for file in files {
+                tags_by_file.entry(file).or_default().push(tag.clone());
+            }
+        }
+        for tags in tags_by_file.values_mut() {
+            tags.sort();
+        }
+
+        tags_by_file
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn changelog_lists_added_object_and_new_tag() {
+        let old_pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+
+        let mut new_pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        new_pack
+            .object_files
+            .insert("textures/objects/barrel.png".to_string(), vec![1, 2, 3]);
+        new_pack
+            .tags
+            .tags
+            .entry("Barrels".to_string())
+            .or_default()
+            .insert("textures/objects/barrel.png".to_string());
+        new_pack
+            .tags
+            .tags
+            .get_mut("Barrels")
+            .unwrap()
+            .insert("textures/objects/random.png".to_string());
+
+        let diff = old_pack.diff(&new_pack);
+
+        assert_eq!(diff.added_objects, vec!["textures/objects/barrel.png"]);
+        assert!(diff.removed_objects.is_empty());
+        assert_eq!(diff.added_tags, vec!["Barrels"]);
+        assert_eq!(
+            diff.retagged_objects["textures/objects/random.png"],
+            (
+                vec!["MyTag".to_string()],
+                vec!["Barrels".to_string(), "MyTag".to_string()]
+            )
+        );
+
+        let markdown = diff.to_markdown();
+        assert!(markdown.contains("- Added 1 objects\n  - `textures/objects/barrel.png`\n"));
+        assert!(markdown.contains("- New 1 tags\n  - `Barrels`\n"));
+        assert!(markdown.contains("  - `textures/objects/random.png`: MyTag -> Barrels, MyTag\n"));
+
+        assert!(old_pack.diff(&old_pack).is_empty());
+        assert_eq!(old_pack.diff(&old_pack).to_markdown(), "No changes.\n");
+    }
+}
@@ -0,0 +1,657 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context};
+use log::{debug, info, warn};
+
+use crate::asset_pack::asset_pack::{
+    parse_meta_file, parse_tags_file, record_json_encoding, take_embedded_tags, AssetPack,
+};
+use crate::asset_pack::file_times::{FileTimes, FILE_TIMES_FILE_NAME};
+use crate::asset_pack::godot_version::GodotVersion;
+use crate::asset_pack::pack_layout::PackLayout;
+use crate::asset_pack::path_utils::*;
+use crate::asset_pack::utils::md5_hash;
+
+/// Category name selecting the `data/` directory, including the tags file.
+pub const DATA_CATEGORY: &str = "data";
+/// Category name selecting the `pack.json` metadata file.
+pub const META_CATEGORY: &str = "meta";
+
+#[derive(Debug, Default)]
+pub struct UnpackOptions {
+    /// Only extract these categories. A category is either a `textures/` subdirectory
+    /// (e.g. `objects`, `walls`), `data` or `meta`. `None` extracts everything.
+    pub categories: Option<Vec<String>>,
+    pub overwrite: bool,
+    /// Skip files that already exist with the same contents, and overwrite the ones that
+    /// changed. Makes extracting a large pack into the same directory again fast.
+    pub incremental: bool,
+    /// Record each extracted file in `UNPACK_PROGRESS_FILE_NAME` in the output directory,
+    /// and skip the files recorded there. So an interrupted extraction continues where it
+    /// stopped, without comparing the files that are already done. The record is removed
+    /// once everything is extracted.
+    pub resumable: bool,
+}
+
+/// Name of the file in which `UnpackOptions::resumable` records the extracted files.
+pub const UNPACK_PROGRESS_FILE_NAME: &str = ".dd_unpack_progress";
+
+#[derive(Debug, Default)]
+pub struct DirectoryOptions {
+    /// Store the modification times of the files in the pack, so `unpack_to_dir` can
+    /// restore them.
+    pub record_file_times: bool,
+}
+
+/// What `unpack_to_dir` wrote.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct UnpackStats {
+    pub files: usize,
+    pub bytes: u64,
+    /// Files that were not written because they were already extracted, with `incremental`.
+    pub skipped: usize,
+}
+
+impl UnpackOptions {
+    fn includes_category(&self, category: &str) -> bool {
+        match &self.categories {
+            Some(categories) => categories.iter().any(|c| c == category),
+            None => true,
+        }
+    }
+
+    /// With `incremental`, true if the file at `path` has the same contents as `data`.
+    fn is_already_unpacked(&self, path: &Path, data: &[u8]) -> bool {
+        self.incremental
+            && fs::read(path).is_ok_and(|existing| md5_hash(&existing) == md5_hash(data))
+    }
+
+    fn includes_file(&self, path: &str) -> bool {
+        if self.categories.is_none() {
+            return true;
+        }
+
+        if is_data_file(path) {
+            self.includes_category(DATA_CATEGORY)
+        } else {
+            texture_category(path).is_some_and(|category| self.includes_category(category))
+        }
+    }
+}
+
+impl AssetPack {
+    /// Reads an asset directory, as written by `unpack_to_dir` or as used by Dungeondraft
+    /// before packaging. `pack.json` is required, the tags file is optional.
+    pub fn from_directory(dir: &Path) -> anyhow::Result<Self> {
+        Self::from_directory_with_options(dir, &DirectoryOptions::default())
+    }
+
+    pub fn from_directory_with_options(
+        dir: &Path,
+        options: &DirectoryOptions,
+    ) -> anyhow::Result<Self> {
+        info!("Reading directory '{}'", dir.display());
+
+        let mut json_encodings = BTreeMap::new();
+
+        let meta_path = dir.join(PACK_FILE_NAME);
+        let meta_data =
+            fs::read(&meta_path).context(format!("Could not read '{}'", meta_path.display()))?;
+        record_json_encoding(&mut json_encodings, PACK_FILE_NAME, &meta_data);
+        let mut meta = parse_meta_file(meta_data)?;
+
+        let tags_path = dir.join(TAGS_FILE_NAME);
+        let tags = if tags_path.exists() {
+            let tags_data = fs::read(&tags_path)
+                .context(format!("Could not read '{}'", tags_path.display()))?;
+            record_json_encoding(&mut json_encodings, TAGS_FILE_NAME, &tags_data);
+            parse_tags_file(tags_data)?
+        } else {
+            take_embedded_tags(&mut meta).unwrap_or_default()
+        };
+
+        let mut files = vec![];
+        let mut ancestors = HashSet::new();
+        ancestors.insert(dir.canonicalize()?);
+        collect_files(dir, "", &mut files, &mut ancestors)?;
+
+        let mut object_files = HashMap::new();
+        let mut other_files = HashMap::new();
+        let mut file_times = FileTimes::default();
+
+        for relative_path in files {
+            if relative_path == PACK_FILE_NAME
+                || relative_path == TAGS_FILE_NAME
+                || relative_path == UNPACK_PROGRESS_FILE_NAME
+            {
+                continue;
+            }
+            // An old manifest would be out of date, a new one is made below.
+            if options.record_file_times && relative_path == FILE_TIMES_FILE_NAME {
+                continue;
+            }
+
+            let path = dir.join(&relative_path);
+            let data = fs::read(&path).context(format!("Could not read '{}'", path.display()))?;
+
+            if options.record_file_times {
+                file_times.record(&relative_path, &path)?;
+            }
+
+            if is_objects_file(&relative_path) {
+                object_files.insert(relative_path, data);
+            } else {
+                other_files.insert(relative_path, data);
+            }
+        }
+
+        if options.record_file_times {
+            other_files.insert(FILE_TIMES_FILE_NAME.to_owned(), file_times.to_bytes()?);
+        }
+
+        Ok(AssetPack {
+            godot_version: GodotVersion::dungeondraft_default(),
+            layout: PackLayout::default(),
+            meta,
+            tags,
+            object_files,
+            other_files,
+            stored_md5: HashMap::new(),
+            file_order: vec![],
+            trailer: vec![],
+            json_encodings,
+        })
+    }
+
+    /// Writes the pack out as a regular asset directory, the way it looks before Dungeondraft
+    /// packages it: `pack.json`, the tags file and all other files at their relative paths.
+    /// If the pack contains a file times manifest, the modification times of the files
+    /// are restored from it. Returns the number and total size of the files written,
+    /// and the number of files skipped because they were already extracted.
+    pub fn unpack_to_dir(
+        &self,
+        dir: &Path,
+        options: &UnpackOptions,
+    ) -> anyhow::Result<UnpackStats> {
+        info!("Unpacking '{}' to '{}'", self.meta.name, dir.display());
+
+        for paths in self.find_case_collisions() {
+            warn!(
+                "These files only differ in upper/lower case, \
+                on some systems only one of them will be extracted: '{}'",
+                paths.join("', '")
+            );
+        }
+
+        let mut files = vec![];
+
+        if options.includes_category(META_CATEGORY) {
+            files.push((PACK_FILE_NAME, json5::to_string(&self.meta)?.into_bytes()));
+        }
+        if options.includes_category(DATA_CATEGORY) {
+            files.push((TAGS_FILE_NAME, json5::to_string(&self.tags)?.into_bytes()));
+        }
+
+        let file_times = match self.other_files.get(FILE_TIMES_FILE_NAME) {
+            Some(data) => FileTimes::parse(data).unwrap_or_else(|e| {
+                warn!(
+                    "Could not parse '{}', ignoring it:\n{}",
+                    FILE_TIMES_FILE_NAME, e
+                );
+                FileTimes::default()
+            }),
+            None => FileTimes::default(),
+        };
+
+        let mut written = UnpackStats::default();
+
+        let mut progress = if options.resumable {
+            Some(UnpackProgress::open(dir, &self.meta.id)?)
+        } else {
+            None
+        };
+        // A file that was being written when the extraction was interrupted is not recorded,
+        // so it has to be overwritten.
+        let overwrite = options.overwrite
+            || options.incremental
+            || progress.as_ref().is_some_and(UnpackProgress::is_resumed);
+
+        for (path, data) in files.iter() {
+            if progress.as_ref().is_some_and(|p| p.is_done(path))
+                || options.is_already_unpacked(&dir.join(path), data)
+            {
+                written.skipped += 1;
+                continue;
+            }
+
+            if write_unpacked_file(dir, path, data, overwrite)? {
+                written.files += 1;
+                written.bytes += data.len() as u64;
+            }
+            if let Some(progress) = progress.as_mut() {
+                progress.record(path)?;
+            }
+        }
+
+        for (path, data) in self.object_files.iter().chain(self.other_files.iter()) {
+            if !options.includes_file(path) {
+                debug!(
+                    "Skipping '{}', it is not in the requested categories.",
+                    path
+                );
+                continue;
+            }
+
+            if progress.as_ref().is_some_and(|p| p.is_done(path))
+                || options.is_already_unpacked(&dir.join(path), data)
+            {
+                written.skipped += 1;
+                continue;
+            }
+
+            if write_unpacked_file(dir, path, data, overwrite)? {
+                file_times.restore(path, &dir.join(path))?;
+                written.files += 1;
+                written.bytes += data.len() as u64;
+            }
+            if let Some(progress) = progress.as_mut() {
+                progress.record(path)?;
+            }
+        }
+
+        if let Some(progress) = progress {
+            progress.finish()?;
+        }
+
+        info!(
+            "Wrote {} files, skipped {} unchanged files.",
+            written.files, written.skipped
+        );
+
+        Ok(written)
+    }
+}
+
+/// The files extracted so far, see `UnpackOptions::resumable`. The record starts with the
+/// id of the pack, followed by one path per line.
+struct UnpackProgress {
+    path: PathBuf,
+    file: File,
+    done: HashSet<String>,
+}
+
+impl UnpackProgress {
+    /// Continues the record in `dir`, or starts a new one if there is none,
+    /// or if it is of a different pack.
+    fn open(dir: &Path, pack_id: &str) -> anyhow::Result<Self> {
+        fs::create_dir_all(dir)
+            .context(format!("Could not create directory '{}'", dir.display()))?;
+        let path = dir.join(UNPACK_PROGRESS_FILE_NAME);
+
+        let existing = fs::read_to_string(&path).unwrap_or_default();
+        let mut lines = existing.lines();
+
+        let (file, done) = if lines.next() == Some(pack_id) {
+            let done: HashSet<String> = lines.map(str::to_owned).collect();
+            info!("Resuming, {} files were already extracted.", done.len());
+            (OpenOptions::new().append(true).open(&path), done)
+        } else {
+            let file = File::create(&path).and_then(|mut file| {
+                writeln!(file, "{}", pack_id)?;
+                Ok(file)
+            });
+            (file, HashSet::new())
+        };
+
+        Ok(UnpackProgress {
+            file: file.context(format!("Could not write '{}'", path.display()))?,
+            path,
+            done,
+        })
+    }
+
+    /// True if an earlier extraction got interrupted.
+    fn is_resumed(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    fn is_done(&self, path: &str) -> bool {
+        self.done.contains(path)
+    }
+
+    fn record(&mut self, path: &str) -> anyhow::Result<()> {
+        writeln!(self.file, "{}", path)
+            .context(format!("Could not write '{}'", self.path.display()))
+    }
+
+    /// Removes the record, everything has been extracted.
+    fn finish(self) -> anyhow::Result<()> {
+        fs::remove_file(&self.path).context(format!("Could not remove '{}'", self.path.display()))
+    }
+}
+
+/// Recursively collects the paths of all files in `dir`, relative to the directory
+/// that was originally passed in, and always separated by `/` as they are in a pack.
+/// Symbolic links are followed, except broken ones and ones to a directory that is
+/// being collected already, which would loop forever. Those are skipped with a warning.
+/// `ancestors` holds the canonical paths of the directories being collected.
+fn collect_files(
+    dir: &Path,
+    prefix: &str,
+    files: &mut Vec<String>,
+    ancestors: &mut HashSet<PathBuf>,
+) -> anyhow::Result<()> {
+    let current_dir = dir.join(prefix);
+
+    for entry in fs::read_dir(&current_dir).context(format!(
+        "Could not read directory '{}'",
+        current_dir.display()
+    ))? {
+        let entry = entry?;
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(name) => {
+                warn!(
+                    "Skipping '{}', its name is not valid unicode.",
+                    name.to_string_lossy()
+                );
+                continue;
+            }
+        };
+        let relative_path = if prefix.is_empty() {
+            name
+        } else {
+            format!("{}/{}", prefix, name)
+        };
+
+        let file_type = entry.file_type()?;
+        let is_dir = if file_type.is_symlink() {
+            match fs::metadata(entry.path()) {
+                Ok(target) => target.is_dir(),
+                Err(_) => {
+                    warn!(
+                        "Skipping '{}', it is a symbolic link to something that does not exist.",
+                        entry.path().display()
+                    );
+                    continue;
+                }
+            }
+        } else {
+            file_type.is_dir()
+        };
+
+        if is_dir {
+            let canonical_path = entry.path().canonicalize()?;
+            if ancestors.contains(&canonical_path) {
+                warn!(
+                    "Skipping '{}', it links to a directory it is in.",
+                    entry.path().display()
+                );
+                continue;
+            }
+
+            ancestors.insert(canonical_path.clone());
+            collect_files(dir, &relative_path, files, ancestors)?;
+            ancestors.remove(&canonical_path);
+        } else {
+            files.push(relative_path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns false if the file already existed and was not overwritten.
+pub(crate) fn write_unpacked_file(
+    dir: &Path,
+    relative_path: &str,
+    data: &[u8],
+    overwrite: bool,
+) -> anyhow::Result<bool> {
+    let relative_path = Path::new(relative_path);
+
+    // Paths come from the pack file, so they should not be able to escape the output directory.
+    if relative_path
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_)))
+    {
+        bail!(
+            "Refusing to unpack '{}', it is not a plain relative path.",
+            relative_path.display()
+        );
+    }
+
+    let path = dir.join(relative_path);
+
+    if path.exists() && !overwrite {
+        warn!(
+            "File '{}' already exists. If you want to overwrite, call again with the `-F` argument.",
+            path.display()
+        );
+        return Ok(false);
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Could not create directory '{}'", parent.display()))?;
+    }
+
+    fs::write(&path, data).context(format!("Could not write file '{}'", path.display()))?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Cursor;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::directory::{
+        DirectoryOptions, UnpackOptions, UNPACK_PROGRESS_FILE_NAME,
+    };
+    use crate::asset_pack::file_times::FILE_TIMES_FILE_NAME;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn unpack_only_objects_category() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.other_files
+            .insert("textures/walls/stone.png".to_string(), vec![1, 2, 3]);
+        pack.other_files
+            .insert("textures/paths/gravel.png".to_string(), vec![4, 5, 6]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let options = UnpackOptions {
+            categories: Some(vec!["objects".to_string()]),
+            ..Default::default()
+        };
+
+        let written = pack.unpack_to_dir(dir.path(), &options).unwrap();
+
+        assert_eq!(written.files, 1);
+        assert_eq!(written.bytes, 10);
+        assert!(dir.path().join("textures/objects/random.png").exists());
+        assert!(!dir.path().join("textures/walls/stone.png").exists());
+        assert!(!dir.path().join("textures/paths/gravel.png").exists());
+        assert!(!dir.path().join("textures/portals/door.png").exists());
+        assert!(!dir.path().join("pack.json").exists());
+        assert!(!dir.path().join("data").exists());
+    }
+
+    #[test]
+    fn incremental_unpack_skips_unchanged_files() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        let options = UnpackOptions {
+            incremental: true,
+            ..Default::default()
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let first = pack.unpack_to_dir(dir.path(), &options).unwrap();
+        assert!(first.files > 0);
+        assert_eq!(first.skipped, 0);
+
+        let second = pack.unpack_to_dir(dir.path(), &options).unwrap();
+        assert_eq!(second.files, 0);
+        assert_eq!(second.skipped, first.files);
+
+        // Only the changed file is written again.
+        pack.object_files
+            .insert("textures/objects/random.png".to_string(), vec![1, 2, 3]);
+        let third = pack.unpack_to_dir(dir.path(), &options).unwrap();
+        assert_eq!(third.files, 1);
+        assert_eq!(third.skipped, first.files - 1);
+        assert_eq!(
+            fs::read(dir.path().join("textures/objects/random.png")).unwrap(),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn resumable_unpack_skips_files_extracted_before_interruption() {
+        let pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        let options = UnpackOptions {
+            resumable: true,
+            ..Default::default()
+        };
+
+        // A file where the directory of the last file should go makes the extraction
+        // fail there. The object files are written before the other files.
+        let dir = tempfile::tempdir().unwrap();
+        let blocker = dir.path().join("textures/portals");
+        fs::create_dir_all(dir.path().join("textures")).unwrap();
+        fs::write(&blocker, []).unwrap();
+        assert!(pack.unpack_to_dir(dir.path(), &options).is_err());
+
+        let progress = fs::read_to_string(dir.path().join(UNPACK_PROGRESS_FILE_NAME)).unwrap();
+        assert_eq!(
+            progress.lines().collect::<Vec<_>>(),
+            vec![
+                "12345678",
+                "pack.json",
+                "data/default.dungeondraft_tags",
+                "textures/objects/random.png"
+            ]
+        );
+
+        fs::remove_file(&blocker).unwrap();
+        let resumed = pack.unpack_to_dir(dir.path(), &options).unwrap();
+        assert_eq!(resumed.skipped, 3);
+        assert_eq!(resumed.files, 1);
+        assert!(dir.path().join("textures/portals/door.png").is_file());
+        assert!(!dir.path().join(UNPACK_PROGRESS_FILE_NAME).exists());
+    }
+
+    #[test]
+    fn unpack_and_read_directory() {
+        let pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        pack.unpack_to_dir(dir.path(), &UnpackOptions::default())
+            .unwrap();
+
+        let re_read_pack = AssetPack::from_directory(dir.path()).unwrap();
+
+        assert_eq!(re_read_pack.meta, pack.meta);
+        assert_eq!(re_read_pack.tags, pack.tags);
+        assert_eq!(re_read_pack.object_files, pack.object_files);
+        assert_eq!(re_read_pack.other_files, pack.other_files);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_directory_with_symbolic_links() {
+        use std::os::unix::fs::symlink;
+
+        let pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        pack.unpack_to_dir(dir.path(), &UnpackOptions::default())
+            .unwrap();
+
+        let shared_dir = tempfile::tempdir().unwrap();
+        fs::write(shared_dir.path().join("barrel.png"), [4, 5, 6]).unwrap();
+        fs::create_dir(shared_dir.path().join("crates")).unwrap();
+        fs::write(shared_dir.path().join("crates/crate.png"), [7, 8]).unwrap();
+
+        let objects_dir = dir.path().join("textures/objects");
+        symlink(
+            shared_dir.path().join("barrel.png"),
+            objects_dir.join("barrel.png"),
+        )
+        .unwrap();
+        symlink(shared_dir.path().join("crates"), objects_dir.join("crates")).unwrap();
+        symlink(
+            shared_dir.path().join("gone.png"),
+            objects_dir.join("gone.png"),
+        )
+        .unwrap();
+        symlink(dir.path().join("textures"), objects_dir.join("loop")).unwrap();
+
+        let read_pack = AssetPack::from_directory(dir.path()).unwrap();
+
+        assert_eq!(
+            read_pack.object_files["textures/objects/barrel.png"],
+            [4, 5, 6]
+        );
+        assert_eq!(
+            read_pack.object_files["textures/objects/crates/crate.png"],
+            [7, 8]
+        );
+        assert!(!read_pack
+            .object_files
+            .contains_key("textures/objects/gone.png"));
+        assert!(!read_pack
+            .object_files
+            .keys()
+            .any(|path| path.starts_with("textures/objects/loop/")));
+        assert_eq!(read_pack.object_files.len(), pack.object_files.len() + 2);
+    }
+
+    #[test]
+    fn restore_file_times_after_round_trip() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.unpack_to_dir(source_dir.path(), &UnpackOptions::default())
+            .unwrap();
+
+        let object_path = source_dir.path().join("textures/objects/random.png");
+        let modified = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&object_path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        let options = DirectoryOptions {
+            record_file_times: true,
+        };
+        let recorded_pack =
+            AssetPack::from_directory_with_options(source_dir.path(), &options).unwrap();
+        assert!(recorded_pack.other_files.contains_key(FILE_TIMES_FILE_NAME));
+
+        // Through an actual pack file, to make sure the manifest survives that.
+        let mut raw_pack = vec![];
+        recorded_pack.to_write(&mut raw_pack).unwrap();
+        let re_read_pack = AssetPack::from_read(&mut Cursor::new(raw_pack)).unwrap();
+
+        let target_dir = tempfile::tempdir().unwrap();
+        re_read_pack
+            .unpack_to_dir(target_dir.path(), &UnpackOptions::default())
+            .unwrap();
+
+        let restored = fs::metadata(target_dir.path().join("textures/objects/random.png"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert_eq!(restored, modified);
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::asset_pack::tags::Tags;
+
+impl Tags {
+    /// The tag sets and tags as a Graphviz DOT graph, with an edge from each set to its tags.
+    /// Sets are boxes and tags ellipses. With `include_objects`, the object files are added
+    /// as well, with an edge from each tag to its files, which gets big for large packs.
+    /// Everything is sorted by name, so the same tags always give the same graph.
+    pub fn to_dot(&self, title: &str, include_objects: bool) -> String {
+        let sets: BTreeMap<&String, BTreeSet<&String>> = self
+            .sets
+            .iter()
+            .map(|(set, tags)| (set, tags.iter().collect()))
+            .collect();
+        let tags: BTreeMap<&String, BTreeSet<&String>> = self
+            .tags
+            .iter()
+            .map(|(tag, files)| (tag, files.iter().collect()))
+            .collect();
+
+        let mut dot = String::new();
+        let _ = writeln!(dot, "digraph {} {{", quoted(title));
+        let _ = writeln!(dot, "    rankdir=LR;");
+
+        for set in sets.keys() {
+            let _ = writeln!(
+                dot,
+                "    {} [label={}, shape=box];",
+                quoted(&format!("set:{}", set)),
+                quoted(set)
+            );
+        }
+        for tag in tags.keys() {
+            let _ = writeln!(
+                dot,
+                "    {} [label={}, shape=ellipse];",
+                quoted(&format!("tag:{}", tag)),
+                quoted(tag)
+            );
+        }
+
+        if include_objects {
+            let files: BTreeSet<&&String> = tags.values().flatten().collect();
+            for file in files {
+                let _ = writeln!(
+                    dot,
+                    "    {} [label={}, shape=note];",
+                    quoted(&format!("object:{}", file)),
+                    quoted(file)
+                );
+            }
+        }
+
+        for (set, set_tags) in sets.iter() {
+            for tag in set_tags {
+                let _ = writeln!(
+                    dot,
+                    "    {} -> {};",
+                    quoted(&format!("set:{}", set)),
+                    quoted(&format!("tag:{}", tag))
+                );
+            }
+        }
+
+        if include_objects {
+            for (tag, files) in tags.iter() {
+                for file in files {
+                    let _ = writeln!(
+                        dot,
+                        "    {} -> {};",
+                        quoted(&format!("tag:{}", tag)),
+                        quoted(&format!("object:{}", file))
+                    );
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// A DOT identifier as a quoted string, which can contain any characters.
+fn quoted(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn dot_graph_has_edges_from_set_to_tags() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.tags
+            .sets
+            .get_mut("Example Set")
+            .unwrap()
+            .insert("Colorable".to_string());
+
+        let dot = pack.tags.to_dot(&pack.meta.name, false);
+
+        assert!(dot.starts_with("digraph \"example_pack\" {\n"));
+        assert!(dot.contains("    \"set:Example Set\" [label=\"Example Set\", shape=box];\n"));
+        assert!(dot.contains("    \"tag:MyTag\" [label=\"MyTag\", shape=ellipse];\n"));
+        assert!(dot.contains("    \"set:Example Set\" -> \"tag:Colorable\";\n"));
+        assert!(dot.contains("    \"set:Example Set\" -> \"tag:MyTag\";\n"));
+        assert!(!dot.contains("object:"));
+        assert!(dot.ends_with("}\n"));
+
+        let with_objects = pack.tags.to_dot(&pack.meta.name, true);
+        assert!(
+            with_objects.contains("    \"tag:MyTag\" -> \"object:textures/objects/random.png\";\n")
+        );
+    }
+}
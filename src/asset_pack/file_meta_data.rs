@@ -2,9 +2,9 @@ use log::trace;
 use std::cmp::Ordering;
 use std::io::{Read, Seek, Write};
 
-use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt, LE};
 
-use crate::asset_pack::path_utils::{ASSET_PACK_PREFIX, RESOURCE_PATH_PREFIX};
+use crate::asset_pack::path_utils;
 use crate::asset_pack::utils;
 use crate::asset_pack::utils::{I32, I64, MD5_BYTES};
 
@@ -15,53 +15,105 @@ pub struct FileMetaData {
     pub offset: u64,
     pub size: usize,
     pub md5: [u8; MD5_BYTES],
+    /// Only present in packs of format version 2 and up (Godot 4).
+    pub flags: Option<u32>,
 }
 
 impl FileMetaData {
+    /// Size of an entry with an empty path.
+    pub const MIN_BINARY_SIZE: usize = I32 + I64 * 2 + MD5_BYTES;
+
+    /// Flag marking a file as removed, used by Godot 4 patch packs.
+    pub const FLAG_REMOVED: u32 = 1 << 1;
+    /// Flag marking a file as zlib-compressed, see `WriteOptions::compress`.
+    /// Godot has no such flag, so this uses the highest bit, far away from the flags Godot
+    /// defines. Godot ignores it, and would read the compressed bytes as the file.
+    pub const FLAG_COMPRESSED: u32 = 1 << 31;
+
     pub fn new(path: String, size: usize) -> Self {
         FileMetaData {
             path,
             offset: 0,
             size,
             md5: [0; MD5_BYTES],
+            flags: None,
         }
     }
 
-    /// Strips `res://packs/<pack-id>/` if the file path starts with it.
+    /// Reads the path as is, e.g. `res://packs/<pack-id>/textures/bla.png`.
+    /// Use `strip_pack_prefix` to get the path relative to the pack.
     pub fn from_read<R: Read + Seek>(data: &mut R) -> anyhow::Result<Self> {
-        let path_length = data.read_i32::<LE>()? as usize;
-        let path_with_maybe_pack_id = utils::read_string(data, path_length)?
-            .trim_start_matches(RESOURCE_PATH_PREFIX)
-            .trim_start_matches(ASSET_PACK_PREFIX)
-            .to_owned();
+        Self::from_read_with_flags(data, false)
+    }
+
+    /// Like `from_read`, but also reads the flags that follow the md5 hash when `has_flags`,
+    /// see `GodotVersion::has_entry_flags`.
+    pub fn from_read_with_flags<R: Read + Seek>(
+        data: &mut R,
+        has_flags: bool,
+    ) -> anyhow::Result<Self> {
+        Self::from_read_with_order::<R, LE>(data, has_flags)
+    }
 
-        let (_id, path) = path_with_maybe_pack_id
-            .split_once('/')
-            .unwrap_or(("", path_with_maybe_pack_id.as_str()));
+    /// Like `from_read_with_flags`, but with the numbers in byte order `B`.
+    pub fn from_read_with_order<R: Read + Seek, B: ByteOrder>(
+        data: &mut R,
+        has_flags: bool,
+    ) -> anyhow::Result<Self> {
+        let path_length = data.read_i32::<B>()? as usize;
+        // Godot pads the path with zeros to a multiple of 4 bytes.
+        let mut path = utils::read_string(data, path_length)?;
+        path.truncate(path.trim_end_matches('\0').len());
 
         trace!("File meta: {}", path);
 
-        let offset = data.read_i64::<LE>()? as u64;
-        let size = data.read_i64::<LE>()? as usize;
+        let offset = data.read_i64::<B>()? as u64;
+        let size = data.read_i64::<B>()? as usize;
 
         let mut md5 = [0; MD5_BYTES];
         data.read_exact(&mut md5)?;
 
+        let flags = if has_flags {
+            Some(data.read_u32::<B>()?)
+        } else {
+            None
+        };
+
         Ok(Self {
-            path: path.to_owned(),
+            path,
             offset,
             size,
             md5,
+            flags,
         })
     }
 
+    /// Whether the entry has `FLAG_REMOVED` set, i.e. a patch pack deletes the file.
+    pub fn is_removed(&self) -> bool {
+        self.flags.unwrap_or(0) & Self::FLAG_REMOVED != 0
+    }
+
+    /// Whether the entry has `FLAG_COMPRESSED` set. Only packs with entry flags can say so.
+    pub fn is_compressed(&self) -> bool {
+        self.flags.unwrap_or(0) & Self::FLAG_COMPRESSED != 0
+    }
+
+    /// Makes the path relative to the pack, see `path_utils::strip_pack_prefix`.
+    pub fn strip_pack_prefix(&mut self, pack_id: Option<&str>) {
+        self.path = path_utils::strip_pack_prefix(&self.path, pack_id).to_owned();
+    }
+
     pub fn to_write<W: Write>(&self, data: &mut W) -> anyhow::Result<()> {
         data.write_i32::<LE>(self.path.len() as i32)?;
-        data.write(self.path.as_bytes())?;
+        data.write_all(self.path.as_bytes())?;
         data.write_i64::<LE>(self.offset as i64)?;
         data.write_i64::<LE>(self.size as i64)?;
 
-        data.write_all(&[0; MD5_BYTES])?;
+        data.write_all(&self.md5)?;
+
+        if let Some(flags) = self.flags {
+            data.write_u32::<LE>(flags)?;
+        }
 
         Ok(())
     }
@@ -75,6 +127,10 @@ impl FileMetaData {
         size += I64 * 2;
         size += MD5_BYTES;
 
+        if self.flags.is_some() {
+            size += I32;
+        }
+
         size
     }
 }
@@ -89,7 +145,7 @@ impl PartialEq<Self> for FileMetaData {
 
 impl PartialOrd<Self> for FileMetaData {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.offset.partial_cmp(&other.offset)
+        Some(self.cmp(other))
     }
 }
 
@@ -0,0 +1,63 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+/// Pack-relative path of the manifest holding the modification times of the files.
+/// It is only present in packs built with `DirectoryOptions::record_file_times`.
+pub const FILE_TIMES_FILE_NAME: &str = "data/file_times.json";
+
+/// Modification times of the files in a pack, in milliseconds since the unix epoch, by path.
+#[derive(Debug, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub struct FileTimes(pub BTreeMap<String, u64>);
+
+impl FileTimes {
+    pub fn parse(data: &[u8]) -> anyhow::Result<Self> {
+        Ok(json5::from_str(std::str::from_utf8(data)?)?)
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(json5::to_string(self)?.into_bytes())
+    }
+
+    /// Records the modification time of the file at `path` under `relative_path`.
+    pub fn record(&mut self, relative_path: &str, path: &Path) -> anyhow::Result<()> {
+        let modified = fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .context(format!(
+                "Could not read modification time of '{}'",
+                path.display()
+            ))?;
+
+        let millis = modified
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        self.0.insert(relative_path.to_owned(), millis);
+
+        Ok(())
+    }
+
+    /// Sets the modification time of the file at `path` to the one recorded for `relative_path`.
+    /// Does nothing if there is no time recorded for it.
+    pub fn restore(&self, relative_path: &str, path: &Path) -> anyhow::Result<()> {
+        if let Some(millis) = self.0.get(relative_path) {
+            let time = SystemTime::UNIX_EPOCH + Duration::from_millis(*millis);
+
+            fs::File::options()
+                .write(true)
+                .open(path)
+                .and_then(|file| file.set_modified(time))
+                .context(format!(
+                    "Could not set modification time of '{}'",
+                    path.display()
+                ))?;
+        }
+
+        Ok(())
+    }
+}
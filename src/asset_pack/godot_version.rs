@@ -2,11 +2,11 @@ use std::fmt;
 use std::fmt::Formatter;
 use std::io::{Read, Seek, Write};
 
-use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt, LE};
 
-use crate::asset_pack::utils::I32;
+use crate::asset_pack::utils::{GODOT_METADATA_RESERVED_SPACE, I32, I64};
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct GodotVersion {
     version: i32,
     major: i32,
@@ -15,6 +15,12 @@ pub struct GodotVersion {
 }
 
 impl GodotVersion {
+    /// The version written into packs that are built from scratch, as found in
+    /// packs exported by Dungeondraft.
+    pub fn dungeondraft_default() -> Self {
+        Self::new(1, 3, 2, 4)
+    }
+
     pub fn new(version: i32, major: i32, minor: i32, revision: i32) -> Self {
         Self {
             version,
@@ -25,11 +31,18 @@ impl GodotVersion {
     }
 
     pub fn from_read<R: Read + Seek>(data: &mut R) -> anyhow::Result<Self> {
+        Self::from_read_with_order::<R, LE>(data)
+    }
+
+    /// Like `from_read`, but with the numbers in byte order `B`.
+    pub fn from_read_with_order<R: Read + Seek, B: ByteOrder>(
+        data: &mut R,
+    ) -> anyhow::Result<Self> {
         Ok(Self {
-            version: data.read_i32::<LE>()?,
-            major: data.read_i32::<LE>()?,
-            minor: data.read_i32::<LE>()?,
-            revision: data.read_i32::<LE>()?,
+            version: data.read_i32::<B>()?,
+            major: data.read_i32::<B>()?,
+            minor: data.read_i32::<B>()?,
+            revision: data.read_i32::<B>()?,
         })
     }
 
@@ -42,6 +55,35 @@ impl GodotVersion {
         Ok(())
     }
 
+    /// The pack format, the first number of the version.
+    pub fn format_version(&self) -> u32 {
+        self.version as u32
+    }
+
+    /// Godot 4 added flags to each entry in the file table, starting with pack format 2.
+    /// The first number of the version is the pack format.
+    pub fn has_entry_flags(&self) -> bool {
+        self.version >= 2
+    }
+
+    /// Godot 4 also added the pack flags and the file base to the header, in front of the
+    /// reserved space. The offsets in its file table count from the file base.
+    pub fn has_file_base(&self) -> bool {
+        self.version >= 2
+    }
+
+    /// Size of the header after the magic number, up to and including the number of files.
+    pub fn header_size(&self) -> usize {
+        let mut size = Self::size_in_bytes() + GODOT_METADATA_RESERVED_SPACE + I32;
+
+        if self.has_file_base() {
+            // Pack flags and file base.
+            size += I32 + I64;
+        }
+
+        size
+    }
+
     pub fn size_in_bytes() -> usize {
         I32 * 4
     }
@@ -0,0 +1,114 @@
+use std::io::Cursor;
+
+use anyhow::bail;
+use image::{DynamicImage, ImageFormat};
+
+use crate::asset_pack::transform::ImageConverter;
+
+/// Converts between PNG and WebP images with the `image` crate, for
+/// `AssetPack::convert_objects`. WebP images are written lossless.
+/// JPEG images can't be read or written.
+pub struct ImageCodec;
+
+impl ImageConverter for ImageCodec {
+    fn can_read(&self, from: &str) -> bool {
+        codec_format(from).is_some()
+    }
+
+    fn convert(&self, data: &[u8], from: &str, to: &str) -> anyhow::Result<Vec<u8>> {
+        let from_format = match codec_format(from) {
+            Some(format) => format,
+            None => bail!("Can't read {} images, only png and webp", from),
+        };
+        let to_format = match codec_format(to) {
+            Some(format) => format,
+            None => bail!("Can't write {} images, only png and webp", to),
+        };
+
+        let image = image::load_from_memory_with_format(data, from_format)?;
+        // The WebP encoder only takes 8 bit images.
+        let image = match to_format {
+            ImageFormat::WebP if image.color().has_alpha() => {
+                DynamicImage::ImageRgba8(image.to_rgba8())
+            }
+            ImageFormat::WebP => DynamicImage::ImageRgb8(image.to_rgb8()),
+            _ => image,
+        };
+
+        let mut converted = Cursor::new(vec![]);
+        image.write_to(&mut converted, to_format)?;
+
+        Ok(converted.into_inner())
+    }
+}
+
+/// The `image` format for the format names of `path_utils::image_format`.
+fn codec_format(format: &str) -> Option<ImageFormat> {
+    match format {
+        "png" => Some(ImageFormat::Png),
+        "webp" => Some(ImageFormat::WebP),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use image::ImageFormat;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::image_codec::ImageCodec;
+    use crate::asset_pack::test_asset_pack_serialization::{create_raw_test_pack, test_png};
+    use crate::asset_pack::transform::ImageConverter;
+
+    #[test]
+    fn convert_png_object_to_webp_and_back() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        let png = test_png(21, 9);
+        let pixels = image::load_from_memory(&png).unwrap().to_rgba8();
+        pack.object_files
+            .insert("textures/objects/random.png".to_string(), png);
+        pack.object_files
+            .insert("textures/objects/photo.jpg".to_string(), vec![0xff, 0xd8]);
+
+        assert_eq!(pack.convert_objects("webp", &ImageCodec).unwrap(), 1);
+
+        let webp = &pack.object_files["textures/objects/random.webp"];
+        assert_eq!(image::guess_format(webp).unwrap(), ImageFormat::WebP);
+        assert_eq!(
+            image::load_from_memory_with_format(webp, ImageFormat::WebP)
+                .unwrap()
+                .to_rgba8(),
+            pixels
+        );
+        assert!(pack.tags.tags["MyTag"].contains("textures/objects/random.webp"));
+        assert!(!pack.tags.tags["MyTag"].contains("textures/objects/random.png"));
+        // JPEG images can't be read, so they are left alone.
+        assert_eq!(
+            pack.object_files["textures/objects/photo.jpg"],
+            vec![0xff, 0xd8]
+        );
+
+        assert_eq!(pack.convert_objects("png", &ImageCodec).unwrap(), 1);
+        let png = &pack.object_files["textures/objects/random.png"];
+        assert_eq!(
+            image::load_from_memory_with_format(png, ImageFormat::Png)
+                .unwrap()
+                .to_rgba8(),
+            pixels
+        );
+        assert!(pack.tags.tags["MyTag"].contains("textures/objects/random.png"));
+    }
+
+    #[test]
+    fn convert_refuses_what_it_cant_read_or_write() {
+        let png = test_png(3, 3);
+        assert!(ImageCodec.convert(&png, "png", "jpeg").is_err());
+        assert!(ImageCodec.convert(&[0xff, 0xd8], "jpeg", "png").is_err());
+        assert!(ImageCodec.convert(&png, "webp", "png").is_err());
+        assert!(ImageCodec.convert(&[0; 10], "png", "webp").is_err());
+        assert!(!ImageCodec.can_read("jpeg"));
+    }
+}
@@ -0,0 +1,82 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::utils::ASSET_PACK_MAGIC_FILE_HEADER;
+
+/// How the bytes of a pack file are spent, see `AssetPack::layout_report`.
+/// The four parts add up to `total_bytes`.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct LayoutReport {
+    pub header_bytes: u64,
+    pub table_bytes: u64,
+    /// The contents of the files.
+    pub payload_bytes: u64,
+    /// Bytes that belong to no file: gaps between the files, and files marked as removed.
+    /// Repacking the pack gets rid of these.
+    pub padding_bytes: u64,
+    pub total_bytes: u64,
+}
+
+impl AssetPack {
+    /// Reads the file table of a pack file, and reports how much of the file is
+    /// overhead, file contents and unused.
+    pub fn layout_report<R: Read + Seek>(data: &mut R) -> anyhow::Result<LayoutReport> {
+        let start = data.stream_position()?;
+        let (godot_version, files_meta) = Self::read_file_table(data, None)?;
+        let table_end = data.stream_position()?;
+        let total_bytes = data.seek(SeekFrom::End(0))? - start;
+
+        // Magic number, godot version, reserved space and file count.
+        let header_bytes = ((ASSET_PACK_MAGIC_FILE_HEADER.len() + godot_version.header_size())
+            as u64)
+            .min(total_bytes);
+        let table_bytes = (table_end - start).saturating_sub(header_bytes);
+        // Entries sharing the same bytes are sorted next to each other, and counted once.
+        let mut stored: Vec<(u64, u64)> = files_meta
+            .iter()
+            .map(|meta| (meta.offset, meta.size as u64))
+            .collect();
+        stored.dedup();
+        let payload_bytes: u64 = stored.iter().map(|(_, size)| size).sum();
+
+        Ok(LayoutReport {
+            header_bytes,
+            table_bytes,
+            payload_bytes,
+            padding_bytes: total_bytes.saturating_sub(header_bytes + table_bytes + payload_bytes),
+            total_bytes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use byteorder::LE;
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack_with;
+
+    #[test]
+    fn layout_report_adds_up_to_pack_size() {
+        let raw_pack = create_raw_test_pack_with::<LE>(0).unwrap();
+        let report = AssetPack::layout_report(&mut Cursor::new(raw_pack.clone())).unwrap();
+
+        assert_eq!(report.total_bytes, raw_pack.len() as u64);
+        assert_eq!(report.header_bytes, 88);
+        assert_eq!(report.padding_bytes, 0);
+        assert_eq!(
+            report.header_bytes + report.table_bytes + report.payload_bytes,
+            report.total_bytes
+        );
+
+        let raw_pack_with_gap = create_raw_test_pack_with::<LE>(16).unwrap();
+        let report_with_gap =
+            AssetPack::layout_report(&mut Cursor::new(raw_pack_with_gap.clone())).unwrap();
+
+        assert_eq!(report_with_gap.total_bytes, raw_pack_with_gap.len() as u64);
+        assert_eq!(report_with_gap.padding_bytes, 16);
+        assert_eq!(report_with_gap.table_bytes, report.table_bytes);
+        assert_eq!(report_with_gap.payload_bytes, report.payload_bytes);
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use anyhow::bail;
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::validation::{ValidationIssue, ValidationOptions};
+
+/// How bad a `ValidationIssue` is, see `AssetPack::lint`.
+/// Sorted from most to least severe.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Severity {
+    /// The pack is broken in Dungeondraft, or files get lost.
+    Error,
+    /// The pack works, but something is probably not as intended.
+    Warning,
+    /// Worth knowing about, but often deliberate.
+    Info,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Info => write!(f, "info"),
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Severity::Error),
+            "warning" => Ok(Severity::Warning),
+            "info" => Ok(Severity::Info),
+            _ => bail!("Unknown severity '{}', expected error, warning or info", s),
+        }
+    }
+}
+
+/// The severity of the issues found by each check, see `ValidationIssue::check`.
+pub const DEFAULT_SEVERITIES: [(&str, Severity); 16] = [
+    ("case-collisions", Severity::Error),
+    ("invalid-data-files", Severity::Error),
+    ("dangling-refs", Severity::Error),
+    ("extension-mismatches", Severity::Error),
+    ("set-contains-itself", Severity::Error),
+    ("unrecognized-locations", Severity::Warning),
+    ("set-named-like-tag", Severity::Warning),
+    ("oversized-textures", Severity::Warning),
+    ("many-files-per-tag", Severity::Warning),
+    ("tags-file-size", Severity::Warning),
+    ("untagged-objects", Severity::Info),
+    ("non-portable-names", Severity::Info),
+    ("json-encoding", Severity::Info),
+    ("redundant-tags", Severity::Info),
+    ("setless-tags", Severity::Info),
+    ("setless-objects", Severity::Info),
+];
+
+/// Textures larger than this are reported by `lint` by default, in pixels.
+pub const DEFAULT_MAX_TEXTURE_SIZE: u32 = 4096;
+
+impl AssetPack {
+    /// Runs every check of `validate`, with the limits in `options`, and groups the issues
+    /// by severity. `severities` changes the severity of some checks, by check name.
+    /// Severities without issues are left out.
+    pub fn lint(
+        &self,
+        options: &ValidationOptions,
+        severities: &HashMap<String, Severity>,
+    ) -> BTreeMap<Severity, Vec<ValidationIssue>> {
+        let options = ValidationOptions {
+            check_portable_names: true,
+            check_image_headers: true,
+            check_references: true,
+            check_untagged: true,
+            max_files_per_tag: options.max_files_per_tag,
+            max_texture_size: options.max_texture_size.or(Some(DEFAULT_MAX_TEXTURE_SIZE)),
+            check_encoding: true,
+            check_redundant_tags: true,
+            redundant_tag_subsets: options.redundant_tag_subsets,
+            check_sets: true,
+        };
+
+        let mut issues_by_severity: BTreeMap<Severity, Vec<ValidationIssue>> = BTreeMap::new();
+
+        for issue in self.validate(&options) {
+            let severity = severities
+                .get(issue.check())
+                .copied()
+                .unwrap_or_else(|| default_severity(issue.check()));
+
+            issues_by_severity.entry(severity).or_default().push(issue);
+        }
+
+        issues_by_severity
+    }
+}
+
+/// Fails if `check` is not the name of a check, see `ValidationIssue::check`.
+pub fn check_exists(check: &str) -> anyhow::Result<()> {
+    if DEFAULT_SEVERITIES.iter().any(|(name, _)| *name == check) {
+        Ok(())
+    } else {
+        let names: Vec<&str> = DEFAULT_SEVERITIES.iter().map(|(name, _)| *name).collect();
+        bail!(
+            "Unknown check '{}', expected one of: {}",
+            check,
+            names.join(", ")
+        )
+    }
+}
+
+fn default_severity(check: &str) -> Severity {
+    DEFAULT_SEVERITIES
+        .iter()
+        .find(|(name, _)| *name == check)
+        .map_or(Severity::Warning, |(_, severity)| *severity)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::lint::Severity;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+    use crate::asset_pack::validation::ValidationIssue;
+
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut data = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+        data.extend_from_slice(&[0, 0, 0, 13]);
+        data.extend_from_slice(b"IHDR");
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn lint_groups_issues_by_severity() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        // The test pack's own `random.png` is all zeroes, and tagged with `MyTag`.
+        pack.object_files.insert(
+            "textures/objects/random.png".to_string(),
+            png_header(64, 64),
+        );
+        pack.object_files.insert(
+            "textures/objects/Huge Map.png".to_string(),
+            png_header(8192, 1024),
+        );
+        pack.object_files.insert(
+            "textures/objects/huge map.png".to_string(),
+            png_header(64, 64),
+        );
+        pack.tags
+            .tags
+            .get_mut("MyTag")
+            .unwrap()
+            .insert("textures/objects/huge map.png".to_string());
+        pack.other_files
+            .insert("notes.txt".to_string(), b"todo".to_vec());
+
+        let issues = pack.lint(&Default::default(), &HashMap::new());
+
+        assert_eq!(
+            issues[&Severity::Error],
+            vec![
+                ValidationIssue::CaseCollision(vec![
+                    "textures/objects/Huge Map.png".to_string(),
+                    "textures/objects/huge map.png".to_string()
+                ]),
+                ValidationIssue::DanglingReference {
+                    tag: "Colorable".to_string(),
+                    path: "textures/objects/sample_cauldron.png".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            issues[&Severity::Warning],
+            vec![
+                ValidationIssue::UnrecognizedLocation("notes.txt".to_string()),
+                ValidationIssue::OversizedTexture {
+                    path: "textures/objects/Huge Map.png".to_string(),
+                    width: 8192,
+                    height: 1024
+                },
+            ]
+        );
+        assert_eq!(
+            issues[&Severity::Info],
+            vec![
+                ValidationIssue::NonPortableName("textures/objects/Huge Map.png".to_string()),
+                ValidationIssue::NonPortableName("textures/objects/huge map.png".to_string()),
+                ValidationIssue::UntaggedObject("textures/objects/Huge Map.png".to_string()),
+                ValidationIssue::TagNotInAnySet("Colorable".to_string()),
+            ]
+        );
+
+        let mut severities = HashMap::new();
+        severities.insert("dangling-refs".to_string(), Severity::Info);
+        severities.insert("untagged-objects".to_string(), Severity::Error);
+        let issues = pack.lint(&Default::default(), &severities);
+
+        assert_eq!(issues[&Severity::Error].len(), 2);
+        assert!(
+            issues[&Severity::Error].contains(&ValidationIssue::UntaggedObject(
+                "textures/objects/Huge Map.png".to_string()
+            ))
+        );
+        assert!(issues[&Severity::Info]
+            .iter()
+            .any(|issue| issue.check() == "dangling-refs"));
+    }
+
+    #[test]
+    fn lint_reports_json_encoding() {
+        let pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        pack.unpack_to_dir(dir.path(), &Default::default()).unwrap();
+
+        let tags_path = dir.path().join("data/default.dungeondraft_tags");
+        let tags = fs::read_to_string(&tags_path).unwrap();
+        fs::write(&tags_path, tags.replace(",", ",\r\n")).unwrap();
+
+        let meta_path = dir.path().join("pack.json");
+        let mut meta = b"\xEF\xBB\xBF".to_vec();
+        meta.extend(fs::read(&meta_path).unwrap());
+        fs::write(&meta_path, meta).unwrap();
+
+        let re_read_pack = AssetPack::from_directory(dir.path()).unwrap();
+        assert_eq!(re_read_pack.tags, pack.tags);
+        assert_eq!(re_read_pack.meta, pack.meta);
+
+        let issues = re_read_pack.lint(&Default::default(), &HashMap::new());
+        let encoding_issue = ValidationIssue::JsonEncoding {
+            path: "data/default.dungeondraft_tags".to_string(),
+            bom: false,
+            crlf: true,
+        };
+        assert!(issues[&Severity::Info].contains(&encoding_issue));
+        assert!(
+            issues[&Severity::Info].contains(&ValidationIssue::JsonEncoding {
+                path: "pack.json".to_string(),
+                bom: true,
+                crlf: false,
+            })
+        );
+        assert_eq!(
+            encoding_issue.to_string(),
+            "Json file has CRLF line endings: 'data/default.dungeondraft_tags'"
+        );
+    }
+}
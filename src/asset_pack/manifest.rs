@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::utils::{md5_hash, pretty_json};
+
+/// The size and md5 hash of every object and other file in a pack, to check a rebuilt pack
+/// against. `pack.json` and the tags file are left out, because they are written again
+/// every time the pack is built.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct Manifest {
+    pub files: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct ManifestEntry {
+    /// Size in bytes.
+    pub size: usize,
+    /// The md5 hash as lowercase hexadecimal.
+    pub md5: String,
+}
+
+/// A difference between a pack and a baseline manifest, see `Manifest::check`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ManifestMismatch {
+    /// In the baseline, but not in the pack.
+    Missing(String),
+    /// In the pack, but not in the baseline.
+    Unexpected(String),
+    /// In both, but with a different size or md5 hash.
+    Changed {
+        path: String,
+        expected: ManifestEntry,
+        actual: ManifestEntry,
+    },
+}
+
+impl Display for ManifestMismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestMismatch::Missing(path) => write!(f, "Missing file '{}'", path),
+            ManifestMismatch::Unexpected(path) => write!(f, "Unexpected file '{}'", path),
+            ManifestMismatch::Changed {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "File '{}' differs: expected {} bytes with md5 {}, got {} bytes with md5 {}",
+                path, expected.size, expected.md5, actual.size, actual.md5
+            ),
+        }
+    }
+}
+
+impl Manifest {
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(pretty_json(&json5::to_string(self)?))
+    }
+
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        Ok(json5::from_str(json)?)
+    }
+
+    /// Compares this manifest of a pack with a baseline manifest.
+    /// Returns the differences sorted by path, empty if they match.
+    pub fn check(&self, baseline: &Manifest) -> Vec<ManifestMismatch> {
+        let mut mismatches = vec![];
+
+        for (path, expected) in baseline.files.iter() {
+            match self.files.get(path) {
+                None => mismatches.push(ManifestMismatch::Missing(path.clone())),
+                Some(actual) if actual != expected => mismatches.push(ManifestMismatch::Changed {
+                    path: path.clone(),
+                    expected: expected.clone(),
+                    actual: actual.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+
+        for path in self.files.keys() {
+            if !baseline.files.contains_key(path) {
+                mismatches.push(ManifestMismatch::Unexpected(path.clone()));
+            }
+        }
+
+        mismatches.sort_by(|a, b| mismatch_path(a).cmp(mismatch_path(b)));
+        mismatches
+    }
+}
+
+fn mismatch_path(mismatch: &ManifestMismatch) -> &str {
+    match mismatch {
+        ManifestMismatch::Missing(path) | ManifestMismatch::Unexpected(path) => path,
+        ManifestMismatch::Changed { path, .. } => path,
+    }
+}
+
+impl AssetPack {
+    pub fn manifest(&self) -> Manifest {
+        Manifest {
+            files: self
+                .object_files
+                .iter()
+                .chain(self.other_files.iter())
+                .map(|(path, data)| {
+                    let md5: String = md5_hash(data)
+                        .iter()
+                        .map(|byte| format!("{:02x}", byte))
+                        .collect();
+
+                    (
+                        path.clone(),
+                        ManifestEntry {
+                            size: data.len(),
+                            md5,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::manifest::{Manifest, ManifestMismatch};
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn pack_matches_own_manifest_but_not_tampered_one() {
+        let pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+
+        let baseline = Manifest::from_json(&pack.manifest().to_json().unwrap()).unwrap();
+        assert_eq!(baseline, pack.manifest());
+        assert!(pack.manifest().check(&baseline).is_empty());
+
+        let mut tampered = baseline.clone();
+        tampered
+            .files
+            .get_mut("textures/objects/random.png")
+            .unwrap()
+            .md5 = "00000000000000000000000000000000".to_string();
+        let door = tampered.files.remove("textures/portals/door.png").unwrap();
+        tampered
+            .files
+            .insert("textures/portals/window.png".to_string(), door);
+
+        let mismatches = pack.manifest().check(&tampered);
+
+        assert_eq!(mismatches.len(), 3);
+        assert!(matches!(
+            &mismatches[0],
+            ManifestMismatch::Changed { path, .. } if path == "textures/objects/random.png"
+        ));
+        assert_eq!(
+            mismatches[1],
+            ManifestMismatch::Unexpected("textures/portals/door.png".to_string())
+        );
+        assert_eq!(
+            mismatches[2],
+            ManifestMismatch::Missing("textures/portals/window.png".to_string())
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+
+use anyhow::bail;
+use log::{info, warn};
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::utils::{md5_hash, MD5_BYTES};
+
+/// What would go wrong when merging packs, as found by `merge_report`.
+/// Each entry lists the ids of the packs involved.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct MergeReport {
+    /// Paths that are in multiple packs with different contents.
+    /// Only the file of the first pack would end up in the merged pack.
+    pub path_collisions: Vec<(String, Vec<String>)>,
+    /// Tags that are in multiple packs. Their files would end up in one tag.
+    pub tag_overlaps: Vec<(String, Vec<String>)>,
+    /// Pack ids that are used by multiple packs.
+    pub id_collisions: Vec<String>,
+}
+
+impl MergeReport {
+    pub fn is_empty(&self) -> bool {
+        self.path_collisions.is_empty()
+            && self.tag_overlaps.is_empty()
+            && self.id_collisions.is_empty()
+    }
+}
+
+impl Display for MergeReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (path, ids) in self.path_collisions.iter() {
+            writeln!(
+                f,
+                "Path '{}' has different contents in packs: {}",
+                path,
+                ids.join(", ")
+            )?;
+        }
+        for (tag, ids) in self.tag_overlaps.iter() {
+            writeln!(f, "Tag '{}' is in packs: {}", tag, ids.join(", "))?;
+        }
+        for id in self.id_collisions.iter() {
+            writeln!(f, "Pack id '{}' is used by multiple packs", id)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reports the collisions merging `packs` with `merge_packs` would run into,
+/// without merging them.
+pub fn merge_report(packs: &[AssetPack]) -> MergeReport {
+    let mut hashes_by_path: BTreeMap<&str, Vec<(&str, [u8; MD5_BYTES])>> = BTreeMap::new();
+    let mut packs_by_tag: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    let mut packs_by_id: BTreeMap<&str, usize> = BTreeMap::new();
+
+    for pack in packs {
+        let id = pack.meta.id.as_str();
+
+        for (path, data) in pack.object_files.iter().chain(pack.other_files.iter()) {
+            hashes_by_path
+                .entry(path)
+                .or_default()
+                .push((id, md5_hash(data)));
+        }
+
+        for tag in pack.tags.tags.keys() {
+            packs_by_tag.entry(tag).or_default().push(id);
+        }
+
+        *packs_by_id.entry(id).or_default() += 1;
+    }
+
+    let path_collisions = hashes_by_path
+        .into_iter()
+        .filter(|(_, hashes)| {
+            hashes
+                .iter()
+                .map(|(_, hash)| hash)
+                .collect::<BTreeSet<_>>()
+                .len()
+                > 1
+        })
+        .map(|(path, hashes)| {
+            (
+                path.to_owned(),
+                hashes.iter().map(|(id, _)| id.to_string()).collect(),
+            )
+        })
+        .collect();
+
+    let tag_overlaps = packs_by_tag
+        .into_iter()
+        .filter(|(_, ids)| ids.len() > 1)
+        .map(|(tag, ids)| {
+            (
+                tag.to_owned(),
+                ids.iter().map(|id| id.to_string()).collect(),
+            )
+        })
+        .collect();
+
+    let id_collisions = packs_by_id
+        .into_iter()
+        .filter(|(_, count)| *count > 1)
+        .map(|(id, _)| id.to_owned())
+        .collect();
+
+    MergeReport {
+        path_collisions,
+        tag_overlaps,
+        id_collisions,
+    }
+}
+
+/// Merges the packs into one, with the metadata of the first pack.
+/// Files are combined, a path that is in multiple packs keeps the file of the first one.
+/// Tags and tag sets with the same name are combined.
+/// Data files still refer to textures by the id of the pack they came from.
+pub fn merge_packs(packs: Vec<AssetPack>) -> anyhow::Result<AssetPack> {
+    let mut packs = packs.into_iter();
+
+    let mut merged = match packs.next() {
+        Some(pack) => pack,
+        None => bail!("No packs to merge"),
+    };
+
+    for pack in packs {
+        info!("Merging '{}' into '{}'", pack.meta.name, merged.meta.name);
+
+        merge_files(&mut merged.object_files, pack.object_files);
+        merge_files(&mut merged.other_files, pack.other_files);
+
+        for (tag, files) in pack.tags.tags {
+            merged.tags.tags.entry(tag).or_default().extend(files);
+        }
+        for (set, tags) in pack.tags.sets {
+            merged.tags.sets.entry(set).or_default().extend(tags);
+        }
+    }
+
+    Ok(merged)
+}
+
+fn merge_files(files: &mut HashMap<String, Vec<u8>>, new_files: HashMap<String, Vec<u8>>) {
+    for (path, data) in new_files {
+        match files.get(&path) {
+            Some(existing) if *existing != data => {
+                warn!("Keeping the first version of '{}', the packs differ.", path)
+            }
+            Some(_) => {}
+            None => {
+                files.insert(path, data);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::merge::{merge_packs, merge_report};
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn merge_report_path_collision() {
+        let read_pack =
+            || AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+
+        let first = read_pack();
+        let mut second = read_pack();
+        second.meta.id = "ABCDEFGH".to_string();
+        second
+            .object_files
+            .insert("textures/objects/random.png".to_string(), vec![1, 2, 3]);
+
+        let report = merge_report(&[first, second]);
+
+        assert_eq!(
+            report.path_collisions,
+            vec![(
+                "textures/objects/random.png".to_string(),
+                vec!["12345678".to_string(), "ABCDEFGH".to_string()]
+            )]
+        );
+        assert_eq!(report.tag_overlaps.len(), 2);
+        assert!(report.id_collisions.is_empty());
+    }
+
+    #[test]
+    fn merge_keeps_first_file() {
+        let first =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        let mut second =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        second
+            .object_files
+            .insert("textures/objects/random.png".to_string(), vec![1, 2, 3]);
+        second
+            .object_files
+            .insert("textures/objects/extra.png".to_string(), vec![4]);
+
+        let original = first.object_files["textures/objects/random.png"].clone();
+        let merged = merge_packs(vec![first, second]).unwrap();
+
+        assert_eq!(merged.object_files["textures/objects/random.png"], original);
+        assert!(merged
+            .object_files
+            .contains_key("textures/objects/extra.png"));
+        assert!(merge_packs(vec![]).is_err());
+    }
+}
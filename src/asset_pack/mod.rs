@@ -1,11 +1,66 @@
+#[allow(clippy::module_inception)]
 pub mod asset_pack;
+mod change_set;
+mod classification;
 mod color_overrides;
-mod file_meta_data;
+mod compression;
+mod coverage;
+mod data_files;
+mod dedupe;
+mod deflate;
+mod diff;
+mod directory;
+mod dot;
+pub mod file_meta_data;
+mod file_times;
 mod godot_version;
+#[cfg(feature = "image-codec")]
+mod image_codec;
+mod layout_report;
+mod lint;
+mod manifest;
+mod merge;
+mod pack_layout;
 mod pack_meta;
 mod path_utils;
+mod read_options;
+mod reorganize;
+mod sample;
+mod schema;
+mod split;
+mod summary;
+mod tag_files;
+mod tag_index;
+mod tag_patterns;
 mod tags;
-mod test_asset_pack_serialization;
+pub(crate) mod test_asset_pack_serialization;
+mod thumbnails;
+mod transform;
 mod utils;
+mod validation;
+mod write_options;
+mod zip_archive;
 
 pub use asset_pack::*;
+pub use change_set::*;
+pub use classification::*;
+pub use coverage::*;
+pub use diff::*;
+pub use directory::*;
+pub use godot_version::GodotVersion;
+#[cfg(feature = "image-codec")]
+pub use image_codec::ImageCodec;
+pub use layout_report::*;
+pub use lint::*;
+pub use manifest::*;
+pub use merge::*;
+pub use pack_layout::*;
+pub use pack_meta::{generate_pack_id_from_seed, PACK_ID_LENGTH};
+pub use read_options::*;
+pub use schema::*;
+pub use summary::*;
+pub use tag_files::*;
+pub use tag_index::*;
+pub use transform::*;
+pub use validation::*;
+pub use write_options::*;
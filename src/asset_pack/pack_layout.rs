@@ -0,0 +1,37 @@
+use crate::asset_pack::path_utils::{PACK_FILE_NAME, TAGS_FILE_NAME};
+
+/// Where the metadata and tags files are stored in the pack.
+/// Kept so a pack that was read can be written back with the same layout.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PackLayout {
+    /// Name of the root json file in `res://packs/`.
+    /// `None` means `<pack-id>.json`, which is what Dungeondraft uses.
+    pub root_json_name: Option<String>,
+    /// Path of the copy of the metadata, relative to the pack directory.
+    pub pack_json_path: String,
+    /// Path of the tags file, relative to the pack directory.
+    pub tags_file_path: String,
+    /// Write the files sorted by path and the tags sorted by name, so the written pack
+    /// only depends on its contents. Set by `AssetPack::normalize`.
+    pub canonical_order: bool,
+}
+
+impl PackLayout {
+    pub fn root_json_name(&self, pack_id: &str) -> String {
+        match &self.root_json_name {
+            Some(name) => name.clone(),
+            None => pack_id.to_owned() + ".json",
+        }
+    }
+}
+
+impl Default for PackLayout {
+    fn default() -> Self {
+        PackLayout {
+            root_json_name: None,
+            pack_json_path: PACK_FILE_NAME.to_owned(),
+            tags_file_path: TAGS_FILE_NAME.to_owned(),
+            canonical_order: false,
+        }
+    }
+}
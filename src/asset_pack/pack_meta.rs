@@ -1,7 +1,15 @@
 use crate::asset_pack::color_overrides::ColorOverrides;
+use crate::asset_pack::utils::md5_hash;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Deserialize, Serialize, PartialEq)]
+/// Key of the time the pack was processed, see `PackMeta::stamp`.
+pub const BUILD_TIMESTAMP_KEY: &str = "build_timestamp";
+/// Key of the program that processed the pack, see `PackMeta::stamp`.
+pub const TOOL_VERSION_KEY: &str = "tool_version";
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
 pub struct PackMeta {
     pub name: String,
     pub id: String,
@@ -9,4 +17,239 @@ pub struct PackMeta {
     pub author: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub custom_color_overrides: Option<ColorOverrides>,
+    /// Fields Dungeondraft does not know about, kept so they are written back unchanged.
+    #[serde(flatten)]
+    pub extra: BTreeMap<String, JsonValue>,
+}
+
+/// Any json value, for the fields of `PackMeta::extra`.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[serde(untagged)]
+pub enum JsonValue {
+    Null(()),
+    Bool(bool),
+    Integer(i64),
+    Number(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(BTreeMap<String, JsonValue>),
+}
+
+impl PackMeta {
+    /// Increases the version:
+    /// - A plain number is incremented: `1` becomes `2`.
+    /// - A semantic version gets its patch number incremented: `1.2.3` becomes `1.2.4`.
+    /// - Anything else gets `.1` appended: `beta` becomes `beta.1`.
+    ///   So does a number that can't be incremented without overflowing.
+    pub fn bump_version(&mut self) {
+        let version = self.version.trim();
+
+        let bumped = if let Ok(number) = version.parse::<u64>() {
+            number.checked_add(1).map(|number| number.to_string())
+        } else if let Some((major, minor, patch)) = parse_semver(version) {
+            patch
+                .checked_add(1)
+                .map(|patch| format!("{}.{}.{}", major, minor, patch))
+        } else {
+            None
+        };
+
+        self.version = bumped.unwrap_or_else(|| format!("{}.1", version));
+    }
+
+    /// Sets the color overrides to the values Dungeondraft gives new packs,
+    /// which leave the colors alone.
+    pub fn reset_color_overrides(&mut self) {
+        self.custom_color_overrides = Some(ColorOverrides::default());
+    }
+
+    /// Records when and by which program the pack was processed, in the `build_timestamp`
+    /// (ISO 8601, in UTC) and `tool_version` fields. Dungeondraft ignores them.
+    pub fn stamp(&mut self, time: SystemTime, tool_version: &str) {
+        self.extra.insert(
+            BUILD_TIMESTAMP_KEY.to_owned(),
+            JsonValue::String(iso_8601_timestamp(time)),
+        );
+        self.extra.insert(
+            TOOL_VERSION_KEY.to_owned(),
+            JsonValue::String(tool_version.to_owned()),
+        );
+    }
+
+    /// Removes the fields added by `stamp`, so the pack only depends on its contents.
+    pub fn remove_stamp(&mut self) {
+        self.extra.remove(BUILD_TIMESTAMP_KEY);
+        self.extra.remove(TOOL_VERSION_KEY);
+    }
+
+    /// Describes each field that differs from `other`, e.g. `version: '1' != '2'`.
+    pub fn differing_fields(&self, other: &PackMeta) -> Vec<String> {
+        let mut differences = vec![];
+
+        let fields = [
+            ("name", &self.name, &other.name),
+            ("id", &self.id, &other.id),
+            ("version", &self.version, &other.version),
+            ("author", &self.author, &other.author),
+        ];
+        for (field, value, other_value) in fields.iter() {
+            if value != other_value {
+                differences.push(format!("{}: '{}' != '{}'", field, value, other_value));
+            }
+        }
+
+        if self.custom_color_overrides != other.custom_color_overrides {
+            differences.push(format!(
+                "custom_color_overrides: {:?} != {:?}",
+                self.custom_color_overrides, other.custom_color_overrides
+            ));
+        }
+
+        differences
+    }
+}
+
+/// Length of the ids Dungeondraft generates for new packs.
+pub const PACK_ID_LENGTH: usize = 8;
+const PACK_ID_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Derives a Dungeondraft-style pack id from `seed`, e.g. the name of the pack.
+/// The same seed always gives the same id, so builds of a pack are reproducible.
+pub fn generate_pack_id_from_seed(seed: &str) -> String {
+    md5_hash(seed.as_bytes())
+        .iter()
+        .take(PACK_ID_LENGTH)
+        .map(|byte| PACK_ID_ALPHABET[*byte as usize % PACK_ID_ALPHABET.len()] as char)
+        .collect()
+}
+
+/// Formats the time like `2021-10-05T14:03:09Z`.
+fn iso_8601_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, seconds_of_day) = (seconds / 86400, seconds % 86400);
+
+    // Converts days since 1970-01-01 into a date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+fn parse_semver(version: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) => {
+            Some((major, minor, patch))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use crate::asset_pack::pack_meta::{
+        generate_pack_id_from_seed, JsonValue, PackMeta, PACK_ID_LENGTH,
+    };
+
+    fn meta_with_version(version: &str) -> PackMeta {
+        PackMeta {
+            name: "".to_string(),
+            id: "".to_string(),
+            version: version.to_string(),
+            author: "".to_string(),
+            custom_color_overrides: None,
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn bump_version() {
+        let mut meta = meta_with_version("1");
+        meta.bump_version();
+        assert_eq!(meta.version, "2");
+
+        let mut meta = meta_with_version("1.2.3");
+        meta.bump_version();
+        assert_eq!(meta.version, "1.2.4");
+
+        let mut meta = meta_with_version("1.2-beta");
+        meta.bump_version();
+        assert_eq!(meta.version, "1.2-beta.1");
+
+        let mut meta = meta_with_version("18446744073709551615");
+        meta.bump_version();
+        assert_eq!(meta.version, "18446744073709551615.1");
+
+        let mut meta = meta_with_version("1.2.18446744073709551615");
+        meta.bump_version();
+        assert_eq!(meta.version, "1.2.18446744073709551615.1");
+    }
+
+    #[test]
+    fn pack_id_from_seed_is_stable() {
+        let id = generate_pack_id_from_seed("My Pack");
+
+        assert_eq!(id.len(), PACK_ID_LENGTH);
+        assert!(id.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(generate_pack_id_from_seed("My Pack"), id);
+        assert_ne!(generate_pack_id_from_seed("My Other Pack"), id);
+    }
+
+    #[test]
+    fn unknown_fields_are_kept() {
+        let json = r#"{"name": "a", "id": "ABCDEFGH", "version": "1", "author": "me",
+            "homepage": "https://example.com", "build": 12, "tags": ["x", null, true, 1.5]}"#;
+        let meta: PackMeta = json5::from_str(json).unwrap();
+
+        assert_eq!(meta.extra.len(), 3);
+        assert_eq!(meta.extra["build"], JsonValue::Integer(12));
+
+        let written = json5::to_string(&meta).unwrap();
+        let re_read: PackMeta = json5::from_str(&written).unwrap();
+        assert_eq!(re_read, meta);
+    }
+
+    #[test]
+    fn stamp_adds_timestamp_and_tool_version() {
+        let mut meta = meta_with_version("1");
+        // 2021-10-05 14:03:09 UTC.
+        meta.stamp(
+            UNIX_EPOCH + Duration::from_secs(1_633_442_589),
+            "dd_asset_tools 0.1.0",
+        );
+
+        let written = json5::to_string(&meta).unwrap();
+        assert!(written.contains(r#""build_timestamp":"2021-10-05T14:03:09Z""#));
+        assert!(written.contains(r#""tool_version":"dd_asset_tools 0.1.0""#));
+
+        meta.remove_stamp();
+        assert!(meta.extra.is_empty());
+        assert_eq!(meta, meta_with_version("1"));
+    }
 }
@@ -1,20 +1,23 @@
 use std::ffi::OsStr;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 pub const RESOURCE_PATH_PREFIX: &str = "res://";
 pub const ASSET_PACK_PREFIX: &str = "packs/";
 pub const PACK_FILE_NAME: &str = "pack.json";
 pub const TAGS_FILE_NAME: &str = "data/default.dungeondraft_tags";
 pub const OBJECT_FILES_PREFIX: &str = "textures/objects/";
+pub const TEXTURES_PREFIX: &str = "textures/";
+pub const DATA_PREFIX: &str = "data/";
+pub const THUMBNAILS_PREFIX: &str = "thumbnails/";
 
 /// Returns true for `<pack-id>.json` files without any parent directory.
-pub fn is_root_json_file(path: &PathBuf) -> bool {
+pub fn is_root_json_file(path: &Path) -> bool {
     path.extension().unwrap_or(OsStr::new("")) == OsStr::new("json")
         && path.parent() == Some(Path::new(""))
 }
 
 /// Returns true for `pack.json` files, regardless of parent directory.
-pub fn is_pack_file(path: &PathBuf) -> bool {
+pub fn is_pack_file(path: &Path) -> bool {
     path.file_name() == Some(OsStr::new(PACK_FILE_NAME))
 }
 
@@ -27,3 +30,183 @@ pub fn is_tags_file(path: &str) -> bool {
 pub fn is_objects_file(path: &str) -> bool {
     path.starts_with(OBJECT_FILES_PREFIX)
 }
+
+/// Returns the `textures/` subcategory of a file, e.g. `walls` for `textures/walls/stone.png`.
+/// Returns `None` for files outside of `textures/`.
+pub fn texture_category(path: &str) -> Option<&str> {
+    path.strip_prefix(TEXTURES_PREFIX)?
+        .split_once('/')
+        .map(|(category, _)| category)
+}
+
+/// Returns the directory directly under `textures/objects/` that a file is in,
+/// e.g. `furniture` for `textures/objects/furniture/old/chair.png`.
+/// Returns `None` for files directly in `textures/objects/`, or outside of it.
+pub fn object_folder(path: &str) -> Option<&str> {
+    path.strip_prefix(OBJECT_FILES_PREFIX)?
+        .split_once('/')
+        .map(|(folder, _)| folder)
+        .filter(|folder| !folder.is_empty())
+}
+
+/// Image formats Dungeondraft can use for objects.
+pub const OBJECT_IMAGE_EXTENSIONS: [&str; 4] = ["png", "webp", "jpg", "jpeg"];
+
+/// Returns the extension of the path in lower case, if it is one of `OBJECT_IMAGE_EXTENSIONS`.
+pub fn image_extension(path: &str) -> Option<String> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(OsStr::to_str)?
+        .to_ascii_lowercase();
+
+    OBJECT_IMAGE_EXTENSIONS
+        .contains(&extension.as_str())
+        .then_some(extension)
+}
+
+/// Returns the image format an extension stands for, e.g. `jpeg` for both `jpg` and `jpeg`.
+pub fn image_format(extension: &str) -> &str {
+    match extension {
+        "jpg" => "jpeg",
+        _ => extension,
+    }
+}
+
+/// Returns true if the path could be an object file of some pack: it is in `textures/objects/`,
+/// has an image extension, and has no backslashes or empty directory names.
+pub fn is_well_formed_object_path(path: &str) -> bool {
+    is_objects_file(path)
+        && !path.contains('\\')
+        && !path.split('/').any(str::is_empty)
+        && image_extension(path).is_some()
+}
+
+/// Returns true if path starts with `thumbnails/`, where Dungeondraft keeps
+/// the downscaled previews it generates for its UI.
+pub fn is_thumbnail_file(path: &str) -> bool {
+    path.starts_with(THUMBNAILS_PREFIX)
+}
+
+/// Returns true if path starts with `data/`.
+pub fn is_data_file(path: &str) -> bool {
+    path.starts_with(DATA_PREFIX)
+}
+
+/// The `textures/` subcategories that are only used by Dungeondraft when a data file refers to them.
+pub const DATA_DEFINED_CATEGORIES: [&str; 3] = ["walls", "tilesets", "paths"];
+pub const TEXTURE_DATA_FILE_EXTENSIONS: [&str; 3] = [
+    "dungeondraft_wall",
+    "dungeondraft_tileset",
+    "dungeondraft_path",
+];
+
+/// Returns true for the wall, tileset and path definitions in `data/`.
+pub fn is_texture_data_file(path: &str) -> bool {
+    is_data_file(path)
+        && Path::new(path)
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|extension| TEXTURE_DATA_FILE_EXTENSIONS.contains(&extension))
+}
+
+/// Returns true for any `data/` file with a `dungeondraft_*` extension,
+/// which Dungeondraft reads as json.
+pub fn is_dungeondraft_data_file(path: &str) -> bool {
+    is_data_file(path)
+        && Path::new(path)
+            .extension()
+            .and_then(OsStr::to_str)
+            .is_some_and(|extension| extension.starts_with("dungeondraft_"))
+}
+
+/// Returns the pack id of a `res://packs/<pack-id>.json` path.
+pub fn root_json_pack_id(path: &str) -> Option<&str> {
+    let id = path
+        .strip_prefix(RESOURCE_PATH_PREFIX)?
+        .strip_prefix(ASSET_PACK_PREFIX)?
+        .strip_suffix(".json")?;
+
+    if id.contains('/') {
+        None
+    } else {
+        Some(id)
+    }
+}
+
+/// Returns the pack id of a `res://packs/<pack-id>/...` path.
+pub fn path_pack_id(path: &str) -> Option<&str> {
+    path.strip_prefix(RESOURCE_PATH_PREFIX)?
+        .strip_prefix(ASSET_PACK_PREFIX)?
+        .split_once('/')
+        .map(|(id, _rest)| id)
+}
+
+/// Turns `res://packs/<pack-id>/textures/bla.png` into `textures/bla.png`.
+/// `res://` and `packs/` are each only stripped when present.
+/// When the pack id is known, the first directory is only stripped if it is that id.
+/// Otherwise it is only stripped if the path is in `packs/`.
+/// A path that is just `res://packs/<pack-id>` becomes empty.
+pub fn strip_pack_prefix<'a>(path: &'a str, pack_id: Option<&str>) -> &'a str {
+    let path = path.strip_prefix(RESOURCE_PATH_PREFIX).unwrap_or(path);
+    let (path, in_packs_dir) = match path.strip_prefix(ASSET_PACK_PREFIX) {
+        Some(path) => (path, true),
+        None => (path, false),
+    };
+
+    match pack_id {
+        Some(id) => match path.strip_prefix(id) {
+            Some("") => "",
+            Some(rest) => rest.strip_prefix('/').unwrap_or(path),
+            None => path,
+        },
+        None if in_packs_dir => path.split_once('/').map_or(path, |(_id, rest)| rest),
+        None => path,
+    }
+}
+
+/// Returns true if the path contains spaces or non-ASCII characters,
+/// which some tools can't handle.
+pub fn is_non_portable_name(path: &str) -> bool {
+    path.chars().any(|c| c == ' ' || !c.is_ascii())
+}
+
+/// Replaces spaces with underscores, and non-ASCII characters with their closest ASCII
+/// equivalent. Characters without an obvious equivalent are removed.
+pub fn portable_name(path: &str) -> String {
+    let mut name = String::with_capacity(path.len());
+
+    for c in path.chars() {
+        match c {
+            ' ' => name.push('_'),
+            c if c.is_ascii() => name.push(c),
+            c => name.push_str(transliterate(c)),
+        }
+    }
+
+    name
+}
+
+fn transliterate(c: char) -> &'static str {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => "a",
+        'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' => "A",
+        'æ' => "ae",
+        'Æ' => "AE",
+        'ç' => "c",
+        'Ç' => "C",
+        'è' | 'é' | 'ê' | 'ë' => "e",
+        'È' | 'É' | 'Ê' | 'Ë' => "E",
+        'ì' | 'í' | 'î' | 'ï' => "i",
+        'Ì' | 'Í' | 'Î' | 'Ï' => "I",
+        'ñ' => "n",
+        'Ñ' => "N",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => "o",
+        'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' => "O",
+        'ù' | 'ú' | 'û' | 'ü' => "u",
+        'Ù' | 'Ú' | 'Û' | 'Ü' => "U",
+        'ý' | 'ÿ' => "y",
+        'Ý' => "Y",
+        'ß' => "ss",
+        _ => "",
+    }
+}
@@ -0,0 +1,21 @@
+use crate::asset_pack::classification::ClassificationRules;
+
+/// Options influencing how `AssetPack::from_read_with_options` interprets the pack file.
+#[derive(Debug, Default, Clone)]
+pub struct ReadOptions {
+    pub classification_rules: ClassificationRules,
+    /// Also read the `pack.json` copy of the metadata, and warn about every field in which it
+    /// differs from the root json file. Normally only the root json file is read.
+    /// Either way, both copies are written with the same contents.
+    pub check_meta_consistency: bool,
+    /// When checking the metadata consistency, use the `pack.json` copy instead of
+    /// the root json file if they differ.
+    pub prefer_pack_json: bool,
+    /// Number of bytes before the godot version, for packs with a stripped or custom header.
+    /// `None` means the usual 4-byte magic number, which is then also checked.
+    /// The file offsets in the table are taken to be relative to a standard 4-byte header.
+    pub header_len: Option<usize>,
+    /// Refuse to read packs whose files add up to more than this many bytes,
+    /// instead of running out of memory holding them all.
+    pub max_memory: Option<u64>,
+}
@@ -0,0 +1,174 @@
+use log::{info, warn};
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::path_utils::OBJECT_FILES_PREFIX;
+
+impl AssetPack {
+    /// Moves all object files directly into `textures/objects/`, keeping their file name.
+    /// When the name is already taken, a number is appended: `rock.png` becomes `rock_2.png`.
+    /// Returns the number of moved files.
+    pub fn flatten_objects(&mut self) -> usize {
+        let moved = self.move_objects(|_, _| Some(OBJECT_FILES_PREFIX.to_owned()));
+
+        info!(
+            "Moved {} object files into '{}'.",
+            moved, OBJECT_FILES_PREFIX
+        );
+
+        moved
+    }
+
+    /// Moves each object file into `textures/objects/<tag>/`, where `<tag>` is the first of its
+    /// tags in alphabetical order. Files without tags are left where they are.
+    /// Name collisions are resolved like in `flatten_objects`.
+    /// Returns the number of moved files.
+    pub fn group_objects_by_tag(&mut self) -> usize {
+        let moved = self.move_objects(|pack, path| {
+            pack.tags
+                .tags
+                .iter()
+                .filter(|(_, files)| files.contains(path))
+                .map(|(tag, _)| tag)
+                .min()
+                .map(|tag| format!("{}{}/", OBJECT_FILES_PREFIX, tag.replace('/', "_")))
+        });
+
+        info!("Moved {} object files into tag directories.", moved);
+
+        moved
+    }
+
+    /// Moves each file in `textures/objects/` into the directory returned by `target_dir`,
+    /// in order of path. Files for which it returns `None` stay where they are.
+    fn move_objects<F>(&mut self, target_dir: F) -> usize
+    where
+        F: Fn(&AssetPack, &str) -> Option<String>,
+    {
+        let mut paths: Vec<String> = self
+            .object_files
+            .keys()
+            .filter(|path| path.starts_with(OBJECT_FILES_PREFIX))
+            .cloned()
+            .collect();
+        paths.sort();
+
+        let mut moved = 0;
+
+        for path in paths {
+            let dir = match target_dir(self, &path) {
+                Some(dir) => dir,
+                None => continue,
+            };
+            let file_name = path.rsplit('/').next().unwrap_or(&path);
+
+            if path == dir.clone() + file_name {
+                continue;
+            }
+
+            let new_path = self.free_path(&dir, file_name);
+
+            match self.move_file(&path, &new_path) {
+                Ok(_) => moved += 1,
+                Err(e) => warn!("Could not move '{}': {}", path, e),
+            }
+        }
+
+        moved
+    }
+
+    /// `dir` + `file_name`, with a number appended to the file stem if that path is taken.
+    fn free_path(&self, dir: &str, file_name: &str) -> String {
+        let is_taken = |path: &str| {
+            self.object_files.contains_key(path) || self.other_files.contains_key(path)
+        };
+
+        let path = dir.to_owned() + file_name;
+        if !is_taken(&path) {
+            return path;
+        }
+
+        let (stem, extension) = match file_name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+            _ => (file_name, String::new()),
+        };
+
+        (2..)
+            .map(|number| format!("{}{}_{}{}", dir, stem, number, extension))
+            .find(|path| !is_taken(path))
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn flatten_nested_objects() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.object_files
+            .insert("textures/objects/furniture/chair.png".to_string(), vec![1]);
+        pack.object_files.insert(
+            "textures/objects/furniture/old/chair.png".to_string(),
+            vec![2],
+        );
+        pack.object_files.insert(
+            "textures/objects/rocks/big/boulder.png".to_string(),
+            vec![3],
+        );
+        pack.tags.tags.get_mut("MyTag").unwrap().extend(vec![
+            "textures/objects/furniture/old/chair.png".to_string(),
+            "textures/objects/rocks/big/boulder.png".to_string(),
+        ]);
+
+        assert_eq!(pack.flatten_objects(), 3);
+
+        assert_eq!(pack.object_files["textures/objects/chair.png"], vec![1]);
+        assert_eq!(pack.object_files["textures/objects/chair_2.png"], vec![2]);
+        assert_eq!(pack.object_files["textures/objects/boulder.png"], vec![3]);
+        assert!(pack
+            .object_files
+            .keys()
+            .all(|path| !path["textures/objects/".len()..].contains('/')));
+
+        let tagged = pack.get_files_in_tag("MyTag").unwrap();
+        assert!(tagged.contains("textures/objects/chair_2.png"));
+        assert!(tagged.contains("textures/objects/boulder.png"));
+        assert!(!tagged.contains("textures/objects/furniture/old/chair.png"));
+
+        // Already flat.
+        assert_eq!(pack.flatten_objects(), 0);
+    }
+
+    #[test]
+    fn group_objects_by_first_tag() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.object_files
+            .insert("textures/objects/random.png".to_string(), vec![1]);
+        pack.object_files
+            .insert("textures/objects/untagged.png".to_string(), vec![2]);
+        pack.tags
+            .tags
+            .get_mut("Colorable")
+            .unwrap()
+            .insert("textures/objects/random.png".to_string());
+
+        assert_eq!(pack.group_objects_by_tag(), 1);
+
+        assert!(pack
+            .object_files
+            .contains_key("textures/objects/Colorable/random.png"));
+        assert!(pack
+            .object_files
+            .contains_key("textures/objects/untagged.png"));
+        assert!(pack
+            .get_files_in_tag("MyTag")
+            .unwrap()
+            .contains("textures/objects/Colorable/random.png"));
+    }
+}
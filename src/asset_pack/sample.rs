@@ -0,0 +1,135 @@
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use anyhow::bail;
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::compression::decompress_file;
+use crate::asset_pack::directory::write_unpacked_file;
+use crate::asset_pack::summary::PackSummary;
+use crate::asset_pack::utils::{md5_hash, MD5_BYTES};
+
+impl PackSummary {
+    /// Picks `count` of the object files at random, sorted by path.
+    /// The same seed always picks the same files, so a sample can be reproduced.
+    pub fn sample_objects(&self, count: usize, seed: u64) -> Vec<String> {
+        let mut ranked: Vec<([u8; MD5_BYTES], &String)> = self
+            .object_files
+            .keys()
+            .map(|path| (md5_hash(format!("{}:{}", seed, path).as_bytes()), path))
+            .collect();
+        ranked.sort();
+
+        let mut sample: Vec<String> = ranked
+            .into_iter()
+            .take(count)
+            .map(|(_, path)| path.clone())
+            .collect();
+        sample.sort();
+        sample
+    }
+}
+
+impl AssetPack {
+    /// Extracts the files at `paths`, relative to the pack, into `dir`, reading only the
+    /// file table and those files. Files that already exist in `dir` are skipped,
+    /// unless `overwrite`. Returns the number of files written.
+    pub fn extract_files<R: Read + Seek>(
+        data: &mut R,
+        paths: &[String],
+        dir: &Path,
+        overwrite: bool,
+    ) -> anyhow::Result<usize> {
+        let (_, files_meta) = Self::read_file_table(data, None)?;
+        let pack_length = data.seek(SeekFrom::End(0))?;
+
+        let mut written = 0;
+
+        for path in paths {
+            let meta = match files_meta.iter().find(|meta| &meta.path == path) {
+                Some(meta) => meta,
+                None => bail!("The pack does not contain '{}'", path),
+            };
+
+            let file_data = Self::read_file_data(data, meta, pack_length)?;
+            let file_data = decompress_file(meta, &file_data)?.unwrap_or(file_data);
+            if write_unpacked_file(dir, path, &file_data, overwrite)? {
+                written += 1;
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    fn raw_pack_with_objects(count: usize) -> Vec<u8> {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        for i in 0..count {
+            pack.object_files
+                .insert(format!("textures/objects/{:03}.png", i), vec![i as u8]);
+        }
+
+        let mut raw_pack = vec![];
+        pack.to_write(&mut raw_pack).unwrap();
+        raw_pack
+    }
+
+    #[test]
+    fn same_seed_gives_same_sample() {
+        let raw_pack = raw_pack_with_objects(100);
+
+        let sample = |seed| {
+            AssetPack::read_metadata_only(&mut Cursor::new(&raw_pack))
+                .unwrap()
+                .sample_objects(5, seed)
+        };
+
+        let first = sample(42);
+        assert_eq!(first.len(), 5);
+        assert!(first.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(sample(42), first);
+        assert_ne!(sample(43), first);
+
+        let summary = AssetPack::read_metadata_only(&mut Cursor::new(&raw_pack)).unwrap();
+        assert_eq!(summary.sample_objects(1000, 42).len(), 101);
+        assert!(summary.sample_objects(0, 42).is_empty());
+    }
+
+    #[test]
+    fn extract_sampled_files() {
+        let raw_pack = raw_pack_with_objects(10);
+        let summary = AssetPack::read_metadata_only(&mut Cursor::new(&raw_pack)).unwrap();
+        let sample = summary.sample_objects(3, 7);
+
+        let dir = tempfile::tempdir().unwrap();
+        let written =
+            AssetPack::extract_files(&mut Cursor::new(&raw_pack), &sample, dir.path(), false)
+                .unwrap();
+
+        assert_eq!(written, 3);
+        for path in sample.iter() {
+            assert!(dir.path().join(path).is_file());
+        }
+        assert_eq!(
+            fs::read_dir(dir.path().join("textures/objects"))
+                .unwrap()
+                .count(),
+            3
+        );
+
+        let missing = vec!["textures/objects/gone.png".to_string()];
+        assert!(
+            AssetPack::extract_files(&mut Cursor::new(&raw_pack), &missing, dir.path(), false)
+                .is_err()
+        );
+    }
+}
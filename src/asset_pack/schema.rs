@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::asset_pack::utils::pretty_json;
+
+const SCHEMA_VERSION: &str = "http://json-schema.org/draft-07/schema#";
+
+/// The subset of JSON Schema needed to describe the json files in a pack.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq)]
+pub struct JsonSchema {
+    #[serde(rename = "$schema", skip_serializing_if = "Option::is_none", default)]
+    pub schema: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub title: Option<String>,
+    #[serde(rename = "type")]
+    pub schema_type: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub properties: BTreeMap<String, JsonSchema>,
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub required: Vec<String>,
+    #[serde(
+        rename = "additionalProperties",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub additional_properties: Option<Box<JsonSchema>>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub items: Option<Box<JsonSchema>>,
+    #[serde(
+        rename = "uniqueItems",
+        skip_serializing_if = "Option::is_none",
+        default
+    )]
+    pub unique_items: Option<bool>,
+}
+
+impl JsonSchema {
+    fn of_type(schema_type: &str) -> Self {
+        JsonSchema {
+            schema_type: schema_type.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// An object with the given properties, all of which are required.
+    fn object(properties: Vec<(&str, JsonSchema)>) -> Self {
+        JsonSchema {
+            required: properties
+                .iter()
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            properties: properties
+                .into_iter()
+                .map(|(name, schema)| (name.to_owned(), schema))
+                .collect(),
+            ..JsonSchema::of_type("object")
+        }
+    }
+
+    /// An object mapping any name to a list of unique strings.
+    fn string_sets() -> Self {
+        JsonSchema {
+            additional_properties: Some(Box::new(JsonSchema {
+                items: Some(Box::new(JsonSchema::of_type("string"))),
+                unique_items: Some(true),
+                ..JsonSchema::of_type("array")
+            })),
+            ..JsonSchema::of_type("object")
+        }
+    }
+
+    fn root(self, title: &str) -> Self {
+        JsonSchema {
+            schema: Some(SCHEMA_VERSION.to_owned()),
+            title: Some(title.to_owned()),
+            ..self
+        }
+    }
+
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(pretty_json(&json5::to_string(self)?))
+    }
+}
+
+/// Schema of `pack.json`, see `PackMeta` and `ColorOverrides`.
+pub fn pack_meta_schema() -> JsonSchema {
+    let color_overrides = JsonSchema::object(vec![
+        ("enabled", JsonSchema::of_type("boolean")),
+        ("min_redness", JsonSchema::of_type("number")),
+        ("min_saturation", JsonSchema::of_type("number")),
+        ("red_tolerance", JsonSchema::of_type("number")),
+    ]);
+
+    let mut schema = JsonSchema::object(vec![
+        ("name", JsonSchema::of_type("string")),
+        ("id", JsonSchema::of_type("string")),
+        ("version", JsonSchema::of_type("string")),
+        ("author", JsonSchema::of_type("string")),
+    ]);
+    // Optional, so it is not added to `required`.
+    schema
+        .properties
+        .insert("custom_color_overrides".to_owned(), color_overrides);
+
+    schema.root("PackMeta")
+}
+
+/// Schema of the tags file, see `Tags`.
+pub fn tags_schema() -> JsonSchema {
+    JsonSchema::object(vec![
+        ("tags", JsonSchema::string_sets()),
+        ("sets", JsonSchema::string_sets()),
+    ])
+    .root("Tags")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::asset_pack::schema::{pack_meta_schema, tags_schema, JsonSchema};
+
+    #[test]
+    fn pack_meta_schema_requires_fields() {
+        let schema: JsonSchema = json5::from_str(&pack_meta_schema().to_json().unwrap()).unwrap();
+
+        for field in &["name", "id", "version", "author"] {
+            assert!(schema.required.contains(&field.to_string()));
+            assert_eq!(schema.properties[*field].schema_type, "string");
+        }
+        assert!(!schema
+            .required
+            .contains(&"custom_color_overrides".to_string()));
+        assert_eq!(
+            schema.properties["custom_color_overrides"].required.len(),
+            4
+        );
+    }
+
+    #[test]
+    fn tags_schema_roundtrip() {
+        let schema = tags_schema();
+        let parsed: JsonSchema = json5::from_str(&schema.to_json().unwrap()).unwrap();
+
+        assert_eq!(parsed, schema);
+        assert_eq!(parsed.required, vec!["tags", "sets"]);
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use anyhow::bail;
+use log::{debug, info};
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::file_meta_data::FileMetaData;
+use crate::asset_pack::pack_layout::PackLayout;
+use crate::asset_pack::path_utils::*;
+
+impl AssetPack {
+    /// Splits the pack into packs that are each at most `max_size` bytes when written.
+    ///
+    /// The object files are distributed over the parts largest first, each going into the first
+    /// part it still fits in. All other files stay in the first part. Every part gets the tags
+    /// of the objects it contains, and the id and name of the pack with the part number appended.
+    pub fn split_by_size(&self, max_size: u64) -> anyhow::Result<Vec<AssetPack>> {
+        let mut objects: Vec<_> = self.object_files.iter().collect();
+        objects.sort_by(|(a_path, a_data), (b_path, b_data)| {
+            b_data.len().cmp(&a_data.len()).then(a_path.cmp(b_path))
+        });
+
+        // Parts with their estimated size when written.
+        let mut parts: Vec<(AssetPack, u64)> = vec![];
+
+        for (path, data) in objects {
+            let fitting_part = parts
+                .iter_mut()
+                .find(|(part, size)| size + part.estimated_file_size(path, data.len()) <= max_size);
+
+            let (part, size) = match fitting_part {
+                Some(part) => part,
+                None => {
+                    let part = self.empty_part(parts.len() + 1);
+                    let size = part.written_size()?;
+
+                    if size + part.estimated_file_size(path, data.len()) > max_size {
+                        bail!(
+                            "File '{}' of {} bytes does not fit into a pack of at most {} bytes",
+                            path,
+                            data.len(),
+                            max_size
+                        );
+                    }
+
+                    parts.push((part, size));
+                    parts.last_mut().unwrap()
+                }
+            };
+
+            debug!("Putting '{}' into pack '{}'.", path, part.meta.id);
+            *size += part.estimated_file_size(path, data.len());
+            part.object_files.insert(path.clone(), data.clone());
+        }
+
+        if parts.is_empty() {
+            let part = self.empty_part(1);
+            let size = part.written_size()?;
+
+            if size > max_size {
+                bail!(
+                    "Pack without object files is already {} bytes, which is over the maximum of {} bytes",
+                    size,
+                    max_size
+                );
+            }
+
+            parts.push((part, size));
+        }
+
+        info!(
+            "Split '{}' into {} packs of at most {} bytes.",
+            self.meta.name,
+            parts.len(),
+            max_size
+        );
+
+        Ok(parts
+            .into_iter()
+            .map(|(mut part, _)| {
+                part.clean_tags();
+                part
+            })
+            .collect())
+    }
+
+    /// Part `number` of a split, without object files. The first part gets all other files.
+    /// It still has all tags, so its written size is an upper bound for the final part.
+    fn empty_part(&self, number: usize) -> AssetPack {
+        let mut meta = self.meta.clone();
+        meta.id = format!("{}_{}", self.meta.id, number);
+        meta.name = format!("{} ({})", self.meta.name, number);
+
+        AssetPack {
+            godot_version: self.godot_version.clone(),
+            layout: PackLayout {
+                root_json_name: None,
+                ..self.layout.clone()
+            },
+            meta,
+            tags: self.tags.clone(),
+            object_files: HashMap::new(),
+            other_files: if number == 1 {
+                self.other_files.clone()
+            } else {
+                HashMap::new()
+            },
+            stored_md5: self.stored_md5.clone(),
+            file_order: self.file_order.clone(),
+            trailer: vec![],
+            json_encodings: self.json_encodings.clone(),
+        }
+    }
+
+    /// How many bytes adding a file to this pack adds to its written size.
+    fn estimated_file_size(&self, path: &str, size: usize) -> u64 {
+        let path_with_prefix = format!(
+            "{}{}{}/{}",
+            RESOURCE_PATH_PREFIX, ASSET_PACK_PREFIX, self.meta.id, path
+        );
+
+        let mut meta = FileMetaData::new(path_with_prefix, size);
+        if self.godot_version.has_entry_flags() {
+            meta.flags = Some(0);
+        }
+
+        (meta.calculate_binary_size() + size) as u64
+    }
+
+    fn written_size(&self) -> anyhow::Result<u64> {
+        let mut data = vec![];
+        self.to_write(&mut data)?;
+        Ok(data.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn split_by_size_stays_under_maximum() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        for name in ["a", "b", "c", "d"].iter() {
+            pack.object_files
+                .insert(format!("textures/objects/{}.png", name), vec![0; 300]);
+        }
+
+        let max_size = 2000;
+        let parts = pack.split_by_size(max_size).unwrap();
+
+        assert!(parts.len() > 1);
+
+        for (index, part) in parts.iter().enumerate() {
+            let mut data = vec![];
+            part.to_write(&mut data).unwrap();
+            assert!(data.len() as u64 <= max_size);
+
+            assert_eq!(part.meta.id, format!("12345678_{}", index + 1));
+            assert_eq!(
+                index == 0,
+                part.other_files.contains_key("textures/portals/door.png")
+            );
+        }
+
+        let object_count: usize = parts.iter().map(|part| part.object_files.len()).sum();
+        assert_eq!(object_count, pack.object_files.len());
+
+        // The tag only ends up in the part that contains its object.
+        for part in parts.iter() {
+            assert_eq!(
+                part.object_files
+                    .contains_key("textures/objects/random.png"),
+                part.tags.tags.contains_key("MyTag")
+            );
+        }
+
+        assert!(pack.split_by_size(500).is_err());
+    }
+}
@@ -0,0 +1,598 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use anyhow::bail;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::asset_pack::asset_pack::{
+    parse_meta_file, parse_tags_file, take_embedded_tags, AssetPack,
+};
+use crate::asset_pack::directory::write_unpacked_file;
+use crate::asset_pack::godot_version::GodotVersion;
+use crate::asset_pack::pack_meta::PackMeta;
+use crate::asset_pack::path_utils::*;
+use crate::asset_pack::tags::Tags;
+use crate::asset_pack::utils::pretty_json;
+
+/// Everything about a pack except the contents of its files.
+#[derive(Debug)]
+pub struct PackSummary {
+    /// Where the pack was read from. Empty if it was not read from a file.
+    pub path: PathBuf,
+    pub godot_version: GodotVersion,
+    pub meta: PackMeta,
+    pub tags: Tags,
+    /// Sizes of the object files, by path.
+    pub object_files: HashMap<String, usize>,
+    /// Sizes of the other files, by path.
+    pub other_files: HashMap<String, usize>,
+}
+
+/// How many files each tag has, and how many tags each tag set has, by name.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize)]
+pub struct TagCounts {
+    pub tags: BTreeMap<String, usize>,
+    pub sets: BTreeMap<String, usize>,
+}
+
+impl TagCounts {
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(pretty_json(&json5::to_string(self)?))
+    }
+}
+
+impl Display for TagCounts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Tags:")?;
+        for (tag, count) in self.tags.iter() {
+            writeln!(f, "    {}: {} files", tag, count)?;
+        }
+
+        writeln!(f, "Tag sets:")?;
+        for (set, count) in self.sets.iter() {
+            writeln!(f, "    {}: {} tags", set, count)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A file in a pack, as listed by `PackSummary::file_entries`.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub struct FileEntry {
+    pub path: String,
+    /// Size in bytes.
+    pub size: usize,
+    /// The tags of the file, sorted. Empty for files that are not objects.
+    pub tags: Vec<String>,
+}
+
+impl PackSummary {
+    pub fn file_count(&self) -> usize {
+        self.object_files.len() + self.other_files.len()
+    }
+
+    /// Total size of the object and other files, in bytes.
+    pub fn total_size(&self) -> usize {
+        self.object_files
+            .values()
+            .chain(self.other_files.values())
+            .sum()
+    }
+
+    /// Writes the metadata and tags to `pack.json` and `data/default.dungeondraft_tags` in `dir`,
+    /// indented to make them easy to read. Returns the number of files written.
+    pub fn export_meta(&self, dir: &Path, overwrite: bool) -> anyhow::Result<usize> {
+        let files = [
+            (PACK_FILE_NAME, json5::to_string(&self.meta)?),
+            (TAGS_FILE_NAME, json5::to_string(&self.tags)?),
+        ];
+
+        let mut written = 0;
+
+        for (path, json) in files.iter() {
+            if write_unpacked_file(dir, path, pretty_json(json).as_bytes(), overwrite)? {
+                written += 1;
+            }
+        }
+
+        Ok(written)
+    }
+
+    pub fn tag_counts(&self) -> TagCounts {
+        TagCounts {
+            tags: member_counts(&self.tags.tags),
+            sets: member_counts(&self.tags.sets),
+        }
+    }
+
+    /// The object and other files, sorted by path.
+    pub fn file_entries(&self) -> Vec<FileEntry> {
+        let mut entries: Vec<FileEntry> = self
+            .object_files
+            .iter()
+            .chain(self.other_files.iter())
+            .map(|(path, size)| {
+                let mut tags: Vec<String> = self
+                    .tags
+                    .tags
+                    .iter()
+                    .filter(|(_, files)| files.contains(path))
+                    .map(|(tag, _)| tag.clone())
+                    .collect();
+                tags.sort();
+
+                FileEntry {
+                    path: path.clone(),
+                    size: *size,
+                    tags,
+                }
+            })
+            .collect();
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries
+    }
+
+    /// Like `file_entries`, but skips the first `offset` files, and returns at most `limit`,
+    /// to page through the files of a large pack.
+    pub fn file_entries_page(&self, offset: usize, limit: Option<usize>) -> Vec<FileEntry> {
+        self.file_entries()
+            .into_iter()
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+
+    /// Number of object files that are not in any tag.
+    pub fn untagged_object_count(&self) -> usize {
+        self.object_files
+            .keys()
+            .filter(|path| !self.tags.tags.values().any(|files| files.contains(*path)))
+            .count()
+    }
+}
+
+impl AssetPack {
+    /// Reads only the file table, metadata and tags of a pack,
+    /// skipping over the contents of all other files.
+    pub fn read_metadata_only<R: Read + Seek>(data: &mut R) -> anyhow::Result<PackSummary> {
+        let (godot_version, files_meta) = Self::read_file_table(data, None)?;
+
+        let mut object_files = HashMap::new();
+        let mut other_files = HashMap::new();
+        let mut maybe_meta = None;
+        let mut maybe_tags = None;
+
+        for meta in files_meta {
+            let pathbuf = &PathBuf::from(meta.path.clone());
+
+            if is_root_json_file(pathbuf) || is_tags_file(&meta.path) {
+                let pack_length = data.seek(SeekFrom::End(0))?;
+                let file_data = Self::read_file_data(data, &meta, pack_length)?;
+
+                if is_root_json_file(pathbuf) {
+                    maybe_meta = Some(parse_meta_file(file_data)?);
+                } else {
+                    maybe_tags = Some(parse_tags_file(file_data)?);
+                }
+            } else if is_objects_file(&meta.path) {
+                object_files.insert(meta.path, meta.size);
+            } else if meta.path.is_empty() {
+                debug!("Skipping directory entry of size {}.", meta.size);
+            } else if !is_pack_file(pathbuf) {
+                other_files.insert(meta.path, meta.size);
+            }
+        }
+
+        let mut meta = match maybe_meta {
+            Some(meta) => meta,
+            None => bail!("Pack does not contain a metadata file"),
+        };
+        // Some nonstandard packs store their tags in the metadata file instead.
+        let tags = match maybe_tags {
+            Some(tags) => tags,
+            None => take_embedded_tags(&mut meta).unwrap_or_default(),
+        };
+
+        Ok(PackSummary {
+            path: PathBuf::new(),
+            godot_version,
+            meta,
+            tags,
+            object_files,
+            other_files,
+        })
+    }
+}
+
+/// The file entries as one json array.
+pub fn file_entries_to_json(entries: &[FileEntry]) -> anyhow::Result<String> {
+    Ok(pretty_json(&json5::to_string(&entries)?))
+}
+
+/// The file entries as one json object per line (NDJSON), so the list can be read
+/// one file at a time instead of all at once.
+pub fn file_entries_to_json_lines(entries: &[FileEntry]) -> anyhow::Result<String> {
+    let mut lines = String::new();
+
+    for entry in entries {
+        lines += &json5::to_string(entry)?;
+        lines.push('\n');
+    }
+
+    Ok(lines)
+}
+
+/// Reads the summaries of the given packs on at most `threads` threads at the same time.
+/// `open` is called to get the data of each pack. Packs that can't be read are skipped with
+/// a warning. The result is sorted by path, so it does not depend on the thread scheduling.
+pub fn scan_packs<S, F>(paths: &[PathBuf], threads: usize, open: F) -> Vec<PackSummary>
+where
+    S: Read + Seek,
+    F: Fn(&Path) -> anyhow::Result<S> + Sync,
+{
+    let next_index = AtomicUsize::new(0);
+    let summaries = Mutex::new(vec![]);
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1).min(paths.len()) {
+            scope.spawn(|| loop {
+                let index = next_index.fetch_add(1, Ordering::Relaxed);
+                let path = match paths.get(index) {
+                    Some(path) => path,
+                    None => break,
+                };
+
+                let summary =
+                    open(path).and_then(|mut data| AssetPack::read_metadata_only(&mut data));
+
+                match summary {
+                    Ok(mut summary) => {
+                        summary.path = path.clone();
+                        summaries.lock().unwrap().push(summary);
+                    }
+                    Err(e) => warn!("Could not read packfile '{}':\n{}", path.display(), e),
+                }
+            });
+        }
+    });
+
+    let mut summaries = summaries.into_inner().unwrap();
+    summaries.sort_by(|a, b| a.path.cmp(&b.path));
+    summaries
+}
+
+/// Totals over a whole library of packs, see `library_counts`.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct LibraryCounts {
+    pub packs: usize,
+    pub objects: usize,
+    pub files: usize,
+    /// Total size of the files, in bytes.
+    pub bytes: usize,
+}
+
+impl Display for LibraryCounts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} packs, {} objects, {} files, {} bytes",
+            self.packs, self.objects, self.files, self.bytes
+        )
+    }
+}
+
+/// Adds up the number of packs, objects, files and bytes of the summaries.
+pub fn library_counts(summaries: &[PackSummary]) -> LibraryCounts {
+    summaries
+        .iter()
+        .fold(LibraryCounts::default(), |counts, summary| LibraryCounts {
+            packs: counts.packs + 1,
+            objects: counts.objects + summary.object_files.len(),
+            files: counts.files + summary.file_count(),
+            bytes: counts.bytes + summary.total_size(),
+        })
+}
+
+/// Pack ids that are used by more than one pack, with the paths of those packs.
+/// Dungeondraft can only load one pack per id.
+pub fn duplicate_pack_ids(summaries: &[PackSummary]) -> Vec<(String, Vec<PathBuf>)> {
+    let mut packs_by_id: BTreeMap<&str, Vec<PathBuf>> = BTreeMap::new();
+
+    for summary in summaries {
+        packs_by_id
+            .entry(&summary.meta.id)
+            .or_default()
+            .push(summary.path.clone());
+    }
+
+    packs_by_id
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|(id, paths)| (id.to_owned(), paths))
+        .collect()
+}
+
+/// Name, help text and value of a metric.
+type Metric = (&'static str, &'static str, fn(&PackSummary) -> usize);
+
+/// The summaries as metrics in the Prometheus text format, as read by the textfile collector.
+/// Each metric has a `pack` label with the pack id and a `name` label with the pack name.
+/// The `path` label keeps packs that share an id apart.
+pub fn prometheus_metrics(summaries: &[PackSummary]) -> String {
+    let metrics: [Metric; 3] = [
+        (
+            "dungeondraft_pack_files_total",
+            "Number of files in the pack.",
+            PackSummary::file_count,
+        ),
+        (
+            "dungeondraft_pack_bytes_total",
+            "Total size of the files in the pack, in bytes.",
+            PackSummary::total_size,
+        ),
+        (
+            "dungeondraft_pack_untagged_objects",
+            "Number of object files that are not in any tag.",
+            PackSummary::untagged_object_count,
+        ),
+    ];
+
+    let mut text = String::new();
+
+    for (name, help, value) in metrics.iter() {
+        text += &format!("# HELP {} {}\n# TYPE {} gauge\n", name, help, name);
+
+        for summary in summaries {
+            text += &format!(
+                "{}{{pack=\"{}\",name=\"{}\",path=\"{}\"}} {}\n",
+                name,
+                escape_label_value(&summary.meta.id),
+                escape_label_value(&summary.meta.name),
+                escape_label_value(&summary.path.to_string_lossy()),
+                value(summary)
+            );
+        }
+    }
+
+    text
+}
+
+fn member_counts(map: &HashMap<String, HashSet<String>>) -> BTreeMap<String, usize> {
+    map.iter()
+        .map(|(name, members)| (name.clone(), members.len()))
+        .collect()
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    use crate::asset_pack::asset_pack::{parse_meta_file, parse_tags_file, AssetPack};
+    use crate::asset_pack::summary::{
+        duplicate_pack_ids, file_entries_to_json, file_entries_to_json_lines, library_counts,
+        prometheus_metrics, scan_packs, FileEntry,
+    };
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+    use crate::asset_pack::write_options::WriteOptions;
+
+    #[test]
+    fn scan_packs_concurrently() {
+        let raw_pack = create_raw_test_pack().unwrap();
+
+        let mut other_pack = AssetPack::from_read(&mut Cursor::new(raw_pack.clone())).unwrap();
+        other_pack.meta.id = "ABCDEFGH".to_string();
+        other_pack.object_files.clear();
+        let mut raw_other_pack = vec![];
+        other_pack.to_write(&mut raw_other_pack).unwrap();
+
+        let mut packs = HashMap::new();
+        packs.insert(PathBuf::from("d/copy.dungeondraft_pack"), raw_pack.clone());
+        packs.insert(PathBuf::from("a/original.dungeondraft_pack"), raw_pack);
+        packs.insert(PathBuf::from("c/other.dungeondraft_pack"), raw_other_pack);
+        packs.insert(PathBuf::from("b/broken.dungeondraft_pack"), vec![1, 2, 3]);
+
+        let paths: Vec<PathBuf> = packs.keys().cloned().collect();
+
+        let summaries = scan_packs(&paths, 3, |path| Ok(Cursor::new(packs[path].clone())));
+
+        let scanned_paths: Vec<&str> = summaries.iter().map(|s| s.path.to_str().unwrap()).collect();
+        assert_eq!(
+            scanned_paths,
+            vec![
+                "a/original.dungeondraft_pack",
+                "c/other.dungeondraft_pack",
+                "d/copy.dungeondraft_pack"
+            ]
+        );
+        assert_eq!(summaries[0].object_files.len(), 1);
+        assert_eq!(summaries[0].file_count(), 2);
+        assert_eq!(summaries[1].object_files.len(), 0);
+
+        let duplicates = duplicate_pack_ids(&summaries);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].0, "12345678");
+        assert_eq!(duplicates[0].1.len(), 2);
+    }
+
+    #[test]
+    fn library_counts_add_up() {
+        let raw_pack = create_raw_test_pack().unwrap();
+
+        let mut other_pack = AssetPack::from_read(&mut Cursor::new(raw_pack.clone())).unwrap();
+        for i in 0..3 {
+            other_pack
+                .object_files
+                .insert(format!("textures/objects/{}.png", i), vec![i; 4]);
+        }
+        let mut raw_other_pack = vec![];
+        other_pack.to_write(&mut raw_other_pack).unwrap();
+
+        let summaries: Vec<_> = [raw_pack, raw_other_pack]
+            .iter()
+            .map(|raw| AssetPack::read_metadata_only(&mut Cursor::new(raw)).unwrap())
+            .collect();
+
+        let counts = library_counts(&summaries);
+
+        assert_eq!(counts.packs, 2);
+        assert_eq!(
+            counts.objects,
+            summaries[0].object_files.len() + summaries[1].object_files.len()
+        );
+        assert_eq!(counts.objects, 1 + 4);
+        assert_eq!(counts.files, 2 + 5);
+        assert_eq!(counts.bytes, 20 + 32);
+        assert_eq!(counts.to_string(), "2 packs, 5 objects, 7 files, 52 bytes");
+    }
+
+    #[test]
+    fn prometheus_metrics_of_example_pack() {
+        let raw_pack = create_raw_test_pack().unwrap();
+        let mut summary = AssetPack::read_metadata_only(&mut Cursor::new(raw_pack)).unwrap();
+        summary.path = PathBuf::from("example.dungeondraft_pack");
+
+        let metrics = prometheus_metrics(&[summary]);
+
+        assert!(metrics.lines().any(|line| line
+            == r#"dungeondraft_pack_files_total{pack="12345678",name="example_pack",path="example.dungeondraft_pack"} 2"#));
+        assert!(metrics.contains("# TYPE dungeondraft_pack_bytes_total gauge"));
+        assert!(metrics.contains(
+            r#"dungeondraft_pack_untagged_objects{pack="12345678",name="example_pack",path="example.dungeondraft_pack"} 0"#
+        ));
+    }
+
+    #[test]
+    fn summary_skips_directory_entries() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.other_files.insert(String::new(), vec![]);
+        let mut raw_pack = vec![];
+        pack.to_write(&mut raw_pack).unwrap();
+
+        let summary = AssetPack::read_metadata_only(&mut Cursor::new(raw_pack)).unwrap();
+
+        assert_eq!(
+            summary.other_files.keys().collect::<Vec<_>>(),
+            vec!["textures/portals/door.png"]
+        );
+    }
+
+    #[test]
+    fn summary_of_pack_with_embedded_tags() {
+        let pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        let options = WriteOptions {
+            embed_tags: true,
+            ..Default::default()
+        };
+        let mut raw_pack = vec![];
+        pack.to_write_with_options(&mut raw_pack, &options).unwrap();
+
+        let summary = AssetPack::read_metadata_only(&mut Cursor::new(raw_pack)).unwrap();
+
+        assert_eq!(summary.tags, pack.tags);
+        assert_eq!(summary.meta, pack.meta);
+        assert_eq!(summary.tag_counts().tags["MyTag"], 1);
+    }
+
+    #[test]
+    fn tag_counts_of_example_pack() {
+        let raw_pack = create_raw_test_pack().unwrap();
+        let summary = AssetPack::read_metadata_only(&mut Cursor::new(raw_pack)).unwrap();
+
+        let counts = summary.tag_counts();
+
+        assert_eq!(
+            counts.to_string(),
+            "Tags:\n    Colorable: 1 files\n    MyTag: 1 files\nTag sets:\n    Example Set: 1 tags\n"
+        );
+        assert_eq!(
+            counts.to_json().unwrap(),
+            "{\n\t\"tags\": {\n\t\t\"Colorable\": 1,\n\t\t\"MyTag\": 1\n\t},\n\t\"sets\": {\n\t\t\"Example Set\": 1\n\t}\n}"
+        );
+    }
+
+    #[test]
+    fn export_meta_of_example_pack() {
+        let raw_pack = create_raw_test_pack().unwrap();
+        let summary = AssetPack::read_metadata_only(&mut Cursor::new(raw_pack)).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(summary.export_meta(dir.path(), false).unwrap(), 2);
+
+        let meta = parse_meta_file(fs::read(dir.path().join("pack.json")).unwrap()).unwrap();
+        let tags =
+            parse_tags_file(fs::read(dir.path().join("data/default.dungeondraft_tags")).unwrap())
+                .unwrap();
+
+        assert_eq!(meta, summary.meta);
+        assert_eq!(tags, summary.tags);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn files_as_json_lines() {
+        let raw_pack = create_raw_test_pack().unwrap();
+        let summary = AssetPack::read_metadata_only(&mut Cursor::new(raw_pack)).unwrap();
+
+        let json_lines = file_entries_to_json_lines(&summary.file_entries()).unwrap();
+        let entries: Vec<FileEntry> = json_lines
+            .lines()
+            .map(|line| json5::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(entries.len(), summary.file_count());
+        assert_eq!(entries, summary.file_entries());
+        assert_eq!(entries[0].path, "textures/objects/random.png");
+        assert_eq!(entries[0].tags, vec!["MyTag"]);
+        assert!(entries[1].tags.is_empty());
+
+        let array: Vec<FileEntry> =
+            json5::from_str(&file_entries_to_json(&summary.file_entries()).unwrap()).unwrap();
+        assert_eq!(array, entries);
+    }
+
+    #[test]
+    fn page_through_files() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        for name in ["d", "b", "a", "c"].iter() {
+            pack.object_files
+                .insert(format!("textures/objects/{}.png", name), vec![1]);
+        }
+        let mut raw_pack = vec![];
+        pack.to_write(&mut raw_pack).unwrap();
+        let summary = AssetPack::read_metadata_only(&mut Cursor::new(raw_pack)).unwrap();
+
+        let page: Vec<String> = summary
+            .file_entries_page(1, Some(2))
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+
+        assert_eq!(
+            page,
+            vec!["textures/objects/b.png", "textures/objects/c.png"]
+        );
+        assert_eq!(summary.file_entries_page(0, None), summary.file_entries());
+        assert_eq!(summary.file_entries_page(5, Some(2)).len(), 1);
+        assert!(summary.file_entries_page(10, None).is_empty());
+    }
+}
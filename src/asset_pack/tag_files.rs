@@ -0,0 +1,163 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use log::{info, warn};
+
+use crate::asset_pack::asset_pack::{parse_tags_file, AssetPack};
+use crate::asset_pack::tags::Tags;
+
+/// What to do with the tags a pack already has when importing a tags file.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TagImport {
+    /// Throw the existing tags and sets away.
+    Replace,
+    /// Add the imported files and tags to the existing tags and sets.
+    Merge,
+}
+
+impl AssetPack {
+    /// Writes the tags and tag sets to a standalone tags file, in the same format as the
+    /// `default.dungeondraft_tags` file inside the pack.
+    pub fn export_tags(&self, path: &Path) -> anyhow::Result<()> {
+        info!(
+            "Exporting tags of '{}' to '{}'",
+            self.meta.name,
+            path.display()
+        );
+
+        fs::write(path, json5::to_string(&self.tags)?)
+            .context(format!("Could not write tags file '{}'", path.display()))
+    }
+
+    /// Reads a tags file as written by `export_tags`, and replaces or merges it into the tags
+    /// of this pack. References to object files the pack does not have are left out.
+    /// Returns the number of references that were left out.
+    pub fn import_tags(&mut self, path: &Path, mode: TagImport) -> anyhow::Result<usize> {
+        info!(
+            "Importing tags from '{}' into '{}'",
+            path.display(),
+            self.meta.name
+        );
+
+        let imported = parse_tags_file(
+            fs::read(path).context(format!("Could not read tags file '{}'", path.display()))?,
+        )?;
+
+        if mode == TagImport::Replace {
+            self.tags = Tags::new();
+        }
+
+        let mut left_out = 0;
+
+        for (tag, files) in imported.tags {
+            let tag_files = self.tags.tags.entry(tag.clone()).or_default();
+
+            for file in files {
+                if self.object_files.contains_key(&file) {
+                    tag_files.insert(file);
+                } else {
+                    warn!(
+                        "Not adding '{}' to tag '{}', the pack does not contain it.",
+                        file, tag
+                    );
+                    left_out += 1;
+                }
+            }
+        }
+
+        for (set, tags) in imported.sets {
+            self.tags.sets.entry(set).or_default().extend(tags);
+        }
+
+        Ok(left_out)
+    }
+
+    /// Replaces all tags and tag sets with the ones in `json`, in the same format as the
+    /// tags file inside the pack. Unlike `import_tags`, nothing is left out: if any tag
+    /// refers to an object file the pack does not have, the tags are not changed and the
+    /// error lists the invalid references.
+    pub fn set_tags_from_json(&mut self, json: &str) -> anyhow::Result<()> {
+        let tags = parse_tags_file(json.as_bytes().to_vec())?;
+
+        let mut invalid: Vec<String> = tags
+            .tags
+            .iter()
+            .flat_map(|(tag, files)| {
+                files
+                    .iter()
+                    .filter(|file| !self.object_files.contains_key(*file))
+                    .map(move |file| format!("'{}' in tag '{}'", file, tag))
+            })
+            .collect();
+
+        if !invalid.is_empty() {
+            invalid.sort();
+            bail!(
+                "Tags refer to files the pack does not contain:\n{}",
+                invalid.join("\n")
+            );
+        }
+
+        self.tags = tags;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::tag_files::TagImport;
+    use crate::asset_pack::tags::Tags;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn export_and_import_tags() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.object_files
+            .insert("textures/objects/sample_cauldron.png".to_string(), vec![1]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let tags_path = dir.path().join("exported.dungeondraft_tags");
+        pack.export_tags(&tags_path).unwrap();
+
+        let exported_tags = std::mem::replace(&mut pack.tags, Tags::new());
+
+        assert_eq!(pack.import_tags(&tags_path, TagImport::Replace).unwrap(), 0);
+        assert_eq!(pack.tags, exported_tags);
+
+        // Without the file, its reference is left out.
+        pack.object_files
+            .remove("textures/objects/sample_cauldron.png");
+        assert_eq!(pack.import_tags(&tags_path, TagImport::Replace).unwrap(), 1);
+        assert!(pack.tags.tags["Colorable"].is_empty());
+        assert_eq!(pack.tags.tags["MyTag"], exported_tags.tags["MyTag"]);
+    }
+
+    #[test]
+    fn set_tags_from_json_validates_references() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+
+        pack.set_tags_from_json(
+            r#"{"tags": {"Random": ["textures/objects/random.png"]}, "sets": {"All": ["Random"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(pack.tags.tags.len(), 1);
+        assert!(pack.tags.tags["Random"].contains("textures/objects/random.png"));
+        assert!(pack.tags.sets["All"].contains("Random"));
+
+        let error = pack
+            .set_tags_from_json(r#"{"tags": {"Gone": ["textures/objects/gone.png"]}, "sets": {}}"#)
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("'textures/objects/gone.png' in tag 'Gone'"));
+        // The tags are left alone.
+        assert!(pack.tags.tags.contains_key("Random"));
+        assert!(!pack.tags.tags.contains_key("Gone"));
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+
+use crate::asset_pack::summary::PackSummary;
+
+/// Which files are in which tag, over multiple packs.
+#[derive(Debug, Default)]
+pub struct TagIndex {
+    /// `(pack id, file path)` entries by tag name, sorted.
+    entries: BTreeMap<String, Vec<(String, String)>>,
+}
+
+impl TagIndex {
+    pub fn from_summaries(summaries: &[PackSummary]) -> Self {
+        let mut entries: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+
+        for summary in summaries {
+            for (tag, files) in summary.tags.tags.iter() {
+                let tag_entries = entries.entry(tag.clone()).or_default();
+
+                for file in files {
+                    tag_entries.push((summary.meta.id.clone(), file.clone()));
+                }
+            }
+        }
+
+        for tag_entries in entries.values_mut() {
+            tag_entries.sort();
+        }
+
+        TagIndex { entries }
+    }
+
+    /// The `(pack id, file path)` entries of all files in the given tag.
+    /// Empty if no pack has the tag.
+    pub fn files_in_tag(&self, tag: &str) -> &[(String, String)] {
+        self.entries.get(tag).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::tag_index::TagIndex;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn index_tag_shared_by_two_packs() {
+        let raw_pack = create_raw_test_pack().unwrap();
+
+        let mut other_pack = AssetPack::from_read(&mut Cursor::new(raw_pack.clone())).unwrap();
+        other_pack.meta.id = "ABCDEFGH".to_string();
+        other_pack
+            .move_file("textures/objects/random.png", "textures/objects/barrel.png")
+            .unwrap();
+        let mut raw_other_pack = vec![];
+        other_pack.to_write(&mut raw_other_pack).unwrap();
+
+        let summaries = vec![
+            AssetPack::read_metadata_only(&mut Cursor::new(raw_pack)).unwrap(),
+            AssetPack::read_metadata_only(&mut Cursor::new(raw_other_pack)).unwrap(),
+        ];
+
+        let index = TagIndex::from_summaries(&summaries);
+
+        assert_eq!(
+            index.files_in_tag("MyTag"),
+            &[
+                (
+                    "12345678".to_string(),
+                    "textures/objects/random.png".to_string()
+                ),
+                (
+                    "ABCDEFGH".to_string(),
+                    "textures/objects/barrel.png".to_string()
+                ),
+            ]
+        );
+        assert!(index.files_in_tag("NoSuchTag").is_empty());
+    }
+}
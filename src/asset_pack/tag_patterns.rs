@@ -0,0 +1,158 @@
+use glob::{MatchOptions, Pattern};
+use log::{debug, warn};
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::path_utils::object_folder;
+
+/// `*` and `?` don't match `/`, so a pattern only reaches into subdirectories with `**`.
+const PATH_MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+impl AssetPack {
+    /// Adds the tag to every object file whose path matches the glob,
+    /// for example `textures/objects/furniture/**`. The tag is created if it does not exist yet.
+    /// Returns the number of files that were tagged. An invalid glob tags nothing.
+    pub fn tag_by_pattern(&mut self, glob: &str, tag: &str) -> usize {
+        let pattern = match Pattern::new(glob) {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                warn!(
+                    "Not tagging with '{}', invalid pattern '{}': {}",
+                    tag, glob, e
+                );
+                return 0;
+            }
+        };
+
+        let matches: Vec<&String> = self
+            .object_files
+            .keys()
+            .filter(|path| pattern.matches_with(path, PATH_MATCH_OPTIONS))
+            .collect();
+
+        if matches.is_empty() {
+            debug!("No object files match '{}'.", glob);
+            return 0;
+        }
+
+        let tag_files = self.tags.tags.entry(tag.to_owned()).or_default();
+        for path in matches.iter() {
+            tag_files.insert((*path).clone());
+        }
+
+        debug!(
+            "Tagged {} files matching '{}' as '{}'.",
+            matches.len(),
+            glob,
+            tag
+        );
+
+        matches.len()
+    }
+
+    /// Tags every object file in a directory under `textures/objects/` with the name of that
+    /// directory, title-cased: `textures/objects/dungeon_rocks/**` gets the tag `Dungeon Rocks`.
+    /// Meant to give packs without tags a starting point.
+    /// Returns the number of files that were tagged.
+    pub fn tag_from_folders(&mut self) -> usize {
+        let mut tagged = 0;
+
+        for path in self.object_files.keys() {
+            let tag = match object_folder(path) {
+                Some(folder) => folder_tag_name(folder),
+                None => continue,
+            };
+
+            if self.tags.tags.entry(tag).or_default().insert(path.clone()) {
+                tagged += 1;
+            }
+        }
+
+        debug!("Tagged {} object files after their directory.", tagged);
+
+        tagged
+    }
+}
+
+/// `dungeon_rocks` or `dungeon-rocks` becomes `Dungeon Rocks`.
+fn folder_tag_name(folder: &str) -> String {
+    folder
+        .split(|c: char| c == '_' || c == '-' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn tag_by_pattern_tags_matching_object_files() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        for path in [
+            "textures/objects/barrel.png",
+            "textures/objects/old_barrel_2.png",
+            "textures/objects/crate.png",
+            "textures/objects/nested/barrel.png",
+        ] {
+            pack.object_files.insert(path.to_string(), vec![1]);
+        }
+
+        assert_eq!(
+            pack.tag_by_pattern("textures/objects/*barrel*.png", "Barrels"),
+            2
+        );
+
+        let barrels = pack.get_files_in_tag("Barrels").unwrap();
+        assert_eq!(barrels.len(), 2);
+        assert!(barrels.contains("textures/objects/barrel.png"));
+        assert!(barrels.contains("textures/objects/old_barrel_2.png"));
+
+        // Nothing matches, so no tag is created.
+        assert_eq!(pack.tag_by_pattern("textures/objects/*.jpg", "Jpegs"), 0);
+        assert!(pack.get_files_in_tag("Jpegs").is_none());
+    }
+
+    #[test]
+    fn tag_from_folders_creates_folder_tags() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        for path in [
+            "textures/objects/furniture/chair.png",
+            "textures/objects/furniture/old/table.png",
+            "textures/objects/dungeon_rocks/rock.png",
+        ] {
+            pack.object_files.insert(path.to_string(), vec![1]);
+        }
+
+        assert_eq!(pack.tag_from_folders(), 3);
+
+        let furniture = pack.get_files_in_tag("Furniture").unwrap();
+        assert_eq!(furniture.len(), 2);
+        assert!(furniture.contains("textures/objects/furniture/chair.png"));
+        assert!(furniture.contains("textures/objects/furniture/old/table.png"));
+        assert!(pack
+            .get_files_in_tag("Dungeon Rocks")
+            .unwrap()
+            .contains("textures/objects/dungeon_rocks/rock.png"));
+        // Directly in `textures/objects/`, so not tagged.
+        assert_eq!(pack.tags.tags.len(), 4);
+
+        assert_eq!(pack.tag_from_folders(), 0);
+    }
+}
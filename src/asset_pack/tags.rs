@@ -1,13 +1,21 @@
+use anyhow::bail;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 
-#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
 pub struct Tags {
     pub tags: HashMap<String, HashSet<String>>,
     pub sets: HashMap<String, HashSet<String>>,
 }
 
+/// `Tags` with everything sorted by name, so it always serializes the same way.
+#[derive(Serialize)]
+struct SortedTags<'a> {
+    tags: BTreeMap<&'a String, BTreeSet<&'a String>>,
+    sets: BTreeMap<&'a String, BTreeSet<&'a String>>,
+}
+
 impl Tags {
     pub fn new() -> Self {
         Tags {
@@ -15,6 +23,48 @@ impl Tags {
             sets: HashMap::new(),
         }
     }
+
+    /// Removes all tags and tag sets.
+    pub fn clear(&mut self) {
+        self.tags.clear();
+        self.sets.clear();
+    }
+
+    /// Renames the tag set, keeping its tags. Returns `false` if there is no set named `old`.
+    /// Fails if there already is a set named `new`.
+    pub fn rename_set(&mut self, old: &str, new: &str) -> anyhow::Result<bool> {
+        if !self.sets.contains_key(old) {
+            return Ok(false);
+        }
+        if old == new {
+            return Ok(true);
+        }
+        if self.sets.contains_key(new) {
+            bail!(
+                "Can not rename tag set '{}' to '{}', that set already exists",
+                old,
+                new
+            );
+        }
+
+        let tags = self.sets.remove(old).unwrap_or_default();
+        self.sets.insert(new.to_owned(), tags);
+        Ok(true)
+    }
+
+    /// Serializes the tags with the tags, sets and their contents sorted by name.
+    pub fn to_sorted_json(&self) -> anyhow::Result<String> {
+        Ok(json5::to_string(&SortedTags {
+            tags: sorted(&self.tags),
+            sets: sorted(&self.sets),
+        })?)
+    }
+}
+
+fn sorted(map: &HashMap<String, HashSet<String>>) -> BTreeMap<&String, BTreeSet<&String>> {
+    map.iter()
+        .map(|(name, values)| (name, values.iter().collect()))
+        .collect()
 }
 
 impl Display for Tags {
@@ -52,3 +102,29 @@ impl Display for Tags {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+    use std::iter::FromIterator;
+
+    use crate::asset_pack::tags::Tags;
+
+    #[test]
+    fn rename_set() {
+        let mut tags = Tags::new();
+        tags.sets.insert(
+            "Nature".to_string(),
+            HashSet::from_iter(vec!["trees".to_string()]),
+        );
+        tags.sets.insert("Dungeon".to_string(), HashSet::new());
+
+        assert!(tags.rename_set("Nature", "Outdoors").unwrap());
+        assert!(!tags.sets.contains_key("Nature"));
+        assert!(tags.sets["Outdoors"].contains("trees"));
+
+        assert!(!tags.rename_set("Nature", "Forest").unwrap());
+        assert!(tags.rename_set("Outdoors", "Dungeon").is_err());
+        assert!(tags.sets["Outdoors"].contains("trees"));
+    }
+}
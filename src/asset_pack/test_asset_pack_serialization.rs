@@ -1,12 +1,18 @@
 #![cfg(test)]
 
-use std::io::{Cursor, Write};
+use std::io::{BufReader, BufWriter, Cursor, Write};
 
 use anyhow::Result;
-use byteorder::{WriteBytesExt, LE};
+use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt, BE, LE};
 
 use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::classification::ClassificationRules;
+use crate::asset_pack::file_meta_data::FileMetaData;
 use crate::asset_pack::godot_version::GodotVersion;
+use crate::asset_pack::pack_layout::PackLayout;
+use crate::asset_pack::read_options::ReadOptions;
+use crate::asset_pack::utils::{md5_hash, MD5_BYTES, PACK_DIR_ENCRYPTED};
+use crate::asset_pack::write_options::WriteOptions;
 
 #[test]
 fn asset_pack_from_read_happy_flow() {
@@ -81,7 +87,921 @@ fn asset_pack_read_write_read_equivalence_check() {
     assert_eq!(pack.tags, re_read_pack.tags);
 }
 
-fn create_raw_test_pack() -> Result<Vec<u8>> {
+#[test]
+fn asset_pack_read_write_through_small_buffers() {
+    // Buffers smaller than most fields, so reads and writes keep crossing buffer boundaries.
+    let mut reader = BufReader::with_capacity(7, Cursor::new(create_raw_test_pack().unwrap()));
+    let pack = AssetPack::from_read(&mut reader).unwrap();
+
+    let mut writer = BufWriter::with_capacity(7, vec![]);
+    pack.to_write(&mut writer).unwrap();
+    let written_pack = writer.into_inner().unwrap();
+
+    let mut unbuffered_pack = vec![];
+    pack.to_write(&mut unbuffered_pack).unwrap();
+    assert_eq!(written_pack, unbuffered_pack);
+
+    let mut reader = BufReader::with_capacity(7, Cursor::new(written_pack));
+    let re_read_pack = AssetPack::from_read(&mut reader).unwrap();
+
+    assert_eq!(pack.meta, re_read_pack.meta);
+    assert_eq!(pack.tags, re_read_pack.tags);
+    assert_eq!(pack.object_files, re_read_pack.object_files);
+    assert_eq!(pack.other_files, re_read_pack.other_files);
+}
+
+#[test]
+fn asset_pack_preserves_metadata_file_layout() {
+    let mut pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+    assert_eq!(pack.layout, PackLayout::default());
+
+    pack.layout = PackLayout {
+        root_json_name: Some("example.json".to_string()),
+        pack_json_path: "meta/pack.json".to_string(),
+        tags_file_path: "custom/data/default.dungeondraft_tags".to_string(),
+        ..Default::default()
+    };
+
+    let mut written_pack = vec![];
+    pack.to_write(&mut written_pack).unwrap();
+    let re_read_pack = AssetPack::from_read(&mut Cursor::new(written_pack)).unwrap();
+
+    assert_eq!(re_read_pack.layout, pack.layout);
+    assert_eq!(re_read_pack.tags, pack.tags);
+
+    let mut re_written_pack = vec![];
+    re_read_pack.to_write(&mut re_written_pack).unwrap();
+
+    assert_eq!(
+        read_raw_file_paths(re_written_pack)[..3],
+        [
+            "res://packs/example.json",
+            "res://packs/12345678/meta/pack.json",
+            "res://packs/12345678/custom/data/default.dungeondraft_tags"
+        ]
+    );
+}
+
+#[test]
+fn asset_pack_write_sorted_by_path() {
+    let mut pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+    pack.other_files
+        .insert("textures/walls/stone.png".to_string(), vec![1]);
+    pack.other_files
+        .insert("data/walls/stone.dungeondraft_wall".to_string(), vec![2]);
+    pack.object_files
+        .insert("textures/objects/barrel.png".to_string(), vec![3]);
+
+    let options = WriteOptions {
+        sort_files_by_path: true,
+        ..Default::default()
+    };
+
+    let mut written_pack = vec![];
+    pack.to_write_with_options(&mut written_pack, &options)
+        .unwrap();
+
+    let paths = read_raw_file_paths(written_pack);
+
+    // The metadata and tags files always come first.
+    let pack_file_paths = &paths[3..];
+    let mut sorted_paths = pack_file_paths.to_vec();
+    sorted_paths.sort();
+
+    assert_eq!(pack_file_paths.len(), 5);
+    assert_eq!(pack_file_paths, sorted_paths.as_slice());
+}
+
+#[test]
+fn asset_pack_write_preserving_file_order() {
+    let mut pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+    for i in 0..10 {
+        pack.object_files
+            .insert(format!("textures/objects/{}.png", i), vec![i]);
+    }
+    let mut source_pack = vec![];
+    pack.to_write(&mut source_pack).unwrap();
+    let source_paths = read_raw_file_paths(source_pack.clone());
+
+    let mut pack = AssetPack::from_read(&mut Cursor::new(source_pack)).unwrap();
+    pack.clean_tags();
+    pack.object_files
+        .insert("textures/objects/new.png".to_string(), vec![]);
+
+    let options = WriteOptions {
+        preserve_file_order: true,
+        ..Default::default()
+    };
+    let mut written_pack = vec![];
+    pack.to_write_with_options(&mut written_pack, &options)
+        .unwrap();
+    let paths = read_raw_file_paths(written_pack);
+
+    assert_eq!(paths[..source_paths.len()], source_paths[..]);
+    assert_eq!(
+        paths[source_paths.len()..],
+        ["res://packs/12345678/textures/objects/new.png".to_string()]
+    );
+}
+
+#[test]
+fn asset_pack_normalized_write_is_stable() {
+    let files = [
+        "textures/objects/barrel.png",
+        "textures/objects/crate.png",
+        "textures/walls/stone.png",
+        "data/walls/stone.dungeondraft_wall",
+    ];
+
+    let edited_pack = |order: &mut dyn Iterator<Item = &&str>| {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        for path in order {
+            pack.other_files.insert(path.to_string(), vec![1]);
+            pack.tags
+                .tags
+                .entry("Added".to_string())
+                .or_default()
+                .insert(path.to_string());
+        }
+        pack.move_file("textures/walls/stone.png", "textures/walls/granite.png")
+            .unwrap();
+        pack.normalize();
+        pack
+    };
+
+    let pack = edited_pack(&mut files.iter());
+    let reversed_pack = edited_pack(&mut files.iter().rev());
+
+    let mut first_write = vec![];
+    pack.to_write(&mut first_write).unwrap();
+    let mut second_write = vec![];
+    pack.to_write(&mut second_write).unwrap();
+    let mut reversed_write = vec![];
+    reversed_pack.to_write(&mut reversed_write).unwrap();
+
+    assert_eq!(first_write, second_write);
+    assert_eq!(first_write, reversed_write);
+
+    let paths = read_raw_file_paths(first_write);
+    let pack_file_paths = &paths[3..];
+    let mut sorted_paths = pack_file_paths.to_vec();
+    sorted_paths.sort();
+    assert_eq!(pack_file_paths, sorted_paths.as_slice());
+}
+
+#[test]
+fn asset_pack_write_pretty_and_compact_json() {
+    let pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+
+    let write = |pretty_json| {
+        let options = WriteOptions {
+            pretty_json,
+            ..Default::default()
+        };
+        let mut written_pack = vec![];
+        pack.to_write_with_options(&mut written_pack, &options)
+            .unwrap();
+        written_pack
+    };
+
+    let compact_pack = write(false);
+    let compact_text = String::from_utf8_lossy(&compact_pack);
+    assert!(compact_text.contains(r#"{"name":"example_pack","id":"12345678","#));
+    assert!(!compact_text.contains("\n\t"));
+
+    let pretty_pack = write(true);
+    let pretty_text = String::from_utf8_lossy(&pretty_pack);
+    assert!(pretty_text.contains("{\n\t\"name\": \"example_pack\",\n\t\"id\": \"12345678\","));
+    assert!(pretty_text.contains("\t\"custom_color_overrides\": {\n\t\t\"enabled\": false,"));
+
+    let re_read_pack = AssetPack::from_read(&mut Cursor::new(pretty_pack)).unwrap();
+    assert_eq!(re_read_pack.meta, pack.meta);
+    assert_eq!(re_read_pack.tags, pack.tags);
+}
+
+#[test]
+fn asset_pack_write_sorted_tag_members() {
+    let mut pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+    let files = [
+        "textures/objects/c.png",
+        "textures/objects/a.png",
+        "textures/objects/b.png",
+    ];
+    pack.tags
+        .tags
+        .get_mut("MyTag")
+        .unwrap()
+        .extend(files.iter().map(|file| file.to_string()));
+
+    let options = WriteOptions {
+        sort_tags: true,
+        ..Default::default()
+    };
+    let mut written_pack = vec![];
+    pack.to_write_with_options(&mut written_pack, &options)
+        .unwrap();
+
+    let written_text = String::from_utf8_lossy(&written_pack);
+    let tag_start = written_text.find(r#""MyTag":["#).unwrap();
+    let tag_end = tag_start + written_text[tag_start..].find(']').unwrap();
+    let members: Vec<&str> = written_text[tag_start + r#""MyTag":["#.len()..tag_end]
+        .split(',')
+        .map(|member| member.trim_matches('"'))
+        .collect();
+
+    let mut sorted_members = members.clone();
+    sorted_members.sort_unstable();
+    assert_eq!(members.len(), pack.tags.tags["MyTag"].len());
+    assert_eq!(members, sorted_members);
+
+    let re_read_pack = AssetPack::from_read(&mut Cursor::new(written_pack)).unwrap();
+    assert_eq!(re_read_pack.tags, pack.tags);
+}
+
+#[test]
+fn asset_pack_read_write_embedded_tags() {
+    let pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+
+    let options = WriteOptions {
+        embed_tags: true,
+        ..Default::default()
+    };
+    let mut written_pack = vec![];
+    pack.to_write_with_options(&mut written_pack, &options)
+        .unwrap();
+
+    assert!(!read_raw_file_paths(written_pack.clone())
+        .iter()
+        .any(|path| path.ends_with("default.dungeondraft_tags")));
+    let pack_json =
+        AssetPack::read_single_file(&mut Cursor::new(written_pack.clone()), "pack.json")
+            .unwrap()
+            .unwrap();
+    assert!(String::from_utf8(pack_json)
+        .unwrap()
+        .contains(r#""tags":{"sets":{"Example Set":["MyTag"]},"tags":{"#));
+
+    let re_read_pack = AssetPack::from_read(&mut Cursor::new(written_pack)).unwrap();
+    assert_eq!(re_read_pack.tags, pack.tags);
+    assert!(re_read_pack.meta.extra.is_empty());
+    assert_eq!(re_read_pack.meta, pack.meta);
+}
+
+#[test]
+fn asset_pack_write_md5_hashes() {
+    let mut pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+    for i in 0..20 {
+        pack.object_files
+            .insert(format!("textures/objects/{}.png", i), vec![i; 100]);
+    }
+
+    let options = WriteOptions {
+        md5_threads: Some(4),
+        ..Default::default()
+    };
+    let mut written_pack = vec![];
+    pack.to_write_with_options(&mut written_pack, &options)
+        .unwrap();
+
+    let re_read_pack = AssetPack::from_read(&mut Cursor::new(written_pack)).unwrap();
+    for (path, data) in re_read_pack
+        .object_files
+        .iter()
+        .chain(re_read_pack.other_files.iter())
+    {
+        assert_eq!(re_read_pack.stored_md5[path], md5_hash(data));
+    }
+    assert!(re_read_pack.verify_checksums().is_empty());
+}
+
+#[test]
+fn asset_pack_write_aligned_files() {
+    let mut pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+    pack.object_files
+        .insert("textures/objects/odd.png".to_string(), vec![7; 13]);
+
+    let options = WriteOptions {
+        alignment: Some(16),
+        ..Default::default()
+    };
+    let mut written_pack = vec![];
+    pack.to_write_with_options(&mut written_pack, &options)
+        .unwrap();
+
+    let (_, files_meta) =
+        AssetPack::read_file_table(&mut Cursor::new(written_pack.clone()), None).unwrap();
+    assert_eq!(files_meta.len(), 6);
+    for meta in files_meta.iter() {
+        assert_eq!(meta.offset % 16, 0, "{} is not aligned", meta.path);
+    }
+
+    let re_read_pack = AssetPack::from_read(&mut Cursor::new(written_pack)).unwrap();
+    assert_eq!(re_read_pack.object_files, pack.object_files);
+    assert_eq!(re_read_pack.other_files, pack.other_files);
+    assert_eq!(re_read_pack.meta, pack.meta);
+}
+
+#[test]
+fn asset_pack_write_shared_metadata_offset() {
+    let pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+
+    let mut separate_pack = vec![];
+    pack.to_write(&mut separate_pack).unwrap();
+
+    let options = WriteOptions {
+        share_metadata_offset: true,
+        ..Default::default()
+    };
+    let mut shared_pack = vec![];
+    pack.to_write_with_options(&mut shared_pack, &options)
+        .unwrap();
+
+    let (_, files_meta) =
+        AssetPack::read_file_table(&mut Cursor::new(shared_pack.clone()), None).unwrap();
+    let root_json = files_meta
+        .iter()
+        .find(|meta| meta.path == "12345678.json")
+        .unwrap();
+    let pack_json = files_meta
+        .iter()
+        .find(|meta| meta.path == "pack.json")
+        .unwrap();
+    assert_eq!(pack_json.offset, root_json.offset);
+    assert_eq!(pack_json.size, root_json.size);
+    // The two fake images of the test pack have the same contents as well.
+    assert_eq!(
+        shared_pack.len(),
+        separate_pack.len() - root_json.size - TEST_PACK_FAKE_PNG.len()
+    );
+    let report = AssetPack::layout_report(&mut Cursor::new(shared_pack.clone())).unwrap();
+    assert_eq!(report.padding_bytes, 0);
+
+    let options = ReadOptions {
+        check_meta_consistency: true,
+        ..Default::default()
+    };
+    let re_read_pack =
+        AssetPack::from_read_with_options(&mut Cursor::new(shared_pack), &options).unwrap();
+    assert_eq!(re_read_pack.meta, pack.meta);
+    assert_eq!(re_read_pack.tags, pack.tags);
+    assert_eq!(re_read_pack.object_files, pack.object_files);
+    assert_eq!(re_read_pack.other_files, pack.other_files);
+}
+
+#[test]
+fn asset_pack_write_shares_offsets_by_contents() {
+    let mut pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+    pack.object_files
+        .insert("textures/objects/rock_a.png".to_string(), b"rock".to_vec());
+    pack.object_files
+        .insert("textures/objects/rock_b.png".to_string(), b"rock".to_vec());
+    pack.object_files
+        .insert("textures/objects/rock_c.png".to_string(), b"rocks".to_vec());
+
+    let options = WriteOptions {
+        share_metadata_offset: true,
+        sort_files_by_path: true,
+        ..Default::default()
+    };
+    let mut shared_pack = vec![];
+    pack.to_write_with_options(&mut shared_pack, &options)
+        .unwrap();
+
+    let (_, files_meta) =
+        AssetPack::read_file_table(&mut Cursor::new(shared_pack.clone()), None).unwrap();
+    let offset_of = |name: &str| {
+        files_meta
+            .iter()
+            .find(|meta| meta.path.ends_with(name))
+            .unwrap()
+            .offset
+    };
+    assert_eq!(offset_of("rock_a.png"), offset_of("rock_b.png"));
+    assert_ne!(offset_of("rock_b.png"), offset_of("rock_c.png"));
+
+    let re_read_pack = AssetPack::from_read(&mut Cursor::new(shared_pack)).unwrap();
+    assert_eq!(re_read_pack.object_files, pack.object_files);
+}
+
+#[test]
+fn asset_pack_write_compressed_files() {
+    let mut pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+    let rock_path = "textures/objects/rock.png".to_string();
+    let rock = b"rock ".repeat(200);
+    pack.object_files.insert(rock_path.clone(), rock.clone());
+
+    let options = WriteOptions {
+        compress: true,
+        ..Default::default()
+    };
+    // Godot 3 packs have no entry flags to mark the compressed files with.
+    let mut refused_pack = vec![];
+    assert!(pack
+        .to_write_with_options(&mut refused_pack, &options)
+        .is_err());
+    assert!(refused_pack.is_empty());
+
+    pack.godot_version = GodotVersion::new(2, 4, 3, 0);
+    let mut plain_pack = vec![];
+    pack.to_write(&mut plain_pack).unwrap();
+    let mut compressed_pack = vec![];
+    pack.to_write_with_options(&mut compressed_pack, &options)
+        .unwrap();
+    assert!(compressed_pack.len() < plain_pack.len());
+
+    let (_, files_meta) =
+        AssetPack::read_file_table(&mut Cursor::new(compressed_pack.clone()), None).unwrap();
+    for meta in files_meta.iter() {
+        assert_eq!(
+            meta.is_compressed(),
+            meta.path == rock_path,
+            "{}",
+            meta.path
+        );
+    }
+    let rock_meta = files_meta
+        .iter()
+        .find(|meta| meta.path == rock_path)
+        .unwrap();
+    assert!(rock_meta.size < rock.len());
+
+    let re_read_pack = AssetPack::from_read(&mut Cursor::new(compressed_pack.clone())).unwrap();
+    assert_eq!(re_read_pack.object_files, pack.object_files);
+    assert_eq!(re_read_pack.other_files, pack.other_files);
+    assert_eq!(re_read_pack.meta, pack.meta);
+    assert_eq!(
+        AssetPack::read_single_file(&mut Cursor::new(&compressed_pack), &rock_path)
+            .unwrap()
+            .unwrap(),
+        rock
+    );
+}
+
+#[test]
+fn asset_pack_read_single_file() {
+    let raw_pack = create_raw_test_pack().unwrap();
+
+    let file =
+        AssetPack::read_single_file(&mut Cursor::new(&raw_pack), "textures/objects/random.png")
+            .unwrap()
+            .unwrap();
+    assert_eq!(file.len(), TEST_PACK_FAKE_PNG.len());
+    assert_eq!(file, TEST_PACK_FAKE_PNG);
+
+    assert!(
+        AssetPack::read_single_file(&mut Cursor::new(&raw_pack), "textures/objects/gone.png")
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn asset_pack_from_read_path_id_differs_from_meta_id() {
+    let mut pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+    pack.meta.id = "ABCDEFGH".to_string();
+    let mut raw_pack = vec![];
+    pack.to_write(&mut raw_pack).unwrap();
+
+    // Change the id in the metadata, but not in the file paths.
+    let old_id = br#""id":"ABCDEFGH""#;
+    let mut replaced = 0;
+    for start in 0..raw_pack.len() - old_id.len() {
+        if &raw_pack[start..start + old_id.len()] == old_id {
+            raw_pack[start..start + old_id.len()].copy_from_slice(br#""id":"12345678""#);
+            replaced += 1;
+        }
+    }
+    assert_eq!(replaced, 2);
+
+    let re_read_pack = AssetPack::from_read(&mut Cursor::new(raw_pack)).unwrap();
+
+    assert_eq!(re_read_pack.meta.id, "12345678");
+    assert_eq!(re_read_pack.object_files, pack.object_files);
+    assert_eq!(re_read_pack.other_files, pack.other_files);
+    assert_eq!(re_read_pack.tags, pack.tags);
+}
+
+#[test]
+fn asset_pack_from_read_with_custom_object_prefix() {
+    let mut pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+    pack.other_files
+        .insert("textures/materials/moss.png".to_string(), vec![1, 2]);
+    let mut raw_pack = vec![];
+    pack.to_write(&mut raw_pack).unwrap();
+
+    let default_pack = AssetPack::from_read(&mut Cursor::new(raw_pack.clone())).unwrap();
+    assert!(default_pack
+        .other_files
+        .contains_key("textures/materials/moss.png"));
+
+    let rules = ClassificationRules::with_object_prefixes(vec!["textures/materials/".to_string()]);
+    let custom_pack = AssetPack::from_read_with_rules(&mut Cursor::new(raw_pack), &rules).unwrap();
+
+    assert!(custom_pack
+        .object_files
+        .contains_key("textures/materials/moss.png"));
+    assert!(custom_pack
+        .object_files
+        .contains_key("textures/objects/random.png"));
+    assert!(!custom_pack
+        .other_files
+        .contains_key("textures/materials/moss.png"));
+}
+
+#[test]
+fn asset_pack_read_write_godot_4_entry_flags() {
+    let mut pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+    pack.godot_version = GodotVersion::new(2, 4, 3, 0);
+
+    let mut raw_pack = vec![];
+    pack.to_write(&mut raw_pack).unwrap();
+
+    let re_read_pack = AssetPack::from_read(&mut Cursor::new(raw_pack.clone())).unwrap();
+    assert_eq!(re_read_pack.godot_version, pack.godot_version);
+    assert_eq!(re_read_pack.meta, pack.meta);
+    assert_eq!(re_read_pack.object_files, pack.object_files);
+    assert_eq!(re_read_pack.other_files, pack.other_files);
+
+    // Mark the door as removed. Its flags follow the offset, size and md5 hash.
+    let path = b"res://packs/12345678/textures/portals/door.png";
+    let path_start = raw_pack
+        .windows(path.len())
+        .position(|window| window == path)
+        .unwrap();
+    let flags_start = path_start + path.len() + 8 + 8 + 16;
+    raw_pack[flags_start..flags_start + 4]
+        .copy_from_slice(&FileMetaData::FLAG_REMOVED.to_le_bytes());
+
+    let patched_pack = AssetPack::from_read(&mut Cursor::new(raw_pack)).unwrap();
+    assert!(patched_pack.other_files.is_empty());
+    assert_eq!(patched_pack.object_files, pack.object_files);
+}
+
+#[test]
+fn asset_pack_from_read_godot_4_layout() {
+    let raw_pack = create_raw_godot_4_test_pack().unwrap();
+
+    let pack = AssetPack::from_read(&mut Cursor::new(raw_pack.clone())).unwrap();
+    assert_eq!(pack.godot_version, GodotVersion::new(2, 4, 2, 1));
+    assert_eq!(pack.meta.id, "12345678");
+    assert!(pack.tags.tags.contains_key("MyTag"));
+    assert_eq!(
+        pack.object_files["textures/objects/random.png"],
+        TEST_PACK_FAKE_PNG
+    );
+    assert_eq!(
+        pack.other_files["textures/portals/door.png"],
+        TEST_PACK_FAKE_PNG
+    );
+    assert!(pack.verify_checksums().is_empty());
+
+    let mut written_pack = vec![];
+    pack.to_write(&mut written_pack).unwrap();
+    let re_read_pack = AssetPack::from_read(&mut Cursor::new(written_pack)).unwrap();
+    assert_eq!(re_read_pack.godot_version, pack.godot_version);
+    assert_eq!(re_read_pack.meta, pack.meta);
+    assert_eq!(re_read_pack.tags, pack.tags);
+    assert_eq!(re_read_pack.object_files, pack.object_files);
+    assert_eq!(re_read_pack.other_files, pack.other_files);
+
+    // The pack flags follow the magic number and the godot version.
+    let mut encrypted_pack = raw_pack.clone();
+    encrypted_pack[20] = PACK_DIR_ENCRYPTED as u8;
+    let error = AssetPack::from_read(&mut Cursor::new(encrypted_pack)).unwrap_err();
+    assert!(error.to_string().contains("encrypted"));
+
+    let mut newer_pack = raw_pack;
+    newer_pack[4] = 3;
+    let error = AssetPack::from_read(&mut Cursor::new(newer_pack)).unwrap_err();
+    assert!(error.to_string().contains("Pack format 3 is not supported"));
+}
+
+#[test]
+fn asset_pack_from_read_implausible_file_count() {
+    let mut cursor = Cursor::new(vec![]);
+    cursor.write_all(&[0x47, 0x44, 0x50, 0x43]).unwrap();
+    cursor.write_all(&[0; 4 * 4]).unwrap();
+    cursor.write_all(&[0; 16 * 4]).unwrap();
+    cursor.write_i32::<LE>(1_000_000_000).unwrap();
+    cursor.write_all(&[0; 100]).unwrap();
+    cursor.set_position(0);
+
+    let error = AssetPack::from_read(&mut cursor).unwrap_err();
+
+    assert!(error.to_string().contains("Implausible file count"));
+}
+
+#[test]
+fn asset_pack_from_read_big_endian() {
+    let raw_pack = create_raw_test_pack_with::<BE>(0).unwrap();
+
+    let pack = AssetPack::from_read(&mut Cursor::new(raw_pack)).unwrap();
+
+    assert_eq!(pack.godot_version, GodotVersion::new(1, 3, 2, 4));
+    assert_eq!(pack.meta.id, "12345678");
+    assert!(pack
+        .object_files
+        .contains_key("textures/objects/random.png"));
+    assert!(pack.other_files.contains_key("textures/portals/door.png"));
+}
+
+#[test]
+fn asset_pack_from_read_wrapped_in_junk() {
+    let mut raw_pack = b"WRAPPER v1\0\0junk".to_vec();
+    raw_pack.extend(create_raw_test_pack().unwrap());
+
+    let pack = AssetPack::from_read(&mut Cursor::new(&raw_pack)).unwrap();
+
+    assert_eq!(pack.meta.id, "12345678");
+    assert_eq!(
+        pack.object_files["textures/objects/random.png"],
+        AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap()))
+            .unwrap()
+            .object_files["textures/objects/random.png"]
+    );
+    assert!(pack.other_files.contains_key("textures/portals/door.png"));
+
+    let (godot_version, _) = AssetPack::read_version(&mut Cursor::new(&raw_pack)).unwrap();
+    assert_eq!(godot_version, pack.godot_version);
+}
+
+#[test]
+fn asset_pack_from_read_differing_meta_copies() {
+    // Changes the version in the second copy, `pack.json`, keeping the length the same.
+    let mut raw_pack = create_raw_test_pack().unwrap();
+    let version = br#""version": "1""#;
+    let pack_json_version = raw_pack
+        .windows(version.len())
+        .enumerate()
+        .filter(|(_, window)| *window == version)
+        .map(|(index, _)| index)
+        .nth(1)
+        .unwrap();
+    raw_pack[pack_json_version + version.len() - 2] = b'2';
+
+    let mut options = ReadOptions {
+        check_meta_consistency: true,
+        ..Default::default()
+    };
+
+    let pack = AssetPack::from_read_with_options(&mut Cursor::new(&raw_pack), &options).unwrap();
+    assert_eq!(pack.meta.version, "1");
+
+    options.prefer_pack_json = true;
+    let pack_json_pack =
+        AssetPack::from_read_with_options(&mut Cursor::new(&raw_pack), &options).unwrap();
+    assert_eq!(pack_json_pack.meta.version, "2");
+
+    assert_eq!(
+        pack.meta.differing_fields(&pack_json_pack.meta),
+        vec!["version: '1' != '2'"]
+    );
+
+    // Both copies are written the same.
+    let mut written_pack = vec![];
+    pack_json_pack.to_write(&mut written_pack).unwrap();
+    options.prefer_pack_json = false;
+    let re_read_pack =
+        AssetPack::from_read_with_options(&mut Cursor::new(written_pack), &options).unwrap();
+    assert_eq!(re_read_pack.meta, pack_json_pack.meta);
+}
+
+/// The paths in the file table, in the order they are written.
+fn read_raw_file_paths(raw_pack: Vec<u8>) -> Vec<String> {
+    let mut cursor = Cursor::new(raw_pack);
+    cursor.set_position(4 + 4 * 4 + 16 * 4);
+    let nr_of_files = cursor.read_i32::<LE>().unwrap();
+
+    (0..nr_of_files)
+        .map(|_| FileMetaData::from_read(&mut cursor).unwrap().path)
+        .collect()
+}
+
+#[test]
+fn asset_pack_from_read_with_gap_after_file_table() {
+    let raw_pack = create_raw_test_pack_with::<LE>(16).unwrap();
+
+    let pack = AssetPack::from_read(&mut Cursor::new(raw_pack)).unwrap();
+
+    assert_eq!(pack.meta.name, "example_pack");
+    assert_eq!(pack.tags.tags.len(), 2);
+    assert_eq!(
+        pack.object_files
+            .get("textures/objects/random.png")
+            .unwrap(),
+        &TEST_PACK_FAKE_PNG.to_vec()
+    );
+}
+
+#[test]
+fn asset_pack_read_version_only() {
+    let raw_pack = create_raw_test_pack().unwrap();
+
+    // Only the header, reading the file table would fail.
+    let header = &raw_pack[..4 + GodotVersion::size_in_bytes()];
+    let (godot_version, format_version) =
+        AssetPack::read_version(&mut Cursor::new(header.to_vec())).unwrap();
+
+    assert_eq!(godot_version, GodotVersion::new(1, 3, 2, 4));
+    assert_eq!(godot_version.to_string(), "1.3.2.4");
+    assert_eq!(format_version, 1);
+
+    assert!(AssetPack::read_version(&mut Cursor::new(vec![0; 20])).is_err());
+}
+
+#[test]
+fn asset_pack_keeps_trailing_bytes() {
+    let mut raw_pack = create_raw_test_pack().unwrap();
+    let trailer: Vec<u8> = (1..=16).collect();
+    raw_pack.extend_from_slice(&trailer);
+
+    let pack = AssetPack::from_read(&mut Cursor::new(raw_pack.clone())).unwrap();
+    assert_eq!(pack.trailer, trailer);
+
+    let mut written_pack = vec![];
+    pack.to_write(&mut written_pack).unwrap();
+    assert!(written_pack.ends_with(&trailer));
+
+    let re_read_pack = AssetPack::from_read(&mut Cursor::new(written_pack)).unwrap();
+    assert_eq!(re_read_pack.trailer, trailer);
+    assert_eq!(re_read_pack.object_files, pack.object_files);
+    assert_eq!(re_read_pack.other_files, pack.other_files);
+
+    let pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+    assert!(pack.trailer.is_empty());
+}
+
+#[test]
+fn asset_pack_from_read_with_table() {
+    let raw_pack = create_raw_test_pack().unwrap();
+
+    let (pack, table) =
+        AssetPack::from_read_with_table(&mut Cursor::new(raw_pack.clone())).unwrap();
+
+    // Root json, pack.json, tags file, object file and other file.
+    assert_eq!(table.len(), 5);
+    assert!(table.iter().all(|meta| meta.offset > 0));
+    assert!(table.windows(2).all(|pair| pair[0].offset < pair[1].offset));
+
+    for (path, data) in pack.object_files.iter().chain(pack.other_files.iter()) {
+        let meta = table.iter().find(|meta| &meta.path == path).unwrap();
+        let start = meta.offset as usize;
+        assert_eq!(&raw_pack[start..start + meta.size], data.as_slice());
+    }
+}
+
+#[test]
+fn asset_pack_from_read_at_without_header() {
+    let raw_pack = create_raw_test_pack().unwrap();
+    let pack = AssetPack::from_read(&mut Cursor::new(raw_pack.clone())).unwrap();
+
+    let headerless_pack =
+        AssetPack::from_read_at(&mut Cursor::new(raw_pack[4..].to_vec()), 0).unwrap();
+    assert_eq!(headerless_pack.meta, pack.meta);
+    assert_eq!(headerless_pack.tags, pack.tags);
+    assert_eq!(headerless_pack.object_files, pack.object_files);
+    assert_eq!(headerless_pack.other_files, pack.other_files);
+
+    let mut custom_header = b"CUSTOM".to_vec();
+    custom_header.extend_from_slice(&raw_pack[4..]);
+    let custom_header_pack = AssetPack::from_read_at(&mut Cursor::new(custom_header), 6).unwrap();
+    assert_eq!(custom_header_pack.object_files, pack.object_files);
+}
+
+#[test]
+fn asset_pack_from_read_lenient_skips_corrupt_file() {
+    let mut raw_pack = create_raw_test_pack().unwrap();
+
+    // Corrupt the size of the object file, so it runs past the end of the pack.
+    let path = b"res://packs/12345678/textures/objects/random.png";
+    let path_start = raw_pack
+        .windows(path.len())
+        .position(|window| window == path)
+        .unwrap();
+    let size_start = path_start + path.len() + 8;
+    raw_pack[size_start..size_start + 8].copy_from_slice(&1_000_000i64.to_le_bytes());
+
+    assert!(AssetPack::from_read(&mut Cursor::new(raw_pack.clone())).is_err());
+
+    let (pack, skipped) = AssetPack::from_read_lenient(&mut Cursor::new(raw_pack)).unwrap();
+
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].path, "textures/objects/random.png");
+    assert_eq!(skipped[0].index, 3);
+
+    assert_eq!(pack.meta.id, "12345678");
+    assert_eq!(pack.tags.tags.len(), 2);
+    assert!(pack.object_files.is_empty());
+    assert_eq!(
+        pack.other_files.get("textures/portals/door.png").unwrap(),
+        &TEST_PACK_FAKE_PNG.to_vec()
+    );
+}
+
+#[test]
+fn asset_pack_from_read_oversized_pack_json() {
+    let mut raw_pack = create_raw_test_pack().unwrap();
+
+    // Claim the root json is far larger than the pack, and than any reasonable allocation.
+    let path = b"res://packs/12345678.json";
+    let path_start = raw_pack
+        .windows(path.len())
+        .position(|window| window == path)
+        .unwrap();
+    let size_start = path_start + path.len() + 8;
+    raw_pack[size_start..size_start + 8].copy_from_slice(&(1i64 << 60).to_le_bytes());
+
+    assert!(AssetPack::from_read(&mut Cursor::new(raw_pack.clone())).is_err());
+    assert!(AssetPack::read_file_table(&mut Cursor::new(raw_pack), None).is_err());
+}
+
+#[test]
+fn asset_pack_from_read_overlapping_files() {
+    let mut raw_pack = create_raw_test_pack().unwrap();
+
+    // Move the door into the middle of the object file.
+    let path = b"res://packs/12345678/textures/portals/door.png";
+    let path_start = raw_pack
+        .windows(path.len())
+        .position(|window| window == path)
+        .unwrap();
+    let offset_start = path_start + path.len();
+    raw_pack[offset_start..offset_start + 8].copy_from_slice(&1085i64.to_le_bytes());
+
+    let error = AssetPack::from_read(&mut Cursor::new(raw_pack)).unwrap_err();
+
+    assert!(error.to_string().contains(
+        "'textures/objects/random.png' (bytes 1080 to 1090) \
+        and 'textures/portals/door.png' (bytes 1085 to 1095)"
+    ));
+}
+
+#[test]
+fn asset_pack_from_read_max_memory() {
+    let raw_pack = create_raw_test_pack().unwrap();
+
+    let options = ReadOptions {
+        max_memory: Some(100),
+        ..Default::default()
+    };
+    let error =
+        AssetPack::from_read_with_options(&mut Cursor::new(&raw_pack), &options).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("more than the maximum of 100 bytes"));
+
+    let options = ReadOptions {
+        max_memory: Some(raw_pack.len() as u64),
+        ..Default::default()
+    };
+    assert!(AssetPack::from_read_with_options(&mut Cursor::new(&raw_pack), &options).is_ok());
+}
+
+#[test]
+fn asset_pack_from_read_retains_stored_md5() {
+    let mut raw_pack = create_raw_test_pack().unwrap();
+
+    let pack = AssetPack::from_read(&mut Cursor::new(raw_pack.clone())).unwrap();
+    assert_eq!(pack.stored_md5.len(), 2);
+    assert_eq!(
+        pack.stored_md5["textures/objects/random.png"],
+        [0; MD5_BYTES]
+    );
+    assert_eq!(pack.stored_md5["textures/portals/door.png"], [0; MD5_BYTES]);
+    // Zeroed hashes are not checked.
+    assert!(pack.verify_checksums().is_empty());
+
+    // Fill in the hash of the object file. It follows the path, offset and size.
+    let path = b"res://packs/12345678/textures/objects/random.png";
+    let path_start = raw_pack
+        .windows(path.len())
+        .position(|window| window == path)
+        .unwrap();
+    let md5_start = path_start + path.len() + 8 + 8;
+    let md5 = md5_hash(&pack.object_files["textures/objects/random.png"]);
+    raw_pack[md5_start..md5_start + MD5_BYTES].copy_from_slice(&md5);
+
+    let pack = AssetPack::from_read(&mut Cursor::new(raw_pack.clone())).unwrap();
+    assert_eq!(pack.stored_md5["textures/objects/random.png"], md5);
+    assert!(pack.verify_checksums().is_empty());
+
+    raw_pack[md5_start] ^= 0xFF;
+
+    let pack = AssetPack::from_read(&mut Cursor::new(raw_pack)).unwrap();
+    assert_eq!(
+        pack.verify_checksums(),
+        vec!["textures/objects/random.png".to_string()]
+    );
+}
+
+pub(crate) fn create_raw_test_pack() -> Result<Vec<u8>> {
+    create_raw_test_pack_with::<LE>(0)
+}
+
+/// Test pack with `gap` unused bytes between the file table and the first file,
+/// with all numbers in byte order `B`.
+pub(crate) fn create_raw_test_pack_with<B: ByteOrder>(gap: i64) -> Result<Vec<u8>> {
     let data: Vec<u8> = vec![];
     let mut cursor = Cursor::new(data);
 
@@ -91,58 +1011,60 @@ fn create_raw_test_pack() -> Result<Vec<u8>> {
     cursor.write_all(&magic_num)?;
 
     // Godot version
-    cursor.write_i32::<LE>(1)?; // Version
-    cursor.write_i32::<LE>(3)?; // Major
-    cursor.write_i32::<LE>(2)?; // Minor
-    cursor.write_i32::<LE>(4)?; // Revision
+    cursor.write_i32::<B>(1)?; // Version
+    cursor.write_i32::<B>(3)?; // Major
+    cursor.write_i32::<B>(2)?; // Minor
+    cursor.write_i32::<B>(4)?; // Revision
 
     // Reserved space
     cursor.write_all(&[0; 16 * 4])?;
 
     // Number of files
-    cursor.write_i32::<LE>(5)?;
+    cursor.write_i32::<B>(5)?;
 
     // ---- File metadata ----
 
     // For some reason Dungeondraft has two identical files in each pack.
     // One json file in the `packs` folder, and another in the `packs/<pack-id>` folder.
-    write_file_meta(
+    write_file_meta::<B>(
         &mut cursor,
         "res://packs/12345678.json",
-        468,
+        468 + gap,
         TEST_PACK_META_JSON.len() as i64,
     )?;
-    write_file_meta(
+    write_file_meta::<B>(
         &mut cursor,
         "res://packs/12345678/pack.json",
-        683,
+        683 + gap,
         TEST_PACK_META_JSON.len() as i64,
     )?;
 
     // Tag file
-    write_file_meta(
+    write_file_meta::<B>(
         &mut cursor,
         "res://packs/12345678/data/default.dungeondraft_tags",
-        898,
+        898 + gap,
         TEST_PACK_TAGS_JSON.len() as i64,
     )?;
 
     // A random object file
-    write_file_meta(
+    write_file_meta::<B>(
         &mut cursor,
         "res://packs/12345678/textures/objects/random.png",
-        1080,
+        1080 + gap,
         TEST_PACK_FAKE_PNG.len() as i64,
     )?;
 
     // A random non-object file
-    write_file_meta(
+    write_file_meta::<B>(
         &mut cursor,
         "res://packs/12345678/textures/portals/door.png",
-        1090,
+        1090 + gap,
         TEST_PACK_FAKE_PNG.len() as i64,
     )?;
 
+    cursor.write_all(&vec![0; gap as usize])?;
+
     // ---- File contents ----
 
     // res://packs/12345678.json
@@ -160,11 +1082,16 @@ fn create_raw_test_pack() -> Result<Vec<u8>> {
     Ok(cursor.into_inner())
 }
 
-fn write_file_meta(cursor: &mut Cursor<Vec<u8>>, path: &str, offset: i64, size: i64) -> Result<()> {
-    cursor.write_i32::<LE>(path.len() as i32)?;
+fn write_file_meta<B: ByteOrder>(
+    cursor: &mut Cursor<Vec<u8>>,
+    path: &str,
+    offset: i64,
+    size: i64,
+) -> Result<()> {
+    cursor.write_i32::<B>(path.len() as i32)?;
     cursor.write_all(path.as_bytes())?;
-    cursor.write_i64::<LE>(offset)?;
-    cursor.write_i64::<LE>(size)?;
+    cursor.write_i64::<B>(offset)?;
+    cursor.write_i64::<B>(size)?;
 
     // md5 hash. Is actually unused in dungeondraft asset packs.
     cursor.write_all(&[0; 16])?;
@@ -172,6 +1099,81 @@ fn write_file_meta(cursor: &mut Cursor<Vec<u8>>, path: &str, offset: i64, size:
     Ok(())
 }
 
+/// Test pack laid out the way Godot 4.0 up to 4.3 writes them: the header has pack flags and
+/// a file base, the paths are padded to 4 bytes, every entry has flags, and the files are
+/// aligned to 32 bytes, with offsets counting from the file base.
+pub(crate) fn create_raw_godot_4_test_pack() -> Result<Vec<u8>> {
+    const ALIGNMENT: usize = 32;
+
+    let files: [(&str, &[u8]); 5] = [
+        ("res://packs/12345678.json", TEST_PACK_META_JSON.as_bytes()),
+        (
+            "res://packs/12345678/pack.json",
+            TEST_PACK_META_JSON.as_bytes(),
+        ),
+        (
+            "res://packs/12345678/data/default.dungeondraft_tags",
+            TEST_PACK_TAGS_JSON.as_bytes(),
+        ),
+        (
+            "res://packs/12345678/textures/objects/random.png",
+            &TEST_PACK_FAKE_PNG,
+        ),
+        (
+            "res://packs/12345678/textures/portals/door.png",
+            &TEST_PACK_FAKE_PNG,
+        ),
+    ];
+    let padding = |length: usize, alignment: usize| (alignment - length % alignment) % alignment;
+
+    let mut cursor = Cursor::new(vec![]);
+    cursor.write_all(&[0x47, 0x44, 0x50, 0x43])?;
+
+    // Godot version
+    cursor.write_i32::<LE>(2)?; // Version
+    cursor.write_i32::<LE>(4)?; // Major
+    cursor.write_i32::<LE>(2)?; // Minor
+    cursor.write_i32::<LE>(1)?; // Revision
+
+    // Pack flags, and the file base, which is filled in once it is known.
+    cursor.write_u32::<LE>(0)?;
+    let file_base_position = cursor.position();
+    cursor.write_u64::<LE>(0)?;
+
+    // Reserved space
+    cursor.write_all(&[0; 16 * 4])?;
+
+    cursor.write_i32::<LE>(files.len() as i32)?;
+
+    let mut offset = 0;
+    for (path, contents) in files.iter() {
+        let path_padding = padding(path.len(), 4);
+        cursor.write_i32::<LE>((path.len() + path_padding) as i32)?;
+        cursor.write_all(path.as_bytes())?;
+        cursor.write_all(&vec![0; path_padding])?;
+        cursor.write_u64::<LE>(offset as u64)?;
+        cursor.write_u64::<LE>(contents.len() as u64)?;
+        cursor.write_all(&md5_hash(contents))?;
+        // Flags
+        cursor.write_u32::<LE>(0)?;
+
+        offset += contents.len() + padding(contents.len(), ALIGNMENT);
+    }
+
+    cursor.write_all(&vec![0; padding(cursor.position() as usize, ALIGNMENT)])?;
+    let file_base = cursor.position();
+
+    for (_, contents) in files.iter() {
+        cursor.write_all(contents)?;
+        cursor.write_all(&vec![0; padding(contents.len(), ALIGNMENT)])?;
+    }
+
+    cursor.set_position(file_base_position);
+    cursor.write_u64::<LE>(file_base)?;
+
+    Ok(cursor.into_inner())
+}
+
 const TEST_PACK_META_JSON: &str = r#"
 {
 	"name": "example_pack",
@@ -206,3 +1208,16 @@ const TEST_PACK_TAGS_JSON: &str = r#"
 "#;
 
 const TEST_PACK_FAKE_PNG: [u8; 10] = [0; 10];
+
+/// A small PNG image with transparent and opaque pixels, gradients and flat areas.
+#[cfg(feature = "image-codec")]
+pub(crate) fn test_png(width: u32, height: u32) -> Vec<u8> {
+    let image = image::RgbaImage::from_fn(width, height, |x, y| {
+        let alpha = if x < width / 3 { 0 } else { 255 };
+        image::Rgba([(x * 7) as u8, (y * 13) as u8, ((x ^ y) * 3) as u8, alpha])
+    });
+
+    let mut png = Cursor::new(vec![]);
+    image.write_to(&mut png, image::ImageFormat::Png).unwrap();
+    png.into_inner()
+}
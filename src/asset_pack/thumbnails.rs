@@ -0,0 +1,59 @@
+use log::info;
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::path_utils::is_thumbnail_file;
+
+impl AssetPack {
+    /// The number and total size in bytes of the thumbnails in the pack.
+    pub fn thumbnail_stats(&self) -> (usize, u64) {
+        self.other_files
+            .iter()
+            .filter(|(path, _)| is_thumbnail_file(path))
+            .fold((0, 0), |(count, size), (_, data)| {
+                (count + 1, size + data.len() as u64)
+            })
+    }
+
+    /// Removes the thumbnails Dungeondraft generated, it makes them again when they are missing.
+    /// Returns the number of removed files.
+    pub fn strip_thumbnails(&mut self) -> usize {
+        let before = self.other_files.len();
+        self.other_files.retain(|path, _| !is_thumbnail_file(path));
+        let removed = before - self.other_files.len();
+
+        info!("Removed {} thumbnails.", removed);
+
+        removed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::path_utils::is_thumbnail_file;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+
+    #[test]
+    fn strip_thumbnails_keeps_other_files() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.other_files
+            .insert("thumbnails/abc123.png".to_string(), vec![1, 2, 3]);
+        pack.other_files
+            .insert("thumbnails/def456.png".to_string(), vec![4]);
+
+        assert!(is_thumbnail_file("thumbnails/abc123.png"));
+        assert!(!is_thumbnail_file("textures/thumbnails/abc123.png"));
+        assert_eq!(pack.thumbnail_stats(), (2, 4));
+
+        assert_eq!(pack.strip_thumbnails(), 2);
+
+        assert_eq!(pack.thumbnail_stats(), (0, 0));
+        assert!(pack.other_files.contains_key("textures/portals/door.png"));
+        assert!(pack
+            .object_files
+            .contains_key("textures/objects/random.png"));
+    }
+}
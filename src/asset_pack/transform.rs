@@ -0,0 +1,193 @@
+use anyhow::{bail, Context};
+use log::{debug, warn};
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::path_utils::{image_extension, image_format, OBJECT_IMAGE_EXTENSIONS};
+
+/// Changes the contents of files, for example to watermark textures.
+/// Applied to a pack with `AssetPack::transform_files`.
+pub trait FileTransform {
+    /// Returns the new contents of the file at `path`. Leaves the file unchanged by default.
+    fn transform(&self, path: &str, data: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+        let _ = path;
+        Ok(data)
+    }
+}
+
+/// Encodes images in a different format, see `AssetPack::convert_objects`.
+/// With the `image-codec` feature, `ImageCodec` converts between PNG and WebP.
+pub trait ImageConverter {
+    /// Whether `convert` can read images in format `from`. Objects in other formats
+    /// are left as they are. All formats by default.
+    fn can_read(&self, from: &str) -> bool {
+        let _ = from;
+        true
+    }
+
+    /// Returns the image in format `to`, e.g. `webp`.
+    /// `from` is the format it is in now, see `path_utils::image_format`.
+    fn convert(&self, data: &[u8], from: &str, to: &str) -> anyhow::Result<Vec<u8>>;
+}
+
+impl AssetPack {
+    /// Replaces the contents of all object and other files with the result of `transform`,
+    /// in order of path. The metadata and tags files are not passed to it.
+    /// Stops at the first file that fails, leaving the files before it transformed.
+    pub fn transform_files<T: FileTransform + ?Sized>(
+        &mut self,
+        transform: &T,
+    ) -> anyhow::Result<()> {
+        for files in [&mut self.object_files, &mut self.other_files] {
+            let mut paths: Vec<String> = files.keys().cloned().collect();
+            paths.sort();
+
+            for path in paths {
+                let data = files.get_mut(&path).unwrap();
+                *data = transform
+                    .transform(&path, std::mem::take(data))
+                    .context(format!("Could not transform '{}'", path))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Converts the object images that are not in the format of `extension` (e.g. `webp`)
+    /// with `converter`, and gives them that extension. The tags and data files referring
+    /// to them are updated, see `move_file`. Returns the number of converted objects.
+    /// Objects in a format the converter can't read are skipped with a warning.
+    /// Stops at the first object that fails, leaving the objects before it converted.
+    pub fn convert_objects<C: ImageConverter + ?Sized>(
+        &mut self,
+        extension: &str,
+        converter: &C,
+    ) -> anyhow::Result<usize> {
+        let extension = extension.to_ascii_lowercase();
+        if !OBJECT_IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+            bail!(
+                "Can't convert objects to '{}', expected one of: {}",
+                extension,
+                OBJECT_IMAGE_EXTENSIONS.join(", ")
+            );
+        }
+        let to = image_format(&extension);
+
+        let mut paths: Vec<String> = self.object_files.keys().cloned().collect();
+        paths.sort();
+
+        let mut converted = 0;
+        for path in paths {
+            let from = match image_extension(&path) {
+                Some(from_extension) if image_format(&from_extension) != to => from_extension,
+                _ => continue,
+            };
+            if !converter.can_read(image_format(&from)) {
+                warn!(
+                    "Can't convert '{}', {} images can't be read.",
+                    path,
+                    image_format(&from)
+                );
+                continue;
+            }
+
+            let data = converter
+                .convert(&self.object_files[&path], image_format(&from), to)
+                .context(format!("Could not convert '{}'", path))?;
+
+            let new_path = format!("{}{}", &path[..path.len() - from.len()], extension);
+            self.move_file(&path, &new_path)?;
+            self.object_files.insert(new_path.clone(), data);
+            // The hash in the file table was of the old contents.
+            self.stored_md5.remove(&new_path);
+
+            debug!("Converted '{}' to '{}'.", path, new_path);
+            converted += 1;
+        }
+
+        Ok(converted)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+    use crate::asset_pack::transform::{FileTransform, ImageConverter};
+
+    struct AppendToPng;
+
+    impl FileTransform for AppendToPng {
+        fn transform(&self, path: &str, mut data: Vec<u8>) -> anyhow::Result<Vec<u8>> {
+            if path.ends_with(".png") {
+                data.push(42);
+            }
+            Ok(data)
+        }
+    }
+
+    #[test]
+    fn transform_files_before_writing() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.other_files
+            .insert("data/walls/stone.dungeondraft_wall".to_string(), vec![1]);
+
+        pack.transform_files(&AppendToPng).unwrap();
+
+        let mut written_pack = vec![];
+        pack.to_write(&mut written_pack).unwrap();
+        let re_read_pack = AssetPack::from_read(&mut Cursor::new(written_pack)).unwrap();
+
+        let mut expected_png = vec![0; 10];
+        expected_png.push(42);
+        assert_eq!(
+            re_read_pack.object_files["textures/objects/random.png"],
+            expected_png
+        );
+        assert_eq!(
+            re_read_pack.other_files["textures/portals/door.png"],
+            expected_png
+        );
+        assert_eq!(
+            re_read_pack.other_files["data/walls/stone.dungeondraft_wall"],
+            vec![1]
+        );
+    }
+
+    /// Stands in for a real encoder, only writes the first bytes of a webp file.
+    struct FakeWebpEncoder;
+
+    impl ImageConverter for FakeWebpEncoder {
+        fn convert(&self, _data: &[u8], from: &str, to: &str) -> anyhow::Result<Vec<u8>> {
+            assert_eq!((from, to), ("png", "webp"));
+            Ok(b"RIFF\0\0\0\0WEBP".to_vec())
+        }
+    }
+
+    #[test]
+    fn convert_objects_to_webp() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.object_files
+            .insert("textures/objects/already.webp".to_string(), vec![1]);
+
+        assert_eq!(pack.convert_objects("webp", &FakeWebpEncoder).unwrap(), 1);
+
+        assert!(!pack
+            .object_files
+            .contains_key("textures/objects/random.png"));
+        assert_eq!(
+            pack.object_files["textures/objects/random.webp"],
+            b"RIFF\0\0\0\0WEBP"
+        );
+        assert_eq!(pack.object_files["textures/objects/already.webp"], vec![1]);
+        assert!(pack.tags.tags["MyTag"].contains("textures/objects/random.webp"));
+        assert!(!pack.tags.tags["MyTag"].contains("textures/objects/random.png"));
+        // Only objects are converted.
+        assert!(pack.other_files.contains_key("textures/portals/door.png"));
+
+        assert!(pack.convert_objects("gif", &FakeWebpEncoder).is_err());
+    }
+}
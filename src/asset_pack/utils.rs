@@ -1,11 +1,24 @@
 use anyhow::{Context, Result};
 use log::info;
-use std::io::Read;
+use md5::{Digest, Md5};
+use std::io::{Read, Seek, SeekFrom};
+use std::thread;
 
 pub const ASSET_PACK_MAGIC_FILE_HEADER: [u8; 4] = [0x47, 0x44, 0x50, 0x43];
+/// Magic numbers accepted at the start of a pack, `GDPC` first.
+/// The reversed one is written by tools that use the wrong byte order.
+pub const RECOGNIZED_MAGIC_FILE_HEADERS: [[u8; 4]; 2] =
+    [ASSET_PACK_MAGIC_FILE_HEADER, [0x43, 0x50, 0x44, 0x47]];
+/// How many bytes at the start of a file are searched for `GDPC`,
+/// for packs wrapped in a custom header.
+pub const MAGIC_SEARCH_LENGTH: usize = 4096;
 pub const I32: usize = 4;
 pub const I64: usize = 8;
 pub const GODOT_METADATA_RESERVED_SPACE: usize = 16 * I32;
+/// The newest pack format that can be read, the one of Godot 4.0 up to 4.3.
+pub const MAX_PACK_FORMAT_VERSION: u32 = 2;
+/// Pack flag of Godot 4 packs whose file table is encrypted.
+pub const PACK_DIR_ENCRYPTED: u32 = 1 << 0;
 pub const MD5_BYTES: usize = 16;
 
 pub fn read_string(data: &mut dyn Read, length: usize) -> Result<String> {
@@ -13,9 +26,167 @@ pub fn read_string(data: &mut dyn Read, length: usize) -> Result<String> {
     data.read_exact(bytes.as_mut_slice())
         .context("Could not read string")?;
 
-    Ok(String::from_utf8(bytes).context("Could not convert string from bytes")?)
+    String::from_utf8(bytes).context("Could not convert string from bytes")
 }
 
 pub fn display_file_as_info(file_data: &str) {
     info!("```\n{}\n```", file_data);
 }
+
+/// Number of bytes between the current position and the end of the stream.
+/// Leaves the stream at the current position.
+pub fn remaining_length<S: Seek>(data: &mut S) -> Result<u64> {
+    let position = data.stream_position()?;
+    let end = data.seek(SeekFrom::End(0))?;
+    data.seek(SeekFrom::Start(position))?;
+
+    Ok(end.saturating_sub(position))
+}
+
+pub fn md5_hash(data: &[u8]) -> [u8; MD5_BYTES] {
+    Md5::digest(data).into()
+}
+
+/// The md5 hash of each of the files, in the same order, computed on at most `threads`
+/// threads at the same time.
+pub fn md5_hashes(files: &[&[u8]], threads: usize) -> Vec<[u8; MD5_BYTES]> {
+    if threads <= 1 || files.len() <= 1 {
+        return files.iter().map(|data| md5_hash(data)).collect();
+    }
+
+    let chunk_size = files.len().div_ceil(threads);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = files
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || chunk.iter().map(|data| md5_hash(data)).collect::<Vec<_>>())
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut index = 0;
+
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+
+    table
+}
+
+/// The checksum zip archives and gzip streams use.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, byte| {
+        CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Spreads compact json, as written by `json5::to_string`, over multiple lines,
+/// indented with tabs. Empty objects and arrays stay on one line.
+pub fn pretty_json(compact: &str) -> String {
+    let mut pretty = String::with_capacity(compact.len() * 2);
+    let mut indent = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut chars = compact.chars().peekable();
+
+    let new_line = |pretty: &mut String, indent: usize| {
+        pretty.push('\n');
+        pretty.extend(std::iter::repeat_n('\t', indent));
+    };
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            pretty.push(c);
+
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                pretty.push(c);
+            }
+            '{' | '[' => {
+                pretty.push(c);
+
+                if matches!(chars.peek(), Some('}') | Some(']')) {
+                    pretty.push(chars.next().unwrap());
+                } else {
+                    indent += 1;
+                    new_line(&mut pretty, indent);
+                }
+            }
+            '}' | ']' => {
+                indent = indent.saturating_sub(1);
+                new_line(&mut pretty, indent);
+                pretty.push(c);
+            }
+            ',' => {
+                pretty.push(c);
+                new_line(&mut pretty, indent);
+            }
+            ':' => pretty.push_str(": "),
+            _ => pretty.push(c),
+        }
+    }
+
+    pretty
+}
+
+#[cfg(test)]
+mod test {
+    use crate::asset_pack::utils::{crc32, md5_hash, md5_hashes};
+
+    #[test]
+    fn parallel_md5_hashes_match_serial() {
+        let files: Vec<Vec<u8>> = (0..37u32)
+            .map(|i| (0..i * 100).map(|byte| (byte % 251) as u8).collect())
+            .collect();
+        let slices: Vec<&[u8]> = files.iter().map(Vec::as_slice).collect();
+
+        let serial = md5_hashes(&slices, 1);
+        assert_eq!(
+            serial,
+            files.iter().map(|data| md5_hash(data)).collect::<Vec<_>>()
+        );
+
+        for threads in [2, 4, 8, 100] {
+            assert_eq!(md5_hashes(&slices, threads), serial);
+        }
+        assert!(md5_hashes(&[], 4).is_empty());
+    }
+
+    #[test]
+    fn crc32_of_known_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+}
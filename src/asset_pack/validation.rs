@@ -0,0 +1,927 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+
+use anyhow::bail;
+use byteorder::{ByteOrder, BE, LE};
+use log::{debug, info, warn};
+
+use crate::asset_pack::asset_pack::AssetPack;
+use crate::asset_pack::path_utils::{
+    image_extension, image_format, is_non_portable_name, is_thumbnail_file, portable_name,
+    DATA_PREFIX, TEXTURES_PREFIX,
+};
+
+/// Size of the serialized tags file above which Dungeondraft becomes noticeably slow
+/// to load the pack, in bytes.
+pub const MAX_TAGS_FILE_SIZE: usize = 1024 * 1024;
+
+/// Turns the optional checks of `AssetPack::validate` on.
+#[derive(Debug, Default, Clone)]
+pub struct ValidationOptions {
+    /// Report paths that contain spaces or non-ASCII characters.
+    pub check_portable_names: bool,
+    /// Report tags that reference more files than this.
+    pub max_files_per_tag: Option<usize>,
+    /// Report object files whose first bytes don't match their image extension.
+    pub check_image_headers: bool,
+    /// Report tags that reference files the pack does not have.
+    pub check_references: bool,
+    /// Report object files that are not in any tag.
+    pub check_untagged: bool,
+    /// Report object textures wider or higher than this many pixels.
+    /// Only png and webp images are measured.
+    pub max_texture_size: Option<u32>,
+    /// Report metadata and tags files saved with a byte order mark or Windows line endings.
+    pub check_encoding: bool,
+    /// Report tags that have the same files as another tag.
+    pub check_redundant_tags: bool,
+    /// With `check_redundant_tags`, also report tags whose files are all in another tag.
+    pub redundant_tag_subsets: bool,
+    /// Report tags that are in no tag set, and the objects that are only in such tags.
+    pub check_sets: bool,
+}
+
+/// A problem with a pack that does not prevent reading it,
+/// but might cause trouble in Dungeondraft or when working with the pack.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ValidationIssue {
+    /// These paths only differ in upper/lower case, so they would overwrite each other
+    /// when extracted on a case-insensitive file system (Windows, macOS).
+    CaseCollision(Vec<String>),
+    /// This path contains spaces or non-ASCII characters. Dungeondraft handles these fine,
+    /// but other tools might not.
+    NonPortableName(String),
+    /// A tag set with the same name as a tag, which makes it ambiguous which one is meant.
+    SetNamedLikeTag(String),
+    /// A tag set that lists its own name. Sets can only contain tags, never sets.
+    SetContainsItself(String),
+    /// This file is outside of the `textures/` and `data/` directories,
+    /// so Dungeondraft will not use it. Usually it was included by accident.
+    UnrecognizedLocation(String),
+    /// This tag references more files than `ValidationOptions::max_files_per_tag`.
+    TagHasManyFiles { tag: String, files: usize },
+    /// The serialized tags file is larger than `MAX_TAGS_FILE_SIZE` bytes.
+    TagsFileTooLarge(usize),
+    /// This object file has an image extension, but its contents are a different format,
+    /// e.g. a renamed jpg. `detected` is `unknown` if the format was not recognized.
+    ExtensionMismatch { path: String, detected: String },
+    /// This tag references a file the pack does not have.
+    DanglingReference { tag: String, path: String },
+    /// This object file is not in any tag, so it can only be found by browsing the folders.
+    UntaggedObject(String),
+    /// This texture is larger than `ValidationOptions::max_texture_size`,
+    /// which costs a lot of video memory in Dungeondraft.
+    OversizedTexture {
+        path: String,
+        width: u32,
+        height: u32,
+    },
+    /// This json file was saved with a byte order mark and/or Windows line endings.
+    /// Dungeondraft reads it fine, but the source files are better normalized.
+    JsonEncoding { path: String, bom: bool, crlf: bool },
+    /// All files of `tag` are also in `other`, and if `identical` the other way around too,
+    /// e.g. `barrels` and `Barrel`. The two can probably be merged.
+    RedundantTag {
+        tag: String,
+        other: String,
+        identical: bool,
+    },
+    /// This tag is in no tag set, which makes it harder to find in Dungeondraft.
+    TagNotInAnySet(String),
+    /// All tags of this object are in no tag set.
+    ObjectNotInAnySet(String),
+    /// This `data/*.dungeondraft_*` file is not valid json, which breaks the pack in Dungeondraft.
+    InvalidDataFile { path: String, error: String },
+}
+
+impl ValidationIssue {
+    /// Short name of the check that found this issue, e.g. `case-collisions`.
+    pub fn check(&self) -> &'static str {
+        match self {
+            ValidationIssue::CaseCollision(_) => "case-collisions",
+            ValidationIssue::NonPortableName(_) => "non-portable-names",
+            ValidationIssue::SetNamedLikeTag(_) => "set-named-like-tag",
+            ValidationIssue::SetContainsItself(_) => "set-contains-itself",
+            ValidationIssue::UnrecognizedLocation(_) => "unrecognized-locations",
+            ValidationIssue::TagHasManyFiles { .. } => "many-files-per-tag",
+            ValidationIssue::TagsFileTooLarge(_) => "tags-file-size",
+            ValidationIssue::ExtensionMismatch { .. } => "extension-mismatches",
+            ValidationIssue::DanglingReference { .. } => "dangling-refs",
+            ValidationIssue::UntaggedObject(_) => "untagged-objects",
+            ValidationIssue::OversizedTexture { .. } => "oversized-textures",
+            ValidationIssue::JsonEncoding { .. } => "json-encoding",
+            ValidationIssue::RedundantTag { .. } => "redundant-tags",
+            ValidationIssue::TagNotInAnySet(_) => "setless-tags",
+            ValidationIssue::ObjectNotInAnySet(_) => "setless-objects",
+            ValidationIssue::InvalidDataFile { .. } => "invalid-data-files",
+        }
+    }
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationIssue::CaseCollision(paths) => write!(
+                f,
+                "Paths only differ in upper/lower case: '{}'",
+                paths.join("', '")
+            ),
+            ValidationIssue::NonPortableName(path) => write!(
+                f,
+                "Path contains spaces or non-ASCII characters: '{}'",
+                path
+            ),
+            ValidationIssue::SetNamedLikeTag(name) => {
+                write!(f, "Tag set has the same name as a tag: '{}'", name)
+            }
+            ValidationIssue::SetContainsItself(name) => {
+                write!(f, "Tag set contains itself: '{}'", name)
+            }
+            ValidationIssue::UnrecognizedLocation(path) => write!(
+                f,
+                "File is not in the `textures/` or `data/` directory: '{}'",
+                path
+            ),
+            ValidationIssue::TagHasManyFiles { tag, files } => {
+                write!(f, "Tag '{}' references {} files", tag, files)
+            }
+            ValidationIssue::ExtensionMismatch { path, detected } => write!(
+                f,
+                "File contents are {}, which does not match the extension: '{}'",
+                detected, path
+            ),
+            ValidationIssue::TagsFileTooLarge(size) => write!(
+                f,
+                "Tags file is {} bytes, Dungeondraft might be slow to load it",
+                size
+            ),
+            ValidationIssue::DanglingReference { tag, path } => write!(
+                f,
+                "Tag '{}' references a file the pack does not have: '{}'",
+                tag, path
+            ),
+            ValidationIssue::UntaggedObject(path) => {
+                write!(f, "Object is not in any tag: '{}'", path)
+            }
+            ValidationIssue::OversizedTexture {
+                path,
+                width,
+                height,
+            } => write!(f, "Texture is {}x{} pixels: '{}'", width, height, path),
+            ValidationIssue::JsonEncoding { path, bom, crlf } => {
+                let problems: Vec<&str> =
+                    [(*bom, "a byte order mark"), (*crlf, "CRLF line endings")]
+                        .iter()
+                        .filter(|(present, _)| *present)
+                        .map(|(_, problem)| *problem)
+                        .collect();
+                write!(f, "Json file has {}: '{}'", problems.join(" and "), path)
+            }
+            ValidationIssue::RedundantTag {
+                tag,
+                other,
+                identical: true,
+            } => write!(f, "Tags '{}' and '{}' have the same files", tag, other),
+            ValidationIssue::RedundantTag {
+                tag,
+                other,
+                identical: false,
+            } => write!(f, "All files of tag '{}' are also in tag '{}'", tag, other),
+            ValidationIssue::TagNotInAnySet(tag) => write!(f, "Tag is in no tag set: '{}'", tag),
+            ValidationIssue::ObjectNotInAnySet(path) => {
+                write!(
+                    f,
+                    "Object is only in tags that are in no tag set: '{}'",
+                    path
+                )
+            }
+            ValidationIssue::InvalidDataFile { path, error } => {
+                write!(f, "Data file is not valid json: '{}': {}", path, error)
+            }
+        }
+    }
+}
+
+impl AssetPack {
+    /// Runs all checks that are always on, and the optional ones that are enabled.
+    /// Returns the issues found.
+    pub fn validate(&self, options: &ValidationOptions) -> Vec<ValidationIssue> {
+        let mut issues = vec![];
+
+        issues.extend(
+            self.find_case_collisions()
+                .into_iter()
+                .map(ValidationIssue::CaseCollision),
+        );
+
+        issues.extend(
+            self.find_sets_named_like_tags()
+                .into_iter()
+                .map(ValidationIssue::SetNamedLikeTag),
+        );
+        issues.extend(
+            self.find_sets_containing_themselves()
+                .into_iter()
+                .map(ValidationIssue::SetContainsItself),
+        );
+
+        issues.extend(
+            self.find_invalid_data_files()
+                .into_iter()
+                .map(|(path, error)| ValidationIssue::InvalidDataFile { path, error }),
+        );
+
+        issues.extend(
+            self.unrecognized_files()
+                .into_iter()
+                .map(|path| ValidationIssue::UnrecognizedLocation(path.to_owned())),
+        );
+
+        if let Some(max_files) = options.max_files_per_tag {
+            issues.extend(
+                self.find_tags_with_more_files_than(max_files)
+                    .into_iter()
+                    .map(|(tag, files)| ValidationIssue::TagHasManyFiles { tag, files }),
+            );
+        }
+
+        let tags_file_size = self.tags_file_size();
+        if tags_file_size > MAX_TAGS_FILE_SIZE {
+            issues.push(ValidationIssue::TagsFileTooLarge(tags_file_size));
+        }
+
+        if options.check_image_headers {
+            issues.extend(
+                self.find_extension_mismatches()
+                    .into_iter()
+                    .map(|(path, detected)| ValidationIssue::ExtensionMismatch {
+                        path,
+                        detected: detected.to_owned(),
+                    }),
+            );
+        }
+
+        if options.check_portable_names {
+            issues.extend(
+                self.find_non_portable_names()
+                    .into_iter()
+                    .map(ValidationIssue::NonPortableName),
+            );
+        }
+
+        if options.check_references {
+            issues.extend(
+                self.find_dangling_references()
+                    .into_iter()
+                    .map(|(tag, path)| ValidationIssue::DanglingReference { tag, path }),
+            );
+        }
+
+        if options.check_untagged {
+            issues.extend(
+                self.find_untagged_objects()
+                    .into_iter()
+                    .map(ValidationIssue::UntaggedObject),
+            );
+        }
+
+        if let Some(max_size) = options.max_texture_size {
+            issues.extend(self.find_oversized_textures(max_size).into_iter().map(
+                |(path, width, height)| ValidationIssue::OversizedTexture {
+                    path,
+                    width,
+                    height,
+                },
+            ));
+        }
+
+        if options.check_encoding {
+            issues.extend(self.json_encodings.iter().map(|(path, encoding)| {
+                ValidationIssue::JsonEncoding {
+                    path: path.clone(),
+                    bom: encoding.bom,
+                    crlf: encoding.crlf,
+                }
+            }));
+        }
+
+        if options.check_redundant_tags {
+            issues.extend(
+                self.find_redundant_tags_with(options.redundant_tag_subsets)
+                    .into_iter()
+                    .map(|(tag, other)| ValidationIssue::RedundantTag {
+                        identical: self.tags.tags[&tag] == self.tags.tags[&other],
+                        tag,
+                        other,
+                    }),
+            );
+        }
+
+        if options.check_sets {
+            issues.extend(
+                self.find_setless_tags()
+                    .into_iter()
+                    .map(ValidationIssue::TagNotInAnySet),
+            );
+            issues.extend(
+                self.find_setless_objects()
+                    .into_iter()
+                    .map(ValidationIssue::ObjectNotInAnySet),
+            );
+        }
+
+        issues
+    }
+
+    /// Tags that are in no tag set, sorted.
+    pub fn find_setless_tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = self
+            .tags
+            .tags
+            .keys()
+            .filter(|tag| self.sets_containing_tag(tag).is_empty())
+            .cloned()
+            .collect();
+
+        tags.sort();
+        tags
+    }
+
+    /// Object files that are in at least one tag, but only in tags that are in no tag set,
+    /// sorted. Untagged objects are left out, see `find_untagged_objects`.
+    pub fn find_setless_objects(&self) -> Vec<String> {
+        let setless_tags = self.find_setless_tags();
+        let mut paths: Vec<String> = self
+            .object_files
+            .keys()
+            .filter(|path| {
+                let mut tags = self
+                    .tags
+                    .tags
+                    .iter()
+                    .filter(|(_, files)| files.contains(*path))
+                    .peekable();
+
+                tags.peek().is_some() && tags.all(|(tag, _)| setless_tags.contains(tag))
+            })
+            .cloned()
+            .collect();
+
+        paths.sort();
+        paths
+    }
+
+    /// Adds the tags returned by `find_setless_tags` to the tag set, creating it if needed.
+    /// Returns the number of tags added.
+    pub fn add_setless_tags_to_set(&mut self, set: &str) -> usize {
+        let setless_tags = self.find_setless_tags();
+        let added = setless_tags.len();
+
+        if added > 0 {
+            self.tags
+                .sets
+                .entry(set.to_owned())
+                .or_default()
+                .extend(setless_tags);
+        }
+
+        info!("Added {} tags to tag set '{}'.", added, set);
+
+        added
+    }
+
+    /// Pairs of tags that have exactly the same files, sorted. The first tag of each pair
+    /// sorts before the second. Empty tags are left out.
+    pub fn find_redundant_tags(&self) -> Vec<(String, String)> {
+        self.find_redundant_tags_with(false)
+    }
+
+    /// Like `find_redundant_tags`, but if `include_subsets`, also pairs of a tag whose
+    /// files are all in another, larger tag, with the smaller tag first.
+    pub fn find_redundant_tags_with(&self, include_subsets: bool) -> Vec<(String, String)> {
+        let mut pairs = vec![];
+
+        for (tag, files) in self.tags.tags.iter() {
+            if files.is_empty() {
+                continue;
+            }
+
+            for (other, other_files) in self.tags.tags.iter() {
+                let redundant = if files.len() == other_files.len() {
+                    tag < other && files == other_files
+                } else {
+                    include_subsets
+                        && files.len() < other_files.len()
+                        && files.is_subset(other_files)
+                };
+
+                if redundant {
+                    pairs.push((tag.clone(), other.clone()));
+                }
+            }
+        }
+
+        pairs.sort();
+        pairs
+    }
+
+    /// Tags with the paths they reference that the pack does not have,
+    /// sorted by tag and then path.
+    pub fn find_dangling_references(&self) -> Vec<(String, String)> {
+        let mut references: Vec<(String, String)> = self
+            .tags
+            .tags
+            .iter()
+            .flat_map(|(tag, files)| {
+                files
+                    .iter()
+                    .filter(|path| !self.object_files.contains_key(*path))
+                    .map(move |path| (tag.clone(), path.clone()))
+            })
+            .collect();
+
+        references.sort();
+        references
+    }
+
+    /// Fails with the offending tags and paths if any tag references a file the pack
+    /// does not have, see `WriteOptions::strict_tags`.
+    pub fn check_tag_references(&self) -> anyhow::Result<()> {
+        let dangling = self.find_dangling_references();
+        if dangling.is_empty() {
+            return Ok(());
+        }
+
+        let offenders: Vec<String> = dangling
+            .iter()
+            .map(|(tag, path)| format!("'{}' in tag '{}'", path, tag))
+            .collect();
+        bail!(
+            "Tags reference {} files the pack does not have:\n{}",
+            offenders.len(),
+            offenders.join("\n")
+        )
+    }
+
+    /// Object files that are in no tag, sorted. Thumbnails are never tagged,
+    /// so they are left out.
+    pub fn find_untagged_objects(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self
+            .object_files
+            .keys()
+            .filter(|path| !is_thumbnail_file(path))
+            .filter(|path| !self.tags.tags.values().any(|files| files.contains(*path)))
+            .cloned()
+            .collect();
+
+        paths.sort();
+        paths
+    }
+
+    /// Object textures that are wider or higher than `max_size` pixels,
+    /// with their width and height, sorted by path.
+    pub fn find_oversized_textures(&self, max_size: u32) -> Vec<(String, u32, u32)> {
+        let mut textures: Vec<(String, u32, u32)> = self
+            .object_files
+            .iter()
+            .filter_map(|(path, data)| {
+                let (width, height) = image_dimensions(data)?;
+                (width > max_size || height > max_size).then(|| (path.clone(), width, height))
+            })
+            .collect();
+
+        textures.sort();
+        textures
+    }
+
+    /// Files that are not in the `textures/` or `data/` directories, sorted.
+    /// The metadata and tags files are not included, those are never in `other_files`.
+    pub fn unrecognized_files(&self) -> Vec<&str> {
+        let mut paths: Vec<&str> = self
+            .other_files
+            .keys()
+            .map(String::as_str)
+            .filter(|path| !path.starts_with(TEXTURES_PREFIX) && !path.starts_with(DATA_PREFIX))
+            .collect();
+
+        paths.sort_unstable();
+        paths
+    }
+
+    /// Total number of file references over all tags.
+    /// A file with multiple tags is counted once for each tag.
+    pub fn tag_file_associations(&self) -> usize {
+        self.tags.tags.values().map(|files| files.len()).sum()
+    }
+
+    /// Tags that reference more than `max_files` files, with their number of files,
+    /// sorted by tag name.
+    pub fn find_tags_with_more_files_than(&self, max_files: usize) -> Vec<(String, usize)> {
+        let mut tags: Vec<(String, usize)> = self
+            .tags
+            .tags
+            .iter()
+            .filter(|(_, files)| files.len() > max_files)
+            .map(|(tag, files)| (tag.clone(), files.len()))
+            .collect();
+
+        tags.sort();
+        tags
+    }
+
+    /// Size of the tags file as it would be written into the pack, in bytes.
+    pub fn tags_file_size(&self) -> usize {
+        json5::to_string(&self.tags).map_or(0, |json| json.len())
+    }
+
+    /// Names of tag sets that are also the name of a tag, sorted.
+    pub fn find_sets_named_like_tags(&self) -> Vec<String> {
+        let mut sets: Vec<String> = self
+            .tags
+            .sets
+            .keys()
+            .filter(|set| self.tags.tags.contains_key(*set))
+            .cloned()
+            .collect();
+
+        sets.sort();
+        sets
+    }
+
+    /// Names of tag sets that list their own name, sorted.
+    pub fn find_sets_containing_themselves(&self) -> Vec<String> {
+        let mut sets: Vec<String> = self
+            .tags
+            .sets
+            .iter()
+            .filter(|(set, tags)| tags.contains(*set))
+            .map(|(set, _)| set.clone())
+            .collect();
+
+        sets.sort();
+        sets
+    }
+
+    /// Object files with a `png`, `webp`, `jpg` or `jpeg` extension whose first bytes are of
+    /// a different format, with the detected format, sorted by path.
+    /// Only the header is checked, the images are not decoded.
+    pub fn find_extension_mismatches(&self) -> Vec<(String, &'static str)> {
+        let mut mismatches: Vec<(String, &'static str)> = self
+            .object_files
+            .iter()
+            .filter_map(|(path, data)| {
+                let extension = image_extension(path)?;
+                let expected = image_format(&extension);
+                let detected = detect_image_format(data).unwrap_or("unknown");
+
+                (detected != expected).then(|| (path.clone(), detected))
+            })
+            .collect();
+
+        mismatches.sort();
+        mismatches
+    }
+
+    /// Paths that contain spaces or non-ASCII characters, sorted.
+    pub fn find_non_portable_names(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self
+            .object_files
+            .keys()
+            .chain(self.other_files.keys())
+            .filter(|path| is_non_portable_name(path))
+            .cloned()
+            .collect();
+
+        paths.sort();
+        paths
+    }
+
+    /// Renames the files returned by `find_non_portable_names` using `portable_name`.
+    /// Files whose new name is already taken are left alone.
+    /// Returns the number of renamed files.
+    pub fn repair_names(&mut self) -> usize {
+        let mut renamed = 0;
+
+        for path in self.find_non_portable_names() {
+            let new_path = portable_name(&path);
+
+            match self.move_file(&path, &new_path) {
+                Ok(_) => {
+                    debug!("Renamed '{}' to '{}'.", path, new_path);
+                    renamed += 1;
+                }
+                Err(e) => warn!("Could not rename '{}': {}", path, e),
+            }
+        }
+
+        info!("Renamed {} files to portable names.", renamed);
+
+        renamed
+    }
+
+    /// Groups of file paths that are identical when ignoring ASCII case.
+    pub fn find_case_collisions(&self) -> Vec<Vec<String>> {
+        let mut paths_by_lowercase: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+        for path in self.object_files.keys().chain(self.other_files.keys()) {
+            paths_by_lowercase
+                .entry(path.to_ascii_lowercase())
+                .or_default()
+                .push(path.clone());
+        }
+
+        paths_by_lowercase
+            .into_values()
+            .filter(|paths| paths.len() > 1)
+            .map(|mut paths| {
+                paths.sort();
+                paths
+            })
+            .collect()
+    }
+}
+
+/// Recognizes the image formats Dungeondraft can use by their first bytes.
+fn detect_image_format(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        Some("png")
+    } else if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("jpeg")
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("webp")
+    } else {
+        None
+    }
+}
+
+/// Reads the width and height of a png or webp image from its header.
+fn image_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    match detect_image_format(data)? {
+        "png" if data.len() >= 24 => {
+            Some((BE::read_u32(&data[16..20]), BE::read_u32(&data[20..24])))
+        }
+        "webp" if data.len() >= 30 => match &data[12..16] {
+            b"VP8X" => Some((
+                LE::read_u24(&data[24..27]) + 1,
+                LE::read_u24(&data[27..30]) + 1,
+            )),
+            b"VP8 " => Some((
+                u32::from(LE::read_u16(&data[26..28]) & 0x3FFF),
+                u32::from(LE::read_u16(&data[28..30]) & 0x3FFF),
+            )),
+            b"VP8L" => {
+                let bits = LE::read_u32(&data[21..25]);
+                Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+    use crate::asset_pack::validation::{ValidationIssue, ValidationOptions};
+
+    #[test]
+    fn validate_case_collision() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.object_files
+            .insert("textures/objects/Rock.png".to_string(), vec![]);
+        pack.object_files
+            .insert("textures/objects/rock.png".to_string(), vec![]);
+
+        assert_eq!(
+            pack.validate(&Default::default()),
+            vec![ValidationIssue::CaseCollision(vec![
+                "textures/objects/Rock.png".to_string(),
+                "textures/objects/rock.png".to_string()
+            ])]
+        );
+    }
+
+    #[test]
+    fn validate_and_repair_non_portable_name() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.object_files
+            .insert("textures/objects/my rock.png".to_string(), vec![]);
+        pack.tags
+            .tags
+            .get_mut("MyTag")
+            .unwrap()
+            .insert("textures/objects/my rock.png".to_string());
+
+        let options = ValidationOptions {
+            check_portable_names: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            pack.validate(&options),
+            vec![ValidationIssue::NonPortableName(
+                "textures/objects/my rock.png".to_string()
+            )]
+        );
+
+        assert_eq!(pack.repair_names(), 1);
+
+        assert!(pack
+            .object_files
+            .contains_key("textures/objects/my_rock.png"));
+        assert!(!pack
+            .object_files
+            .contains_key("textures/objects/my rock.png"));
+        let tagged = pack.get_files_in_tag("MyTag").unwrap();
+        assert!(tagged.contains("textures/objects/my_rock.png"));
+        assert!(!tagged.contains("textures/objects/my rock.png"));
+        assert!(pack.validate(&options).is_empty());
+    }
+
+    #[test]
+    fn validate_confusing_tag_sets() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.tags
+            .sets
+            .get_mut("Example Set")
+            .unwrap()
+            .insert("Example Set".to_string());
+        pack.tags.sets.insert(
+            "MyTag".to_string(),
+            vec!["Colorable".to_string()].into_iter().collect(),
+        );
+
+        assert_eq!(
+            pack.validate(&Default::default()),
+            vec![
+                ValidationIssue::SetNamedLikeTag("MyTag".to_string()),
+                ValidationIssue::SetContainsItself("Example Set".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_unrecognized_location() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.other_files
+            .insert("README.txt".to_string(), b"Thanks!".to_vec());
+        pack.other_files.insert(
+            "data/walls/stone.dungeondraft_wall".to_string(),
+            b"{}".to_vec(),
+        );
+
+        assert_eq!(pack.unrecognized_files(), vec!["README.txt"]);
+        assert_eq!(
+            pack.validate(&Default::default()),
+            vec![ValidationIssue::UnrecognizedLocation(
+                "README.txt".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn validate_tag_with_many_files() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        let associations = pack.tag_file_associations();
+        let many_files = pack.tags.tags.entry("Many".to_string()).or_default();
+        for i in 0..4 {
+            many_files.insert(format!("textures/objects/rock_{}.png", i));
+        }
+
+        assert_eq!(pack.tag_file_associations(), associations + 4);
+
+        let options = ValidationOptions {
+            max_files_per_tag: Some(3),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            pack.validate(&options),
+            vec![ValidationIssue::TagHasManyFiles {
+                tag: "Many".to_string(),
+                files: 4
+            }]
+        );
+        assert!(pack.validate(&Default::default()).is_empty());
+    }
+
+    #[test]
+    fn validate_extension_mismatch() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.object_files.insert(
+            "textures/objects/photo.png".to_string(),
+            vec![0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F'],
+        );
+        pack.object_files.insert(
+            "textures/objects/real.png".to_string(),
+            vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0],
+        );
+        pack.object_files.insert(
+            "textures/objects/image.webp".to_string(),
+            b"RIFF\x10\0\0\0WEBPVP8 ".to_vec(),
+        );
+        // The test pack's own `random.png` is all zeroes.
+        pack.object_files.remove("textures/objects/random.png");
+
+        let options = ValidationOptions {
+            check_image_headers: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            pack.validate(&options),
+            vec![ValidationIssue::ExtensionMismatch {
+                path: "textures/objects/photo.png".to_string(),
+                detected: "jpeg".to_string()
+            }]
+        );
+        assert!(pack.validate(&Default::default()).is_empty());
+    }
+
+    #[test]
+    fn find_redundant_tags() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        let files = |paths: &[&str]| paths.iter().map(|path| path.to_string()).collect();
+        pack.tags.tags.insert(
+            "barrels".to_string(),
+            files(&["textures/objects/barrel.png", "textures/objects/keg.png"]),
+        );
+        pack.tags.tags.insert(
+            "Barrel".to_string(),
+            files(&["textures/objects/keg.png", "textures/objects/barrel.png"]),
+        );
+        pack.tags
+            .tags
+            .insert("Kegs".to_string(), files(&["textures/objects/keg.png"]));
+
+        assert_eq!(
+            pack.find_redundant_tags(),
+            vec![("Barrel".to_string(), "barrels".to_string())]
+        );
+        assert_eq!(
+            pack.find_redundant_tags_with(true),
+            vec![
+                ("Barrel".to_string(), "barrels".to_string()),
+                ("Kegs".to_string(), "Barrel".to_string()),
+                ("Kegs".to_string(), "barrels".to_string()),
+            ]
+        );
+
+        let options = ValidationOptions {
+            check_redundant_tags: true,
+            ..Default::default()
+        };
+        assert!(pack
+            .validate(&options)
+            .contains(&ValidationIssue::RedundantTag {
+                tag: "Barrel".to_string(),
+                other: "barrels".to_string(),
+                identical: true,
+            }));
+    }
+
+    #[test]
+    fn setless_tags_and_auto_set() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.object_files
+            .insert("textures/objects/barrel.png".to_string(), vec![1]);
+        pack.tags.tags.insert(
+            "Barrels".to_string(),
+            vec!["textures/objects/barrel.png".to_string()]
+                .into_iter()
+                .collect(),
+        );
+
+        let options = ValidationOptions {
+            check_sets: true,
+            ..Default::default()
+        };
+        let issues = pack.validate(&options);
+        // `MyTag` is in `Example Set`, `Colorable` and `Barrels` are in no set.
+        assert!(issues.contains(&ValidationIssue::TagNotInAnySet("Barrels".to_string())));
+        assert!(issues.contains(&ValidationIssue::TagNotInAnySet("Colorable".to_string())));
+        assert!(!issues.contains(&ValidationIssue::TagNotInAnySet("MyTag".to_string())));
+        assert!(issues.contains(&ValidationIssue::ObjectNotInAnySet(
+            "textures/objects/barrel.png".to_string()
+        )));
+        assert!(!issues.contains(&ValidationIssue::ObjectNotInAnySet(
+            "textures/objects/random.png".to_string()
+        )));
+
+        assert_eq!(pack.add_setless_tags_to_set("Misc"), 2);
+        assert_eq!(pack.sets_containing_tag("Barrels"), vec!["Misc"]);
+        assert!(pack.find_setless_tags().is_empty());
+        assert!(pack.validate(&options).is_empty());
+    }
+}
@@ -0,0 +1,40 @@
+/// Options influencing how `AssetPack::to_write_with_options` lays out the pack file.
+#[derive(Debug, Default, Clone)]
+pub struct WriteOptions {
+    /// Write the files in order of their path, instead of in arbitrary order.
+    /// Keeps similar files together, which helps when the pack is compressed afterwards,
+    /// and makes the output deterministic.
+    pub sort_files_by_path: bool,
+    /// Write the files in the order they had in the pack that was read, with new files
+    /// after them sorted by path, so re-packing an edited pack changes as few bytes as
+    /// possible. `sort_files_by_path` takes precedence.
+    pub preserve_file_order: bool,
+    /// Indent the embedded `pack.json` and tags file with tabs, the way Dungeondraft does,
+    /// instead of writing them on a single line.
+    pub pretty_json: bool,
+    /// Write the tags and tag sets sorted by name, each with its members sorted too,
+    /// so the tags file gives clean diffs in version control.
+    pub sort_tags: bool,
+    /// Store the md5 hash of every file in the file table, computed on this many threads.
+    /// Without it the hashes are left zeroed, which Dungeondraft accepts.
+    pub md5_threads: Option<usize>,
+    /// Start every file at a multiple of this many bytes, with zeroes in between,
+    /// the way Godot 4 aligns the files in its packs.
+    pub alignment: Option<usize>,
+    /// Store the tags in `pack.json` under `tags`, instead of in a separate tags file.
+    /// Only some nonstandard tools expect this, Dungeondraft does not read it.
+    pub embed_tags: bool,
+    /// Store a file only once when it has the same contents as the file before it, with both
+    /// entries pointing at the same bytes. This is the case for the two metadata files.
+    /// Godot finds every file by its offset, so this saves the size of the metadata
+    /// without changing what is read, but it is not what Dungeondraft writes.
+    pub share_metadata_offset: bool,
+    /// Refuse to write the pack if any tag references a file the pack does not have,
+    /// instead of writing the broken references. See `AssetPack::check_tag_references`.
+    pub strict_tags: bool,
+    /// Store the object and other files zlib-compressed, when that makes them smaller,
+    /// with `FileMetaData::FLAG_COMPRESSED` on those files. Only for packs with entry flags,
+    /// see `GodotVersion::has_entry_flags`, so not for the packs Dungeondraft writes.
+    /// This program decompresses them when reading, but Godot and Dungeondraft can't.
+    pub compress: bool,
+}
@@ -0,0 +1,400 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use anyhow::{bail, Context};
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use log::{debug, info};
+
+use crate::asset_pack::asset_pack::{
+    parse_meta_file, parse_tags_file, record_json_encoding, take_embedded_tags, AssetPack,
+};
+use crate::asset_pack::deflate::inflate;
+use crate::asset_pack::godot_version::GodotVersion;
+use crate::asset_pack::pack_layout::PackLayout;
+use crate::asset_pack::path_utils::*;
+use crate::asset_pack::utils::crc32;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const LOCAL_HEADER_SIZE: u64 = 30;
+const END_OF_CENTRAL_DIRECTORY_SIZE: u64 = 22;
+/// The end of central directory record can be followed by a comment of at most this size.
+const MAX_COMMENT_SIZE: u64 = u16::MAX as u64;
+/// Version 2.0, the first with directories and stored or deflated entries.
+const ZIP_VERSION: u16 = 20;
+/// The names are UTF-8.
+const FLAG_UTF8: u16 = 1 << 11;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+/// 1980-01-01, the earliest date a zip file can hold. Keeps the archive deterministic.
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+impl AssetPack {
+    /// Writes the pack as a zip archive with the same files as `unpack_to_dir` writes:
+    /// `pack.json`, the tags file and all other files at their relative paths.
+    /// The files are stored without compression, and in order of their path.
+    pub fn to_zip<W: Write>(&self, data: &mut W) -> anyhow::Result<()> {
+        let mut files: Vec<(&str, Vec<u8>)> = vec![
+            (PACK_FILE_NAME, json5::to_string(&self.meta)?.into_bytes()),
+            (TAGS_FILE_NAME, json5::to_string(&self.tags)?.into_bytes()),
+        ];
+        let mut other_files: Vec<(&str, Vec<u8>)> = self
+            .object_files
+            .iter()
+            .chain(self.other_files.iter())
+            .map(|(path, file_data)| (path.as_str(), file_data.clone()))
+            .collect();
+        other_files.sort();
+        files.extend(other_files);
+
+        if files.len() > u16::MAX as usize {
+            bail!("Too many files for a zip archive: {}", files.len());
+        }
+        if let Some((path, _)) = files
+            .iter()
+            .find(|(path, _)| path.len() > u16::MAX as usize)
+        {
+            bail!("The path is too long for a zip archive: '{}'", path);
+        }
+
+        let mut entries = vec![];
+        let mut offset: u64 = 0;
+
+        for (path, file_data) in files.iter() {
+            let entry = ZipEntry {
+                crc: crc32(file_data),
+                size: to_u32(file_data.len() as u64, path)?,
+                offset: to_u32(offset, path)?,
+            };
+
+            data.write_u32::<LE>(LOCAL_HEADER_SIGNATURE)?;
+            data.write_u16::<LE>(ZIP_VERSION)?;
+            entry.write_common_fields(data, path)?;
+            data.write_u16::<LE>(0)?;
+            data.write_all(path.as_bytes())?;
+            data.write_all(file_data)?;
+
+            offset += LOCAL_HEADER_SIZE + path.len() as u64 + file_data.len() as u64;
+            entries.push(entry);
+        }
+
+        let central_directory_offset = to_u32(offset, "the central directory")?;
+        let mut central_directory_size = 0;
+
+        for ((path, _), entry) in files.iter().zip(entries.iter()) {
+            data.write_u32::<LE>(CENTRAL_HEADER_SIGNATURE)?;
+            data.write_u16::<LE>(ZIP_VERSION)?;
+            data.write_u16::<LE>(ZIP_VERSION)?;
+            entry.write_common_fields(data, path)?;
+            // Extra field, comment, disk number, internal and external attributes.
+            data.write_u16::<LE>(0)?;
+            data.write_u16::<LE>(0)?;
+            data.write_u16::<LE>(0)?;
+            data.write_u16::<LE>(0)?;
+            data.write_u32::<LE>(0)?;
+            data.write_u32::<LE>(entry.offset)?;
+            data.write_all(path.as_bytes())?;
+
+            central_directory_size += 46 + path.len() as u32;
+        }
+
+        data.write_u32::<LE>(END_OF_CENTRAL_DIRECTORY_SIGNATURE)?;
+        // Number of this disk, and of the disk with the central directory.
+        data.write_u16::<LE>(0)?;
+        data.write_u16::<LE>(0)?;
+        data.write_u16::<LE>(files.len() as u16)?;
+        data.write_u16::<LE>(files.len() as u16)?;
+        data.write_u32::<LE>(central_directory_size)?;
+        data.write_u32::<LE>(central_directory_offset)?;
+        data.write_u16::<LE>(0)?;
+
+        info!("Wrote {} files to the zip archive.", files.len());
+
+        Ok(())
+    }
+
+    /// Reads a zip archive laid out like an asset directory, see `from_directory`.
+    /// The entries can be stored, as written by `to_zip`, or deflated, as most zip tools do.
+    pub fn from_zip<R: Read + Seek>(data: &mut R) -> anyhow::Result<Self> {
+        let mut files = read_zip_entries(data)?;
+
+        let mut json_encodings = BTreeMap::new();
+
+        let meta_data = files
+            .remove(PACK_FILE_NAME)
+            .context(format!("The archive does not contain '{}'", PACK_FILE_NAME))?;
+        record_json_encoding(&mut json_encodings, PACK_FILE_NAME, &meta_data);
+        let mut meta = parse_meta_file(meta_data)?;
+        let tags = match files.remove(TAGS_FILE_NAME) {
+            Some(tags_data) => {
+                record_json_encoding(&mut json_encodings, TAGS_FILE_NAME, &tags_data);
+                parse_tags_file(tags_data)?
+            }
+            None => take_embedded_tags(&mut meta).unwrap_or_default(),
+        };
+
+        let (object_files, other_files) = files
+            .into_iter()
+            .partition(|(path, _)| is_objects_file(path));
+
+        Ok(AssetPack {
+            godot_version: GodotVersion::dungeondraft_default(),
+            layout: PackLayout::default(),
+            meta,
+            tags,
+            object_files,
+            other_files,
+            stored_md5: HashMap::new(),
+            file_order: vec![],
+            trailer: vec![],
+            json_encodings,
+        })
+    }
+}
+
+/// The fields of an uncompressed file that the local and central headers share.
+struct ZipEntry {
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+impl ZipEntry {
+    /// From the flags up to and including the length of the file name.
+    fn write_common_fields<W: Write>(&self, data: &mut W, path: &str) -> anyhow::Result<()> {
+        data.write_u16::<LE>(FLAG_UTF8)?;
+        data.write_u16::<LE>(METHOD_STORED)?;
+        data.write_u16::<LE>(0)?;
+        data.write_u16::<LE>(DOS_DATE)?;
+        data.write_u32::<LE>(self.crc)?;
+        // Compressed and uncompressed size, the same because nothing is compressed.
+        data.write_u32::<LE>(self.size)?;
+        data.write_u32::<LE>(self.size)?;
+        data.write_u16::<LE>(path.len() as u16)?;
+
+        Ok(())
+    }
+}
+
+/// Reads all files in the archive, by path. Directory entries are skipped.
+fn read_zip_entries<R: Read + Seek>(data: &mut R) -> anyhow::Result<HashMap<String, Vec<u8>>> {
+    let (entry_count, central_directory_offset) = read_end_of_central_directory(data)?;
+
+    data.seek(SeekFrom::Start(central_directory_offset))?;
+
+    let mut entries = vec![];
+
+    for _ in 0..entry_count {
+        if data.read_u32::<LE>()? != CENTRAL_HEADER_SIGNATURE {
+            bail!("Corrupt zip archive, expected a central directory entry");
+        }
+        // Version made by, version needed and flags.
+        data.seek(SeekFrom::Current(6))?;
+        let method = data.read_u16::<LE>()?;
+        // Modification time and date.
+        data.seek(SeekFrom::Current(4))?;
+        let crc = data.read_u32::<LE>()?;
+        let compressed_size = data.read_u32::<LE>()?;
+        let size = data.read_u32::<LE>()?;
+        let name_length = data.read_u16::<LE>()?;
+        let extra_length = data.read_u16::<LE>()?;
+        let comment_length = data.read_u16::<LE>()?;
+        // Disk number, internal and external attributes.
+        data.seek(SeekFrom::Current(8))?;
+        let offset = data.read_u32::<LE>()?;
+
+        let mut name = vec![0; name_length as usize];
+        data.read_exact(&mut name)?;
+        let name = String::from_utf8(name)?;
+        data.seek(SeekFrom::Current(
+            extra_length as i64 + comment_length as i64,
+        ))?;
+
+        if name.ends_with('/') {
+            debug!("Skipping directory entry '{}'.", name);
+            continue;
+        }
+        if method != METHOD_STORED && method != METHOD_DEFLATED {
+            bail!(
+                "'{}' is compressed with method {}, only stored and deflated entries can be read",
+                name,
+                method
+            );
+        }
+
+        entries.push((name, method, crc, compressed_size, size, offset));
+    }
+
+    let mut files = HashMap::new();
+
+    for (name, method, crc, compressed_size, size, offset) in entries {
+        data.seek(SeekFrom::Start(offset as u64))?;
+        if data.read_u32::<LE>()? != LOCAL_HEADER_SIGNATURE {
+            bail!("Corrupt zip archive, expected the header of '{}'", name);
+        }
+        data.seek(SeekFrom::Start(offset as u64 + LOCAL_HEADER_SIZE - 4))?;
+        let name_length = data.read_u16::<LE>()?;
+        let extra_length = data.read_u16::<LE>()?;
+        data.seek(SeekFrom::Current(name_length as i64 + extra_length as i64))?;
+
+        let mut file_data = vec![];
+        data.take(compressed_size as u64)
+            .read_to_end(&mut file_data)
+            .context(format!("Could not read '{}'", name))?;
+        if file_data.len() != compressed_size as usize {
+            bail!("Corrupt zip archive, '{}' runs past its end", name);
+        }
+
+        if method == METHOD_DEFLATED {
+            file_data = inflate(&file_data).context(format!("Could not inflate '{}'", name))?;
+        }
+
+        if file_data.len() != size as usize {
+            bail!(
+                "'{}' is {} bytes, but the archive says it is {} bytes",
+                name,
+                file_data.len(),
+                size
+            );
+        }
+        if crc32(&file_data) != crc {
+            bail!("'{}' does not match its checksum", name);
+        }
+
+        files.insert(name, file_data);
+    }
+
+    Ok(files)
+}
+
+/// Finds the end of central directory record, and returns the number of entries
+/// and the offset of the central directory.
+fn read_end_of_central_directory<R: Read + Seek>(data: &mut R) -> anyhow::Result<(u16, u64)> {
+    let length = data.seek(SeekFrom::End(0))?;
+    let search_start = length.saturating_sub(END_OF_CENTRAL_DIRECTORY_SIZE + MAX_COMMENT_SIZE);
+
+    let mut tail = vec![];
+    data.seek(SeekFrom::Start(search_start))?;
+    data.read_to_end(&mut tail)?;
+
+    let signature = END_OF_CENTRAL_DIRECTORY_SIGNATURE.to_le_bytes();
+    let position = match tail
+        .windows(signature.len())
+        .rposition(|window| window == signature)
+    {
+        Some(position) => position,
+        None => bail!("Not a zip archive, it has no end of central directory record"),
+    };
+
+    // Skip the disk numbers and the number of entries on this disk.
+    data.seek(SeekFrom::Start(search_start + position as u64 + 10))?;
+    let entry_count = data.read_u16::<LE>()?;
+    let _central_directory_size = data.read_u32::<LE>()?;
+    let central_directory_offset = data.read_u32::<LE>()?;
+
+    Ok((entry_count, central_directory_offset as u64))
+}
+
+fn to_u32(value: u64, what: &str) -> anyhow::Result<u32> {
+    if value > u32::MAX as u64 {
+        bail!(
+            "Zip archives of more than 4 GiB are not supported, at '{}'",
+            what
+        );
+    }
+    Ok(value as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use crate::asset_pack::asset_pack::AssetPack;
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+    use crate::asset_pack::zip_archive::CENTRAL_HEADER_SIGNATURE;
+
+    #[test]
+    fn zip_round_trip() {
+        let pack = AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+
+        let mut archive = vec![];
+        pack.to_zip(&mut archive).unwrap();
+
+        let names = [
+            "pack.json",
+            "data/default.dungeondraft_tags",
+            "textures/objects/random.png",
+        ];
+        for name in names.iter() {
+            assert!(archive
+                .windows(name.len())
+                .any(|window| window == name.as_bytes()));
+        }
+
+        let imported_pack = AssetPack::from_zip(&mut Cursor::new(archive.clone())).unwrap();
+        assert_eq!(imported_pack.meta, pack.meta);
+        assert_eq!(imported_pack.tags, pack.tags);
+        assert_eq!(imported_pack.object_files, pack.object_files);
+        assert_eq!(imported_pack.other_files, pack.other_files);
+
+        // The contents of the last file, just before the central directory,
+        // no longer match its checksum.
+        let central_directory_start = archive
+            .windows(4)
+            .position(|window| window == CENTRAL_HEADER_SIGNATURE.to_le_bytes())
+            .unwrap();
+        archive[central_directory_start - 1] ^= 0xFF;
+        assert!(AssetPack::from_zip(&mut Cursor::new(archive)).is_err());
+
+        assert!(AssetPack::from_zip(&mut Cursor::new(vec![1, 2, 3])).is_err());
+    }
+
+    #[test]
+    fn zip_export_refuses_long_paths() {
+        let mut pack =
+            AssetPack::from_read(&mut Cursor::new(create_raw_test_pack().unwrap())).unwrap();
+        pack.other_files
+            .insert("a".repeat(u16::MAX as usize + 1), vec![1]);
+
+        let mut archive = vec![];
+        assert!(pack.to_zip(&mut archive).is_err());
+        assert!(archive.is_empty());
+    }
+
+    #[test]
+    fn zip_import_deflated_entries() {
+        let pack = AssetPack::from_zip(&mut Cursor::new(DEFLATED_ZIP.to_vec())).unwrap();
+
+        assert_eq!(pack.meta.id, "ZIPPED01");
+        assert_eq!(
+            pack.object_files["textures/objects/rock.png"],
+            b"rock".repeat(50)
+        );
+    }
+
+    /// `pack.json` and `textures/objects/rock.png`, deflated by Python's `zipfile` module.
+    const DEFLATED_ZIP: [u8; 306] = [
+        0x50, 0x4b, 0x03, 0x04, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x21, 0x00, 0xd2,
+        0xee, 0x29, 0x62, 0x37, 0x00, 0x00, 0x00, 0x3d, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00,
+        0x70, 0x61, 0x63, 0x6b, 0x2e, 0x6a, 0x73, 0x6f, 0x6e, 0xab, 0x56, 0xca, 0x4b, 0xcc, 0x4d,
+        0x55, 0xb2, 0x52, 0xaa, 0xca, 0x2c, 0x28, 0x48, 0x4d, 0x51, 0xd2, 0x51, 0xca, 0x4c, 0x01,
+        0xf2, 0xa2, 0x3c, 0x03, 0x02, 0x5c, 0x5d, 0x0c, 0x0c, 0x81, 0xfc, 0xb2, 0xd4, 0xa2, 0xe2,
+        0xcc, 0xfc, 0x3c, 0xa0, 0x20, 0x88, 0x97, 0x58, 0x5a, 0x92, 0x91, 0x5f, 0x04, 0xe4, 0x00,
+        0x35, 0xd5, 0x02, 0x00, 0x50, 0x4b, 0x03, 0x04, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00,
+        0x00, 0x21, 0x00, 0x06, 0x48, 0xa9, 0x71, 0x09, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x00, 0x00,
+        0x19, 0x00, 0x00, 0x00, 0x74, 0x65, 0x78, 0x74, 0x75, 0x72, 0x65, 0x73, 0x2f, 0x6f, 0x62,
+        0x6a, 0x65, 0x63, 0x74, 0x73, 0x2f, 0x72, 0x6f, 0x63, 0x6b, 0x2e, 0x70, 0x6e, 0x67, 0x2b,
+        0xca, 0x4f, 0xce, 0x2e, 0x1a, 0x06, 0x18, 0x00, 0x50, 0x4b, 0x01, 0x02, 0x14, 0x03, 0x14,
+        0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x21, 0x00, 0xd2, 0xee, 0x29, 0x62, 0x37, 0x00,
+        0x00, 0x00, 0x3d, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x80, 0x01, 0x00, 0x00, 0x00, 0x00, 0x70, 0x61, 0x63, 0x6b, 0x2e, 0x6a,
+        0x73, 0x6f, 0x6e, 0x50, 0x4b, 0x01, 0x02, 0x14, 0x03, 0x14, 0x00, 0x00, 0x00, 0x08, 0x00,
+        0x00, 0x00, 0x21, 0x00, 0x06, 0x48, 0xa9, 0x71, 0x09, 0x00, 0x00, 0x00, 0xc8, 0x00, 0x00,
+        0x00, 0x19, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x01,
+        0x5e, 0x00, 0x00, 0x00, 0x74, 0x65, 0x78, 0x74, 0x75, 0x72, 0x65, 0x73, 0x2f, 0x6f, 0x62,
+        0x6a, 0x65, 0x63, 0x74, 0x73, 0x2f, 0x72, 0x6f, 0x63, 0x6b, 0x2e, 0x70, 0x6e, 0x67, 0x50,
+        0x4b, 0x05, 0x06, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x02, 0x00, 0x7e, 0x00, 0x00, 0x00,
+        0x9e, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+}
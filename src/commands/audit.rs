@@ -0,0 +1,94 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::{
+    duplicate_pack_ids, library_counts, prometheus_metrics, scan_packs,
+};
+use log::warn;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use crate::{buffer_size_or_exit, find_packs, input_dir_valid_or_exit, threads_or_exit};
+
+pub const NAME: &str = "audit";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Lists the packs in a directory, and reports packs that share the same id.")
+        .arg(
+            Arg::with_name("INPUT_DIR")
+                .help("Input directory, will scan recursively for `*.dungeondraft_pack` files")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .takes_value(true)
+                .default_value("4")
+                .help("How many packs to read at the same time"),
+        )
+        .arg(
+            Arg::with_name("stats_format")
+                .long("stats-format")
+                .takes_value(true)
+                .possible_values(&["text", "prometheus"])
+                .default_value("text")
+                .help(
+                    "How to print the pack statistics. `prometheus` prints metrics \
+                    for the Prometheus textfile collector",
+                ),
+        )
+        .arg(Arg::with_name("count_only").long("count-only").help(
+            "Only print the number of packs, objects and files and their total size, \
+                    for a quick census of a large library",
+        ))
+}
+
+pub fn run(matches: &ArgMatches) {
+    let input_dir = PathBuf::from(matches.value_of("INPUT_DIR").unwrap());
+    input_dir_valid_or_exit(&input_dir);
+
+    let threads = threads_or_exit(matches);
+
+    let buffer_size = buffer_size_or_exit(matches);
+
+    let packs = find_packs(&input_dir);
+    let summaries = scan_packs(&packs, threads, |path| {
+        Ok(BufReader::with_capacity(buffer_size, File::open(path)?))
+    });
+
+    if matches.is_present("count_only") {
+        println!("{}", library_counts(&summaries));
+        return;
+    }
+
+    let prometheus = matches.value_of("stats_format") == Some("prometheus");
+
+    if prometheus {
+        print!("{}", prometheus_metrics(&summaries));
+    } else {
+        for summary in summaries.iter() {
+            println!(
+                "{}: '{}' ({}) by {}, {} objects, {} files, {} bytes",
+                summary.path.display(),
+                summary.meta.name,
+                summary.meta.id,
+                summary.meta.author,
+                summary.object_files.len(),
+                summary.file_count(),
+                summary.total_size()
+            );
+        }
+    }
+
+    for (id, paths) in duplicate_pack_ids(&summaries) {
+        warn!("Pack id '{}' is used by multiple packs:", id);
+        for path in paths {
+            warn!("    {}", path.display());
+        }
+    }
+
+    if !prometheus {
+        println!("{} packs read", summaries.len());
+    }
+}
@@ -0,0 +1,46 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use log::error;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::read_pack;
+
+pub const NAME: &str = "changelog";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about(
+            "Prints what changed between two versions of an asset pack as a Markdown list, \
+            for release notes.",
+        )
+        .arg(
+            Arg::with_name("OLD_PACK")
+                .help("The previous version of the `*.dungeondraft_pack` file")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("NEW_PACK")
+                .help("The new version of the `*.dungeondraft_pack` file")
+                .required(true)
+                .index(2),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let mut packs = vec![];
+
+    for name in ["OLD_PACK", "NEW_PACK"].iter() {
+        let pack_path = PathBuf::from(matches.value_of(name).unwrap());
+
+        match read_pack(&pack_path, matches) {
+            Ok(pack) => packs.push(pack),
+            Err(e) => {
+                error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+                exit(1);
+            }
+        }
+    }
+
+    print!("{}", packs[0].diff(&packs[1]).to_markdown());
+}
@@ -0,0 +1,116 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::Manifest;
+use log::{error, info};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use crate::read_pack;
+
+pub const NAME: &str = "check-manifest";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about(
+            "Checks an asset pack against a baseline manifest with the size and md5 hash \
+            of every file. Exits with an error if any file is missing, unexpected or different.",
+        )
+        .arg(
+            Arg::with_name("PACK")
+                .help("The `*.dungeondraft_pack` file to check")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("MANIFEST")
+                .help("The baseline manifest json file")
+                .required(true)
+                .index(2),
+        )
+        .arg(Arg::with_name("write").long("write").help(
+            "Write the manifest of the pack to MANIFEST as the new baseline, \
+                instead of checking against it",
+        ))
+}
+
+pub fn run(matches: &ArgMatches) {
+    let pack_path = PathBuf::from(matches.value_of("PACK").unwrap());
+    let manifest_path = PathBuf::from(matches.value_of("MANIFEST").unwrap());
+
+    let manifest = match read_pack(&pack_path, matches) {
+        Ok(pack) => pack.manifest(),
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    if matches.is_present("write") {
+        write_manifest(&manifest, &manifest_path, matches.is_present("dry_run"));
+        return;
+    }
+
+    let baseline = fs::read_to_string(&manifest_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| Manifest::from_json(&json));
+
+    let baseline = match baseline {
+        Ok(baseline) => baseline,
+        Err(e) => {
+            error!(
+                "Could not read manifest '{}':\n{}",
+                manifest_path.display(),
+                e
+            );
+            exit(1);
+        }
+    };
+
+    let mismatches = manifest.check(&baseline);
+
+    for mismatch in mismatches.iter() {
+        println!("{}", mismatch);
+    }
+
+    if mismatches.is_empty() {
+        println!(
+            "'{}' matches the {} files in the manifest",
+            pack_path.display(),
+            baseline.files.len()
+        );
+    } else {
+        error!(
+            "'{}' does not match the manifest: {} differences",
+            pack_path.display(),
+            mismatches.len()
+        );
+        exit(1);
+    }
+}
+
+fn write_manifest(manifest: &Manifest, path: &Path, dry_run: bool) {
+    if dry_run {
+        println!(
+            "Would write a manifest of {} files to '{}'",
+            manifest.files.len(),
+            path.display()
+        );
+        return;
+    }
+
+    let result = manifest
+        .to_json()
+        .and_then(|json| Ok(fs::write(path, json)?));
+
+    match result {
+        Ok(()) => info!(
+            "Wrote a manifest of {} files to '{}'",
+            manifest.files.len(),
+            path.display()
+        ),
+        Err(e) => {
+            error!("Could not write manifest '{}':\n{}", path.display(), e);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use log::{error, info};
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::read_pack;
+
+pub const NAME: &str = "export-coverage";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about(
+            "Exports a grid of the folders under `textures/objects/` against the tags, \
+            with how many objects in each folder carry each tag.",
+        )
+        .arg(
+            Arg::with_name("PACK")
+                .help("The `*.dungeondraft_pack` file to read")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["csv", "json"])
+                .default_value("csv")
+                .help("How to write the grid"),
+        )
+        .arg(
+            Arg::with_name("out")
+                .long("out")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Write the grid here instead of to stdout"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let pack_path = PathBuf::from(matches.value_of("PACK").unwrap());
+
+    let pack = match read_pack(&pack_path, matches) {
+        Ok(pack) => pack,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    let coverage = pack.tag_coverage();
+
+    let grid = if matches.value_of("format") == Some("json") {
+        match coverage.to_json() {
+            Ok(json) => json + "\n",
+            Err(e) => {
+                error!("Could not serialize the coverage:\n{}", e);
+                exit(1);
+            }
+        }
+    } else {
+        coverage.to_csv()
+    };
+
+    let out = match matches.value_of("out") {
+        Some(out) => out,
+        None => {
+            print!("{}", grid);
+            return;
+        }
+    };
+
+    if matches.is_present("dry_run") {
+        println!(
+            "Would write a {}x{} grid to '{}'",
+            coverage.folders.len(),
+            coverage.tags.len(),
+            out
+        );
+        return;
+    }
+
+    match fs::write(out, grid) {
+        Ok(()) => info!(
+            "Wrote a {}x{} grid to '{}'",
+            coverage.folders.len(),
+            coverage.tags.len(),
+            out
+        ),
+        Err(e) => {
+            error!("Could not write '{}':\n{}", out, e);
+            exit(1);
+        }
+    }
+}
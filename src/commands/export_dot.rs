@@ -0,0 +1,65 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::AssetPack;
+use log::{error, info};
+use std::fs;
+use std::process::exit;
+
+use crate::pack_input::{open_pack_or_exit, pack_input_args};
+
+pub const NAME: &str = "export-dot";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    pack_input_args(SubCommand::with_name(NAME).about(
+        "Exports the tag sets and tags of an asset pack as a Graphviz DOT graph, \
+            with an edge from each set to its tags.",
+    ))
+    .arg(
+        Arg::with_name("objects")
+            .long("objects")
+            .help("Also add the object files, with an edge from each tag to its files"),
+    )
+    .arg(
+        Arg::with_name("out")
+            .long("out")
+            .takes_value(true)
+            .value_name("FILE")
+            .help("Write the graph here instead of to stdout"),
+    )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let (pack_name, mut reader) = open_pack_or_exit(matches);
+
+    let summary = match AssetPack::read_metadata_only(&mut reader) {
+        Ok(summary) => summary,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_name, e);
+            exit(1);
+        }
+    };
+
+    let dot = summary
+        .tags
+        .to_dot(&summary.meta.name, matches.is_present("objects"));
+
+    let out = match matches.value_of("out") {
+        Some(out) => out,
+        None => {
+            print!("{}", dot);
+            return;
+        }
+    };
+
+    if matches.is_present("dry_run") {
+        println!("Would write {} bytes to '{}'", dot.len(), out);
+        return;
+    }
+
+    match fs::write(out, dot) {
+        Ok(()) => info!("Wrote the graph to '{}'", out),
+        Err(e) => {
+            error!("Could not write '{}':\n{}", out, e);
+            exit(1);
+        }
+    }
+}
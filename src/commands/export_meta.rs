@@ -0,0 +1,70 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::AssetPack;
+use log::error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::buffer_size_or_exit;
+
+pub const NAME: &str = "export-meta";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about(
+            "Writes only the `pack.json` and tags file of an asset pack into a directory, \
+            without any of the textures.",
+        )
+        .arg(
+            Arg::with_name("PACK")
+                .help("The `*.dungeondraft_pack` file to read")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("OUTPUT_DIR")
+                .help("The files will be written into this directory")
+                .required(true)
+                .index(2),
+        )
+        .arg(
+            Arg::with_name("force_overwrite")
+                .short("F")
+                .help("Overwrite existing output files"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let pack_path = PathBuf::from(matches.value_of("PACK").unwrap());
+    let output_dir = PathBuf::from(matches.value_of("OUTPUT_DIR").unwrap());
+
+    let summary = File::open(&pack_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| {
+            AssetPack::read_metadata_only(&mut BufReader::with_capacity(
+                buffer_size_or_exit(matches),
+                file,
+            ))
+        });
+
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    match summary.export_meta(&output_dir, matches.is_present("force_overwrite")) {
+        Ok(written) => println!("Wrote {} files", written),
+        Err(e) => {
+            error!(
+                "Something went wrong while writing to '{}':\n{}",
+                output_dir.display(),
+                e
+            );
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::AssetPack;
+use log::error;
+use std::fs;
+use std::fs::File;
+use std::io::{self, BufReader, Write};
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::buffer_size_or_exit;
+
+pub const NAME: &str = "extract-file";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about(
+            "Writes the contents of a single file in an asset pack to stdout, \
+            without reading the rest of the pack.",
+        )
+        .arg(
+            Arg::with_name("PACK")
+                .help("The `*.dungeondraft_pack` file to read")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("PATH")
+                .help("Path of the file inside the pack, e.g. `textures/objects/rock.png`")
+                .required(true)
+                .index(2),
+        )
+        .arg(
+            Arg::with_name("out")
+                .long("out")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Write the file here instead of to stdout"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let pack_path = PathBuf::from(matches.value_of("PACK").unwrap());
+    let path = matches.value_of("PATH").unwrap();
+
+    let file_data = File::open(&pack_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| {
+            AssetPack::read_single_file(
+                &mut BufReader::with_capacity(buffer_size_or_exit(matches), file),
+                path,
+            )
+        });
+
+    let file_data = match file_data {
+        Ok(Some(file_data)) => file_data,
+        Ok(None) => {
+            error!(
+                "Packfile '{}' does not contain '{}'.",
+                pack_path.display(),
+                path
+            );
+            exit(1);
+        }
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    let result = match matches.value_of("out") {
+        Some(out) => fs::write(out, &file_data),
+        None => io::stdout().lock().write_all(&file_data),
+    };
+
+    if let Err(e) = result {
+        error!("Could not write '{}':\n{}", path, e);
+        exit(1);
+    }
+}
@@ -0,0 +1,59 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::AssetPack;
+use log::error;
+use std::io::SeekFrom;
+use std::process::exit;
+
+use crate::pack_input::{open_pack_or_exit, pack_input_args};
+
+pub const NAME: &str = "info";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    pack_input_args(
+        SubCommand::with_name(NAME)
+            .about("Shows the metadata and the godot version of an asset pack."),
+    )
+    .arg(
+        Arg::with_name("version_only")
+            .long("version-only")
+            .help("Only read and show the godot version and pack format, which is the fastest"),
+    )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let (pack_name, mut reader) = open_pack_or_exit(matches);
+
+    let (godot_version, format_version) = match AssetPack::read_version(&mut reader) {
+        Ok(version) => version,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_name, e);
+            exit(1);
+        }
+    };
+
+    println!("Godot version: {}", godot_version);
+    println!("Pack format: {}", format_version);
+
+    if matches.is_present("version_only") {
+        return;
+    }
+
+    let summary = reader
+        .seek(SeekFrom::Start(0))
+        .map_err(anyhow::Error::from)
+        .and_then(|_| AssetPack::read_metadata_only(&mut reader));
+
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_name, e);
+            exit(1);
+        }
+    };
+
+    println!("Name: {}", summary.meta.name);
+    println!("Id: {}", summary.meta.id);
+    println!("Version: {}", summary.meta.version);
+    println!("Author: {}", summary.meta.author);
+    println!("Files: {}", summary.file_count());
+}
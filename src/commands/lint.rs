@@ -0,0 +1,139 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::{
+    check_exists, Severity, ValidationOptions, DEFAULT_MAX_TEXTURE_SIZE,
+};
+use log::error;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::read_pack;
+
+pub const NAME: &str = "lint";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about(
+            "Runs all checks on an asset pack and lists the problems by severity. \
+            Exits with an error code if any errors are found.",
+        )
+        .arg(
+            Arg::with_name("PACK")
+                .help("The `*.dungeondraft_pack` file to check")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("severity")
+                .long("severity")
+                .value_name("CHECK=LEVEL")
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Change the severity of a check to error, warning or info, \
+                    e.g. `untagged-objects=error`. Can be given multiple times",
+                ),
+        )
+        .arg(
+            Arg::with_name("max_files_per_tag")
+                .long("max-files-per-tag")
+                .value_name("COUNT")
+                .default_value("5000")
+                .help("Report tags that reference more files than this"),
+        )
+        .arg(
+            Arg::with_name("max_texture_size")
+                .long("max-texture-size")
+                .value_name("PIXELS")
+                .help("Report textures wider or higher than this [default: 4096]"),
+        )
+        .arg(
+            Arg::with_name("redundant_tag_subsets")
+                .long("redundant-tag-subsets")
+                .help("Also report tags whose files are all in another tag, not only tags with the same files"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let pack_path = PathBuf::from(matches.value_of("PACK").unwrap());
+
+    let pack = match read_pack(&pack_path, matches) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    let max_files_per_tag = match matches.value_of("max_files_per_tag").unwrap().parse() {
+        Ok(count) => count,
+        Err(_) => {
+            error!("The maximum number of files per tag should be a number.");
+            exit(1);
+        }
+    };
+
+    let max_texture_size = match matches
+        .value_of("max_texture_size")
+        .map_or(Ok(DEFAULT_MAX_TEXTURE_SIZE), str::parse)
+    {
+        Ok(size) => size,
+        Err(_) => {
+            error!("The maximum texture size should be a number of pixels.");
+            exit(1);
+        }
+    };
+
+    let options = ValidationOptions {
+        max_files_per_tag: Some(max_files_per_tag),
+        max_texture_size: Some(max_texture_size),
+        redundant_tag_subsets: matches.is_present("redundant_tag_subsets"),
+        ..Default::default()
+    };
+
+    let issues = pack.lint(&options, &severities_or_exit(matches));
+
+    for (severity, issues) in issues.iter() {
+        println!("{} ({}):", severity, issues.len());
+        for issue in issues {
+            println!("    [{}] {}", issue.check(), issue);
+        }
+    }
+
+    let count = |severity| issues.get(&severity).map_or(0, Vec::len);
+    println!(
+        "{} errors, {} warnings, {} info",
+        count(Severity::Error),
+        count(Severity::Warning),
+        count(Severity::Info)
+    );
+
+    if issues.contains_key(&Severity::Error) {
+        exit(1);
+    }
+}
+
+fn severities_or_exit(matches: &ArgMatches) -> HashMap<String, Severity> {
+    let mut severities = HashMap::new();
+
+    for value in matches.values_of("severity").into_iter().flatten() {
+        let parsed = match value.split_once('=') {
+            Some((check, level)) => check_exists(check)
+                .and_then(|_| level.parse::<Severity>())
+                .map(|severity| (check.to_owned(), severity)),
+            None => Err(anyhow::anyhow!("expected CHECK=LEVEL")),
+        };
+
+        match parsed {
+            Ok((check, severity)) => {
+                severities.insert(check, severity);
+            }
+            Err(e) => {
+                error!("Invalid severity '{}': {}", value, e);
+                exit(1);
+            }
+        }
+    }
+
+    severities
+}
@@ -0,0 +1,104 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::{file_entries_to_json, file_entries_to_json_lines, AssetPack};
+use log::error;
+use std::process::exit;
+
+use crate::pack_input::{open_pack_or_exit, pack_input_args};
+
+pub const NAME: &str = "list";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    pack_input_args(
+        SubCommand::with_name(NAME)
+            .about("Lists the files of an asset pack with their size and tags."),
+    )
+    .arg(
+        Arg::with_name("json")
+            .long("json")
+            .help("Print the files as a json array"),
+    )
+    .arg(
+        Arg::with_name("json_lines")
+            .long("json-lines")
+            .conflicts_with("json")
+            .help(
+                "Print one json object per file per line, \
+                which is easier to stream than one big array",
+            ),
+    )
+    .arg(
+        Arg::with_name("limit")
+            .long("limit")
+            .takes_value(true)
+            .value_name("N")
+            .help("List at most N files"),
+    )
+    .arg(
+        Arg::with_name("offset")
+            .long("offset")
+            .takes_value(true)
+            .value_name("M")
+            .default_value("0")
+            .help(
+                "Skip the first M files. The files are sorted by path, \
+                so they can be paged through with `--limit`",
+            ),
+    )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let (pack_name, mut reader) = open_pack_or_exit(matches);
+    let summary = AssetPack::read_metadata_only(&mut reader);
+
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_name, e);
+            exit(1);
+        }
+    };
+
+    let limit = matches
+        .value_of("limit")
+        .map(|limit| number_or_exit("limit", limit));
+    let offset = number_or_exit("offset", matches.value_of("offset").unwrap());
+    let entries = summary.file_entries_page(offset, limit);
+
+    let json = if matches.is_present("json_lines") {
+        file_entries_to_json_lines(&entries)
+    } else if matches.is_present("json") {
+        file_entries_to_json(&entries).map(|json| json + "\n")
+    } else {
+        for entry in entries {
+            if entry.tags.is_empty() {
+                println!("{} ({} bytes)", entry.path, entry.size);
+            } else {
+                println!(
+                    "{} ({} bytes): {}",
+                    entry.path,
+                    entry.size,
+                    entry.tags.join(", ")
+                );
+            }
+        }
+        return;
+    };
+
+    match json {
+        Ok(json) => print!("{}", json),
+        Err(e) => {
+            error!("Could not serialize the file list:\n{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn number_or_exit(name: &str, value: &str) -> usize {
+    match value.parse::<usize>() {
+        Ok(number) => number,
+        Err(_) => {
+            error!("The {} should be a number, not '{}'.", name, value);
+            exit(1);
+        }
+    }
+}
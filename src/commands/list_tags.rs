@@ -0,0 +1,47 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::AssetPack;
+use log::error;
+use std::process::exit;
+
+use crate::pack_input::{open_pack_or_exit, pack_input_args};
+
+pub const NAME: &str = "list-tags";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    pack_input_args(SubCommand::with_name(NAME).about(
+        "Lists the tags of an asset pack with their number of files, \
+            and the tag sets with their number of tags.",
+    ))
+    .arg(
+        Arg::with_name("json")
+            .long("json")
+            .help("Print the counts as json"),
+    )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let (pack_name, mut reader) = open_pack_or_exit(matches);
+    let summary = AssetPack::read_metadata_only(&mut reader);
+
+    let summary = match summary {
+        Ok(summary) => summary,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_name, e);
+            exit(1);
+        }
+    };
+
+    let counts = summary.tag_counts();
+
+    if matches.is_present("json") {
+        match counts.to_json() {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                error!("Could not serialize the tag counts:\n{}", e);
+                exit(1);
+            }
+        }
+    } else {
+        print!("{}", counts);
+    }
+}
@@ -0,0 +1,131 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::{merge_packs, merge_report, AssetPack, WriteOptions};
+use log::error;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::overwrite::Overwrite;
+use crate::{buffer_size_or_exit, read_pack, write_pack};
+
+pub const NAME: &str = "merge";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about(
+            "Merges asset packs into one, with the metadata of the first pack.\n\
+        A file that is in multiple packs is taken from the first pack that has it.",
+        )
+        .arg(
+            Arg::with_name("PACKS")
+                .help("The `*.dungeondraft_pack` files to merge")
+                .required(true)
+                .multiple(true)
+                .min_values(2)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .short("o")
+                .value_name("OUTPUT_FILE")
+                .required_unless("dry_run")
+                .help("The `*.dungeondraft_pack` file to create"),
+        )
+        .arg(
+            Arg::with_name("tag_prefix")
+                .long("tag-prefix")
+                .value_name("PREFIX")
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Put this in front of the tag names of a pack before merging, \
+                so tags with the same name don't get combined.\n\
+                Give it once for each pack, in the same order. Use \"\" to leave a pack alone.",
+                ),
+        )
+        .arg(
+            Arg::with_name("force_overwrite")
+                .short("F")
+                .help("Overwrite the output file if it exists"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let buffer_size = buffer_size_or_exit(matches);
+
+    let mut packs = vec![];
+
+    for pack_path in matches.values_of("PACKS").unwrap().map(PathBuf::from) {
+        match read_pack(&pack_path, matches) {
+            Ok(pack) => packs.push(pack),
+            Err(e) => {
+                error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+                exit(1);
+            }
+        }
+    }
+
+    if let Some(prefixes) = matches.values_of("tag_prefix") {
+        let prefixes: Vec<&str> = prefixes.collect();
+
+        if prefixes.len() != packs.len() {
+            error!(
+                "Got {} tag prefixes for {} packs, give one for each pack.",
+                prefixes.len(),
+                packs.len()
+            );
+            exit(1);
+        }
+
+        for (pack, prefix) in packs.iter_mut().zip(prefixes) {
+            if !prefix.is_empty() {
+                pack.prefix_tags(prefix);
+            }
+        }
+    }
+
+    let merged = match merge_unless_dry_run(packs, matches.is_present("dry_run")) {
+        Some(merged) => merged,
+        None => return,
+    };
+
+    let output_path = PathBuf::from(matches.value_of("output").unwrap());
+
+    let mut overwrite = if matches.is_present("force_overwrite") {
+        Overwrite::Always
+    } else {
+        Overwrite::Never
+    };
+
+    write_pack(
+        &merged,
+        &output_path,
+        &WriteOptions::default(),
+        buffer_size,
+        &mut overwrite,
+        false,
+    );
+}
+
+/// Merges the packs, or on a dry run only prints the collisions and returns `None`.
+pub fn merge_unless_dry_run(packs: Vec<AssetPack>, dry_run: bool) -> Option<AssetPack> {
+    if dry_run {
+        let report = merge_report(&packs);
+
+        if report.is_empty() {
+            println!("No collisions found");
+        } else {
+            print!("{}", report);
+        }
+
+        return None;
+    }
+
+    match merge_packs(packs) {
+        Ok(merged) => Some(merged),
+        Err(e) => {
+            error!("Could not merge packs:\n{}", e);
+            exit(1);
+        }
+    }
+}
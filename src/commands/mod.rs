@@ -0,0 +1,23 @@
+pub mod audit;
+pub mod changelog;
+pub mod check_manifest;
+pub mod export_coverage;
+pub mod export_dot;
+pub mod export_meta;
+pub mod extract_file;
+pub mod info;
+pub mod lint;
+pub mod list;
+pub mod list_tags;
+pub mod merge;
+pub mod pack;
+pub mod reorganize;
+pub mod sample;
+pub mod schema;
+pub mod search;
+pub mod split;
+pub mod stats;
+pub mod tags;
+pub mod unpack;
+pub mod validate;
+pub mod zip;
@@ -0,0 +1,101 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::{
+    generate_pack_id_from_seed, AssetPack, DirectoryOptions, WriteOptions,
+};
+use log::{error, info};
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::overwrite::Overwrite;
+use crate::{buffer_size_or_exit, input_dir_valid_or_exit, write_pack};
+
+pub const NAME: &str = "pack";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Builds an asset pack from a directory containing a `pack.json`.")
+        .arg(
+            Arg::with_name("INPUT_DIR")
+                .help("The asset directory, as created by the `unpack` command")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("OUTPUT_FILE")
+                .help(
+                    "The `*.dungeondraft_pack` file to create. If this is a directory, \
+                    the file is named after the pack",
+                )
+                .required(true)
+                .index(2),
+        )
+        .arg(
+            Arg::with_name("force_overwrite")
+                .short("F")
+                .help("Overwrite the output file if it exists"),
+        )
+        .arg(
+            Arg::with_name("record_file_times")
+                .long("record-file-times")
+                .help(
+                    "Store the modification times of the files in the pack, \
+                    so they are restored when it is unpacked",
+                ),
+        )
+        .arg(
+            Arg::with_name("id_seed")
+                .long("id-seed")
+                .value_name("STR")
+                .help(
+                    "Replace the pack id with one derived from this text, \
+                    so the same text always gives the same id",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let input_dir = PathBuf::from(matches.value_of("INPUT_DIR").unwrap());
+    input_dir_valid_or_exit(&input_dir);
+
+    let mut output_path = PathBuf::from(matches.value_of("OUTPUT_FILE").unwrap());
+
+    let mut overwrite = if matches.is_present("force_overwrite") {
+        Overwrite::Always
+    } else {
+        Overwrite::Never
+    };
+
+    let options = DirectoryOptions {
+        record_file_times: matches.is_present("record_file_times"),
+    };
+
+    let mut pack = match AssetPack::from_directory_with_options(&input_dir, &options) {
+        Ok(pack) => pack,
+        Err(e) => {
+            error!(
+                "Could not read asset directory '{}':\n{}",
+                input_dir.display(),
+                e
+            );
+            exit(1);
+        }
+    };
+
+    if let Some(seed) = matches.value_of("id_seed") {
+        pack.meta.id = generate_pack_id_from_seed(seed);
+        info!("Using pack id '{}'", pack.meta.id);
+    }
+
+    if output_path.is_dir() {
+        output_path.push(pack.suggested_filename());
+    }
+
+    write_pack(
+        &pack,
+        &output_path,
+        &WriteOptions::default(),
+        buffer_size_or_exit(matches),
+        &mut overwrite,
+        matches.is_present("dry_run"),
+    );
+}
@@ -0,0 +1,88 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::WriteOptions;
+use log::error;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::overwrite::Overwrite;
+use crate::{buffer_size_or_exit, read_pack, write_pack};
+
+pub const NAME: &str = "reorganize";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Moves the object files of an asset pack into a different directory structure.")
+        .arg(
+            Arg::with_name("PACK")
+                .help("The `*.dungeondraft_pack` file to reorganize")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("OUTPUT_FILE")
+                .help("Where to write the reorganized pack")
+                .required(true)
+                .index(2),
+        )
+        .arg(
+            Arg::with_name("flatten")
+                .long("flatten")
+                .required_unless("group_by_tag")
+                .help(
+                    "Move all objects directly into `textures/objects/`.\n\
+                Files with the same name get a number appended.",
+                ),
+        )
+        .arg(
+            Arg::with_name("group_by_tag")
+                .long("group-by-tag")
+                .conflicts_with("flatten")
+                .help(
+                    "Move each object into `textures/objects/<tag>/`, \
+                using the first of its tags alphabetically.\n\
+                Objects without tags are left where they are.",
+                ),
+        )
+        .arg(
+            Arg::with_name("force_overwrite")
+                .short("F")
+                .help("Overwrite the output file if it exists"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let pack_path = PathBuf::from(matches.value_of("PACK").unwrap());
+    let output_path = PathBuf::from(matches.value_of("OUTPUT_FILE").unwrap());
+    let buffer_size = buffer_size_or_exit(matches);
+
+    let mut pack = match read_pack(&pack_path, matches) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    let moved = if matches.is_present("flatten") {
+        pack.flatten_objects()
+    } else {
+        pack.group_objects_by_tag()
+    };
+
+    println!("Moved {} object files", moved);
+
+    let mut overwrite = if matches.is_present("force_overwrite") {
+        Overwrite::Always
+    } else {
+        Overwrite::Never
+    };
+
+    write_pack(
+        &pack,
+        &output_path,
+        &WriteOptions::default(),
+        buffer_size,
+        &mut overwrite,
+        matches.is_present("dry_run"),
+    );
+}
@@ -0,0 +1,120 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::AssetPack;
+use log::error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process::exit;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::buffer_size_or_exit;
+
+pub const NAME: &str = "sample";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about(
+            "Extracts a random sample of the object files of an asset pack into a directory, \
+            as a quick check of a large pack.",
+        )
+        .arg(
+            Arg::with_name("PACK")
+                .help("The `*.dungeondraft_pack` file to sample")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("OUTPUT_DIR")
+                .help("The sampled files will be extracted into this directory")
+                .required(true)
+                .index(2),
+        )
+        .arg(
+            Arg::with_name("count")
+                .long("count")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("10")
+                .help("How many object files to extract"),
+        )
+        .arg(Arg::with_name("seed").long("seed").takes_value(true).help(
+            "Pick the files with this seed. The same seed always picks the same files.\n\
+                Without it a seed is chosen and printed, so the sample can be reproduced.",
+        ))
+        .arg(
+            Arg::with_name("force_overwrite")
+                .short("F")
+                .help("Overwrite existing output files"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let pack_path = PathBuf::from(matches.value_of("PACK").unwrap());
+    let output_dir = PathBuf::from(matches.value_of("OUTPUT_DIR").unwrap());
+
+    let count = number_or_exit("count", matches.value_of("count").unwrap());
+    let seed = match matches.value_of("seed") {
+        Some(seed) => number_or_exit("seed", seed),
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default(),
+    };
+
+    let mut reader = match File::open(&pack_path) {
+        Ok(file) => BufReader::with_capacity(buffer_size_or_exit(matches), file),
+        Err(e) => {
+            error!("Could not open packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    let sample = match AssetPack::read_metadata_only(&mut reader) {
+        Ok(summary) => summary.sample_objects(count as usize, seed),
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    println!("Sampled {} files with seed {}", sample.len(), seed);
+    for path in sample.iter() {
+        println!("    {}", path);
+    }
+
+    if matches.is_present("dry_run") {
+        println!(
+            "Would extract {} files to '{}'",
+            sample.len(),
+            output_dir.display()
+        );
+        return;
+    }
+
+    match AssetPack::extract_files(
+        &mut reader,
+        &sample,
+        &output_dir,
+        matches.is_present("force_overwrite"),
+    ) {
+        Ok(written) => println!("Extracted {} files", written),
+        Err(e) => {
+            error!(
+                "Something went wrong while extracting to '{}':\n{}",
+                output_dir.display(),
+                e
+            );
+            exit(1);
+        }
+    }
+}
+
+fn number_or_exit(name: &str, value: &str) -> u64 {
+    match value.parse::<u64>() {
+        Ok(number) => number,
+        Err(_) => {
+            error!("The {} should be a number, not '{}'.", name, value);
+            exit(1);
+        }
+    }
+}
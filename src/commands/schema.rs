@@ -0,0 +1,33 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::{pack_meta_schema, tags_schema};
+use log::error;
+use std::process::exit;
+
+pub const NAME: &str = "schema";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Prints a JSON Schema for the `pack.json` or the tags file of an asset pack.")
+        .arg(
+            Arg::with_name("FORMAT")
+                .help("Which file to print the schema for")
+                .possible_values(&["pack-json", "tags"])
+                .required(true)
+                .index(1),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let schema = match matches.value_of("FORMAT").unwrap() {
+        "tags" => tags_schema(),
+        _ => pack_meta_schema(),
+    };
+
+    match schema.to_json() {
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            error!("Could not serialize the schema:\n{}", e);
+            exit(1);
+        }
+    }
+}
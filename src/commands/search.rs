@@ -0,0 +1,59 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::{scan_packs, TagIndex};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use crate::{buffer_size_or_exit, find_packs, input_dir_valid_or_exit, threads_or_exit};
+
+pub const NAME: &str = "search";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Searches all packs in a directory for files with a tag.")
+        .arg(
+            Arg::with_name("INPUT_DIR")
+                .help("Input directory, will scan recursively for `*.dungeondraft_pack` files")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("tag")
+                .long("tag")
+                .value_name("NAME")
+                .required(true)
+                .help("The tag to search for"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .takes_value(true)
+                .default_value("4")
+                .help("How many packs to read at the same time"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let input_dir = PathBuf::from(matches.value_of("INPUT_DIR").unwrap());
+    input_dir_valid_or_exit(&input_dir);
+
+    let tag = matches.value_of("tag").unwrap();
+
+    let threads = threads_or_exit(matches);
+
+    let buffer_size = buffer_size_or_exit(matches);
+
+    let packs = find_packs(&input_dir);
+    let summaries = scan_packs(&packs, threads, |path| {
+        Ok(BufReader::with_capacity(buffer_size, File::open(path)?))
+    });
+
+    let index = TagIndex::from_summaries(&summaries);
+    let files = index.files_in_tag(tag);
+
+    for (pack_id, path) in files {
+        println!("{}: {}", pack_id, path);
+    }
+
+    println!("{} files tagged '{}'", files.len(), tag);
+}
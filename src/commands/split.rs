@@ -0,0 +1,100 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::WriteOptions;
+use log::error;
+use std::fs;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::overwrite::Overwrite;
+use crate::{buffer_size_or_exit, read_pack, write_pack};
+
+pub const NAME: &str = "split";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Splits an asset pack into multiple smaller packs.")
+        .arg(
+            Arg::with_name("PACK")
+                .help("The `*.dungeondraft_pack` file to split")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("OUTPUT_DIR")
+                .help("Where to write the resulting packs")
+                .required(true)
+                .index(2),
+        )
+        .arg(
+            Arg::with_name("max_pack_size")
+                .long("max-pack-size")
+                .value_name("BYTES")
+                .help("Maximum size of each resulting pack, in bytes")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("force_overwrite")
+                .short("F")
+                .help("Overwrite the output files if they exist"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let pack_path = PathBuf::from(matches.value_of("PACK").unwrap());
+    let output_dir = PathBuf::from(matches.value_of("OUTPUT_DIR").unwrap());
+
+    let max_pack_size = match matches.value_of("max_pack_size").unwrap().parse::<u64>() {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Invalid maximum pack size: {}", e);
+            exit(1);
+        }
+    };
+
+    let buffer_size = buffer_size_or_exit(matches);
+
+    let mut overwrite = if matches.is_present("force_overwrite") {
+        Overwrite::Always
+    } else {
+        Overwrite::Never
+    };
+
+    let pack = match read_pack(&pack_path, matches) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    let parts = match pack.split_by_size(max_pack_size) {
+        Ok(parts) => parts,
+        Err(e) => {
+            error!("Could not split '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    if let Err(e) = fs::create_dir_all(&output_dir) {
+        error!(
+            "Could not create output directory '{}':\n{}",
+            output_dir.display(),
+            e
+        );
+        exit(1);
+    }
+
+    let file_stem = pack_path.file_stem().unwrap().to_string_lossy();
+
+    for (index, part) in parts.iter().enumerate() {
+        let output_path = output_dir.join(format!("{}_{}.dungeondraft_pack", file_stem, index + 1));
+        write_pack(
+            part,
+            &output_path,
+            &WriteOptions::default(),
+            buffer_size,
+            &mut overwrite,
+            matches.is_present("dry_run"),
+        );
+    }
+}
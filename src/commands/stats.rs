@@ -0,0 +1,112 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::AssetPack;
+use log::error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::{buffer_size_or_exit, read_pack};
+
+pub const NAME: &str = "stats";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Shows how many files an asset pack has, and how big they are.")
+        .arg(
+            Arg::with_name("PACK")
+                .help("The `*.dungeondraft_pack` file to read")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("by_tag")
+                .long("by-tag")
+                .help("Also show the total size of the object files in each tag, largest first"),
+        )
+        .arg(Arg::with_name("layout").long("layout").help(
+            "Also show how much of the pack file is header, file table, \
+                    file contents and unused bytes between the files",
+        ))
+}
+
+pub fn run(matches: &ArgMatches) {
+    let pack_path = PathBuf::from(matches.value_of("PACK").unwrap());
+
+    let pack = match read_pack(&pack_path, matches) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    let object_bytes: usize = pack.object_files.values().map(Vec::len).sum();
+    let other_bytes: usize = pack.other_files.values().map(Vec::len).sum();
+    let (thumbnail_count, thumbnail_bytes) = pack.thumbnail_stats();
+
+    println!("Pack: {} ({})", pack.meta.name, pack.meta.id);
+    println!(
+        "Object files: {} ({} bytes)",
+        pack.object_files.len(),
+        object_bytes
+    );
+    println!(
+        "Other files: {} ({} bytes)",
+        pack.other_files.len() - thumbnail_count,
+        other_bytes as u64 - thumbnail_bytes
+    );
+    println!(
+        "Thumbnails: {} ({} bytes)",
+        thumbnail_count, thumbnail_bytes
+    );
+    println!("Tags: {}", pack.tags.tags.len());
+    println!("Tag sets: {}", pack.tags.sets.len());
+
+    if matches.is_present("by_tag") {
+        let mut tag_sizes: Vec<(&String, u64)> = pack
+            .tags
+            .tags
+            .keys()
+            .filter_map(|tag| Some((tag, pack.tag_byte_size(tag)?)))
+            .collect();
+        tag_sizes.sort_by(|(a_tag, a_size), (b_tag, b_size)| {
+            b_size.cmp(a_size).then_with(|| a_tag.cmp(b_tag))
+        });
+
+        println!();
+        for (tag, size) in tag_sizes {
+            println!("{}: {} bytes", tag, size);
+        }
+    }
+
+    if matches.is_present("layout") {
+        let report = File::open(&pack_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| {
+                AssetPack::layout_report(&mut BufReader::with_capacity(
+                    buffer_size_or_exit(matches),
+                    file,
+                ))
+            });
+
+        let report = match report {
+            Ok(report) => report,
+            Err(e) => {
+                error!(
+                    "Could not read the file table of '{}':\n{}",
+                    pack_path.display(),
+                    e
+                );
+                exit(1);
+            }
+        };
+
+        println!();
+        println!("Header: {} bytes", report.header_bytes);
+        println!("File table: {} bytes", report.table_bytes);
+        println!("File contents: {} bytes", report.payload_bytes);
+        println!("Unused: {} bytes", report.padding_bytes);
+        println!("Total: {} bytes", report.total_bytes);
+    }
+}
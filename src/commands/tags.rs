@@ -0,0 +1,113 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::{TagImport, WriteOptions};
+use log::error;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::overwrite::Overwrite;
+use crate::{buffer_size_or_exit, read_pack, write_pack};
+
+pub const NAME: &str = "tags";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Exports the tags of an asset pack to a tags file, or imports them from one.")
+        .arg(
+            Arg::with_name("PACK")
+                .help("The `*.dungeondraft_pack` file")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("OUTPUT_FILE")
+                .help("Where to write the pack with the imported tags")
+                .required_unless("export_tags")
+                .index(2),
+        )
+        .arg(
+            Arg::with_name("export_tags")
+                .long("export-tags")
+                .value_name("FILE")
+                .help("Write the tags and tag sets of the pack to this file"),
+        )
+        .arg(
+            Arg::with_name("import_tags")
+                .long("import-tags")
+                .value_name("FILE")
+                .requires("OUTPUT_FILE")
+                .required_unless("export_tags")
+                .help(
+                    "Replace the tags and tag sets of the pack with the ones in this file.\n\
+                References to files that are not in the pack are left out.",
+                ),
+        )
+        .arg(
+            Arg::with_name("merge_tags")
+                .long("merge-tags")
+                .requires("import_tags")
+                .help("Add the imported tags to the existing ones, instead of replacing them"),
+        )
+        .arg(
+            Arg::with_name("force_overwrite")
+                .short("F")
+                .help("Overwrite the output file if it exists"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let pack_path = PathBuf::from(matches.value_of("PACK").unwrap());
+    let buffer_size = buffer_size_or_exit(matches);
+
+    let mut pack = match read_pack(&pack_path, matches) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    // Exported before importing, so the export shows the tags the pack had.
+    if let Some(export_path) = matches.value_of("export_tags") {
+        if let Err(e) = pack.export_tags(&PathBuf::from(export_path)) {
+            error!("Could not export tags:\n{}", e);
+            exit(1);
+        }
+    }
+
+    if let Some(import_path) = matches.value_of("import_tags") {
+        let mode = if matches.is_present("merge_tags") {
+            TagImport::Merge
+        } else {
+            TagImport::Replace
+        };
+
+        match pack.import_tags(&PathBuf::from(import_path), mode) {
+            Ok(left_out) if left_out > 0 => println!(
+                "Left out {} references to files that are not in the pack",
+                left_out
+            ),
+            Ok(_) => {}
+            Err(e) => {
+                error!("Could not import tags:\n{}", e);
+                exit(1);
+            }
+        }
+
+        let output_path = PathBuf::from(matches.value_of("OUTPUT_FILE").unwrap());
+
+        let mut overwrite = if matches.is_present("force_overwrite") {
+            Overwrite::Always
+        } else {
+            Overwrite::Never
+        };
+
+        write_pack(
+            &pack,
+            &output_path,
+            &WriteOptions::default(),
+            buffer_size,
+            &mut overwrite,
+            matches.is_present("dry_run"),
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::UnpackOptions;
+use log::error;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::read_pack;
+
+pub const NAME: &str = "unpack";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Extracts the files of an asset pack into a directory.")
+        .arg(
+            Arg::with_name("PACK")
+                .help("The `*.dungeondraft_pack` file to extract")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("OUTPUT_DIR")
+                .help("The files will be extracted into this directory")
+                .required(true)
+                .index(2),
+        )
+        .arg(
+            Arg::with_name("category")
+                .long("category")
+                .takes_value(true)
+                .use_delimiter(true)
+                .help(
+                    "Only extract these categories, comma separated. For example: `objects,walls`.\n\
+                Categories are the directories in `textures/`,\n\
+                `data` for the data files and tags, and `meta` for `pack.json`.",
+                ),
+        )
+        .arg(
+            Arg::with_name("force_overwrite")
+                .short("F")
+                .help("Overwrite existing output files"),
+        )
+        .arg(
+            Arg::with_name("incremental")
+                .long("incremental")
+                .help(
+                    "Skip files that were already extracted with the same contents,\n\
+                and overwrite the ones that changed",
+                ),
+        )
+        .arg(
+            Arg::with_name("resumable")
+                .long("resumable")
+                .help(
+                    "Keep track of the extracted files in the output directory, so an interrupted\n\
+                extraction continues where it stopped when run again",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let pack_path = PathBuf::from(matches.value_of("PACK").unwrap());
+    let output_dir = PathBuf::from(matches.value_of("OUTPUT_DIR").unwrap());
+
+    let options = UnpackOptions {
+        categories: matches
+            .values_of("category")
+            .map(|values| values.map(str::to_owned).collect()),
+        overwrite: matches.is_present("force_overwrite"),
+        incremental: matches.is_present("incremental"),
+        resumable: matches.is_present("resumable"),
+    };
+
+    let pack = match read_pack(&pack_path, matches) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    if matches.is_present("dry_run") {
+        println!(
+            "Would extract {} files to '{}'",
+            pack.object_files.len() + pack.other_files.len() + 2,
+            output_dir.display()
+        );
+        return;
+    }
+
+    match pack.unpack_to_dir(&output_dir, &options) {
+        Ok(written) if options.incremental || options.resumable => println!(
+            "Extracted {} files, skipped {} unchanged files",
+            written.files, written.skipped
+        ),
+        Ok(written) => println!("Extracted {} files", written.files),
+        Err(e) => {
+            error!(
+                "Something went wrong while unpacking to '{}':\n{}",
+                output_dir.display(),
+                e
+            );
+            exit(1);
+        }
+    }
+}
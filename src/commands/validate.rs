@@ -0,0 +1,89 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::ValidationOptions;
+use log::error;
+use std::path::PathBuf;
+use std::process::exit;
+
+use crate::read_pack;
+
+pub const NAME: &str = "validate";
+
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(NAME)
+        .about("Checks an asset pack for problems. Exits with an error code if any are found.")
+        .arg(
+            Arg::with_name("PACK")
+                .help("The `*.dungeondraft_pack` file to check")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("check_names")
+                .long("check-names")
+                .help("Also report paths containing spaces or non-ASCII characters"),
+        )
+        .arg(
+            Arg::with_name("check_image_headers")
+                .long("check-image-headers")
+                .help("Also report object files whose contents don't match their image extension"),
+        )
+        .arg(
+            Arg::with_name("report_encoding")
+                .long("report-encoding")
+                .help(
+                    "Also report json files saved with a byte order mark or Windows line endings",
+                ),
+        )
+        .arg(
+            Arg::with_name("max_files_per_tag")
+                .long("max-files-per-tag")
+                .value_name("COUNT")
+                .default_value("5000")
+                .help("Report tags that reference more files than this"),
+        )
+}
+
+pub fn run(matches: &ArgMatches) {
+    let pack_path = PathBuf::from(matches.value_of("PACK").unwrap());
+
+    let pack = match read_pack(&pack_path, matches) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    let max_files_per_tag = match matches.value_of("max_files_per_tag").unwrap().parse() {
+        Ok(count) => count,
+        Err(_) => {
+            error!("The maximum number of files per tag should be a number.");
+            exit(1);
+        }
+    };
+
+    let options = ValidationOptions {
+        check_portable_names: matches.is_present("check_names"),
+        max_files_per_tag: Some(max_files_per_tag),
+        check_image_headers: matches.is_present("check_image_headers"),
+        check_encoding: matches.is_present("report_encoding"),
+        ..Default::default()
+    };
+
+    println!(
+        "{} references from tags to files",
+        pack.tag_file_associations()
+    );
+
+    let issues = pack.validate(&options);
+
+    for issue in issues.iter() {
+        println!("{}", issue);
+    }
+
+    println!("{} issues found", issues.len());
+
+    if !issues.is_empty() {
+        exit(1);
+    }
+}
@@ -0,0 +1,154 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+use dd_asset_tools::asset_pack::{AssetPack, WriteOptions};
+use log::{error, info, warn};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use crate::overwrite::Overwrite;
+use crate::{buffer_size_or_exit, read_pack, write_pack};
+
+pub const EXPORT_NAME: &str = "export-zip";
+pub const IMPORT_NAME: &str = "import-zip";
+
+pub fn export_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(EXPORT_NAME)
+        .about(
+            "Converts an asset pack into a zip archive with the same files as `unpack` extracts, \
+            so it can be edited with any zip tool.",
+        )
+        .arg(
+            Arg::with_name("PACK")
+                .help("The `*.dungeondraft_pack` file to read")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("OUTPUT_FILE")
+                .help("The zip archive to create")
+                .required(true)
+                .index(2),
+        )
+        .arg(
+            Arg::with_name("force_overwrite")
+                .short("F")
+                .help("Overwrite the output file if it exists"),
+        )
+}
+
+pub fn import_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name(IMPORT_NAME)
+        .about(
+            "Builds an asset pack from a zip archive containing a `pack.json`, \
+            as created by `export-zip`. The files can be stored or deflated.",
+        )
+        .arg(
+            Arg::with_name("ZIP")
+                .help("The zip archive to read")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::with_name("OUTPUT_FILE")
+                .help("The `*.dungeondraft_pack` file to create")
+                .required(true)
+                .index(2),
+        )
+        .arg(
+            Arg::with_name("force_overwrite")
+                .short("F")
+                .help("Overwrite the output file if it exists"),
+        )
+}
+
+fn overwrite_mode(matches: &ArgMatches) -> Overwrite {
+    if matches.is_present("force_overwrite") {
+        Overwrite::Always
+    } else {
+        Overwrite::Never
+    }
+}
+
+pub fn run_export(matches: &ArgMatches) {
+    let pack_path = PathBuf::from(matches.value_of("PACK").unwrap());
+    let output_path = PathBuf::from(matches.value_of("OUTPUT_FILE").unwrap());
+    let buffer_size = buffer_size_or_exit(matches);
+
+    let pack = match read_pack(&pack_path, matches) {
+        Ok(p) => p,
+        Err(e) => {
+            error!("Could not read packfile '{}':\n{}", pack_path.display(), e);
+            exit(1);
+        }
+    };
+
+    if matches.is_present("dry_run") {
+        println!(
+            "Would write {} files to '{}'",
+            pack.object_files.len() + pack.other_files.len() + 2,
+            output_path.display()
+        );
+        return;
+    }
+
+    if output_path.exists()
+        && !overwrite_mode(matches).allows(&output_path, &mut io::stdin().lock(), &mut io::stdout())
+    {
+        warn!(
+            "Output file '{}' already exists. If you want to overwrite, call again with the `-F` argument.",
+            output_path.display()
+        );
+        exit(1);
+    }
+
+    if let Err(e) = write_zip(&pack, &output_path, buffer_size) {
+        error!(
+            "Could not write the zip archive '{}':\n{}",
+            output_path.display(),
+            e
+        );
+        exit(1);
+    }
+
+    info!("Wrote '{}'", output_path.display());
+}
+
+fn write_zip(pack: &AssetPack, path: &Path, buffer_size: usize) -> anyhow::Result<()> {
+    let mut file = BufWriter::with_capacity(buffer_size, File::create(path)?);
+    pack.to_zip(&mut file)?;
+    file.flush()?;
+
+    Ok(())
+}
+
+pub fn run_import(matches: &ArgMatches) {
+    let zip_path = PathBuf::from(matches.value_of("ZIP").unwrap());
+    let output_path = PathBuf::from(matches.value_of("OUTPUT_FILE").unwrap());
+    let buffer_size = buffer_size_or_exit(matches);
+
+    let pack = File::open(&zip_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| AssetPack::from_zip(&mut BufReader::with_capacity(buffer_size, file)));
+
+    let pack = match pack {
+        Ok(pack) => pack,
+        Err(e) => {
+            error!(
+                "Could not read zip archive '{}':\n{}",
+                zip_path.display(),
+                e
+            );
+            exit(1);
+        }
+    };
+
+    write_pack(
+        &pack,
+        &output_path,
+        &WriteOptions::default(),
+        buffer_size,
+        &mut overwrite_mode(matches),
+        matches.is_present("dry_run"),
+    );
+}
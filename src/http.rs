@@ -0,0 +1,88 @@
+use std::io::Read;
+use std::time::Duration;
+
+use anyhow::Context;
+
+/// How long to wait for the server before giving up.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Downloads the file at an `http://` or `https://` url into memory, e.g. to read a pack
+/// from it with a `Cursor`. Redirects are followed.
+pub fn fetch(url: &str) -> anyhow::Result<Vec<u8>> {
+    let response = ureq::get(url)
+        .timeout(TIMEOUT)
+        .call()
+        .context(format!("Could not download '{}'", url))?;
+
+    let mut body = vec![];
+    response
+        .into_reader()
+        .read_to_end(&mut body)
+        .context(format!("Could not download '{}'", url))?;
+
+    Ok(body)
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+    use crate::asset_pack::AssetPack;
+    use crate::http::fetch;
+
+    /// Answers a single request with `response`, returns the url to request.
+    fn serve_once(response: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            stream.write_all(&response).unwrap();
+        });
+
+        format!("http://{}/packs/example.dungeondraft_pack", address)
+    }
+
+    #[test]
+    fn fetch_pack_and_read_info() {
+        let raw_pack = create_raw_test_pack().unwrap();
+        let mut response = format!(
+            "HTTP/1.0 200 OK\r\nContent-Length: {}\r\n\r\n",
+            raw_pack.len()
+        )
+        .into_bytes();
+        response.extend_from_slice(&raw_pack);
+
+        let data = fetch(&serve_once(response)).unwrap();
+        assert_eq!(data, raw_pack);
+
+        let mut cursor = Cursor::new(data);
+        let (godot_version, _) = AssetPack::read_version(&mut cursor).unwrap();
+        assert_eq!(godot_version.to_string(), "1.3.2.4");
+
+        cursor.set_position(0);
+        let summary = AssetPack::read_metadata_only(&mut cursor).unwrap();
+        assert_eq!(summary.meta.id, "12345678");
+    }
+
+    #[test]
+    fn fetch_not_found() {
+        let url = serve_once(b"HTTP/1.0 404 Not Found\r\n\r\n".to_vec());
+
+        let error = fetch(&url).unwrap_err();
+        assert!(format!("{:#}", error).contains("404"));
+    }
+
+    #[test]
+    fn fetch_follows_redirects() {
+        let url = serve_once(b"HTTP/1.0 200 OK\r\nContent-Length: 4\r\n\r\nrock".to_vec());
+        let redirect = format!("HTTP/1.0 302 Found\r\nLocation: {}\r\n\r\n", url);
+
+        assert_eq!(fetch(&serve_once(redirect.into_bytes())).unwrap(), b"rock");
+    }
+}
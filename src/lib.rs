@@ -0,0 +1,5 @@
+pub mod asset_pack;
+#[cfg(feature = "http")]
+pub mod http;
+#[cfg(all(feature = "mmap", unix))]
+pub mod mapped_file;
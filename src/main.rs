@@ -1,23 +1,138 @@
 use anyhow::{Context, Result};
-use asset_pack::AssetPack;
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, ArgMatches};
+#[cfg(feature = "image-codec")]
+use dd_asset_tools::asset_pack::ImageCodec;
+use dd_asset_tools::asset_pack::{
+    AssetPack, ClassificationRules, CleanOptions, CleanReport, ReadOptions, UnpackOptions,
+    UnpackStats, WriteOptions,
+};
+#[cfg(all(feature = "mmap", unix))]
+use dd_asset_tools::mapped_file::MappedFile;
 use glob::glob;
 use log::{debug, error, info, warn, LevelFilter};
+use overwrite::Overwrite;
 use simplelog::{ColorChoice, ConfigBuilder, TermLogger, TerminalMode};
+use std::collections::BTreeMap;
 use std::fs;
 use std::fs::File;
-use std::path::PathBuf;
+use std::io;
+#[cfg(all(feature = "mmap", unix))]
+use std::io::Cursor;
+use std::io::{BufReader, BufWriter, Seek};
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use watch::{file_states, PackWatcher};
 
-mod asset_pack;
+mod commands;
+mod overwrite;
+mod pack_input;
+mod watch;
 
 const ASSET_PACK_EXTENSION: &str = ".dungeondraft_pack";
+/// Capacity of the buffers around pack files, in bytes.
+const DEFAULT_BUFFER_SIZE: &str = "65536";
+/// How often `--watch` looks for changed packs. A changed pack is processed
+/// once it has stayed the same for this long.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
 
-fn main() {
-    let matches = App::new("Dungeondraft Asset Tools")
+/// What to do with each pack, on top of cleaning the tags.
+struct ProcessOptions {
+    /// Skip cleaning the tags, only apply the other edits.
+    no_clean: bool,
+    clean_options: CleanOptions,
+    /// Print every reference to a file the pack does not have.
+    ref_check_external: bool,
+    prune_unreferenced: bool,
+    /// Keep one of every group of identical object files.
+    dedupe_objects: bool,
+    strip_thumbnails: bool,
+    repair_names: bool,
+    bump_version: bool,
+    reset_color_overrides: bool,
+    /// Record the time and program version in `pack.json`, see `PackMeta::stamp`.
+    stamp: bool,
+    /// Remove an earlier stamp, so the output only depends on the pack contents.
+    no_stamp: bool,
+    output_format: OutputFormat,
+    write_options: WriteOptions,
+    read_options: ReadOptions,
+    buffer_size: usize,
+    /// Read the packs through a memory map, see `--mmap`.
+    mmap: bool,
+    /// Globs and the tag to add to the object files matching them.
+    tag_patterns: Vec<(String, String)>,
+    /// Put in front of every tag name.
+    tag_prefix: Option<String>,
+    /// Tag the object files after the directory they are in.
+    auto_tag_from_folders: bool,
+    /// Tags to remove, along with the object files that are only in them.
+    exclude_tags: Vec<String>,
+    /// Remove all tags and tag sets, keeping the object files.
+    clear_tags: bool,
+    /// Name of the tag set to add all tags to that are in no set.
+    auto_set: Option<String>,
+    /// The image format to convert the object files to, `png` or `webp`.
+    #[cfg(feature = "image-codec")]
+    convert_objects_to: Option<String>,
+    /// Tag sets to rename, in order, as old and new name.
+    rename_sets: Vec<(String, String)>,
+    /// Skip a pack instead of only warning when an edit does not apply to it,
+    /// like renaming a set it does not have.
+    strict: bool,
+    /// Warn when the output file name contains neither the pack id nor the pack name.
+    enforce_name_match: bool,
+    /// Don't log the details of each pack, only print the totals at the end.
+    summary_only: bool,
+    /// Only print what would be written, see `--dry-run`.
+    dry_run: bool,
+    /// Where to write what was removed from the tags of each pack, as json.
+    clean_report: Option<PathBuf>,
+}
+
+/// What processing a single pack did, added up over all packs for `--summary-only`.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+struct PackStats {
+    tags_removed: usize,
+    sets_removed: usize,
+    files_written: usize,
+    bytes_written: u64,
+}
+
+impl PackStats {
+    fn add(&mut self, other: &PackStats) {
+        self.tags_removed += other.tags_removed;
+        self.sets_removed += other.sets_removed;
+        self.files_written += other.files_written;
+        self.bytes_written += other.bytes_written;
+    }
+}
+
+/// What `handle_packs` did over all packs.
+#[derive(Debug, Default)]
+struct BatchResult {
+    processed_count: usize,
+    totals: PackStats,
+    /// What was removed from the tags of each pack, by pack path.
+    clean_reports: BTreeMap<String, CleanReport>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum OutputFormat {
+    /// A `*.dungeondraft_pack` file.
+    Pack,
+    /// A directory with the extracted files.
+    Dir,
+}
+
+/// The command line interface: the main flow, its options, and the subcommands.
+fn app<'a, 'b>() -> App<'a, 'b> {
+    let app = App::new("Dungeondraft Asset Tools")
         .version("0.1")
         .author("Wybe Westra <dev@wwestra.nl>")
         .about("For now can remove empty tags and tag groups from Dungeondraft asset packs.")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("INPUT_DIR")
                 .help("Input directory, will scan recursively for `*.dungeondraft_pack` files")
@@ -38,11 +153,388 @@ fn main() {
                 .short("F")
                 .help("Overwrite existing output files"),
         )
-        .arg(Arg::with_name("v").short("v").multiple(true).help(
-            "Print extra info.\n\
-        Put in -vv, or -vvv for even more info.",
+        .arg(
+            Arg::with_name("interactive")
+                .long("interactive")
+                .conflicts_with("force_overwrite")
+                .help("Ask before overwriting each existing output file"),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .long("watch")
+                .requires("force_overwrite")
+                .help(
+                    "After processing the packs, keep watching the input directory and \
+                process every pack that is added or changed. Requires `-F`.",
+                ),
+        )
+        .arg(
+            Arg::with_name("sort_files_by_path")
+                .long("sort-files-by-path")
+                .help(
+                    "Write the files in the pack ordered by their path.\n\
+                This makes the output deterministic, and can help when compressing the pack.",
+                ),
+        )
+        .arg(
+            Arg::with_name("preserve_file_order")
+                .long("preserve-file-order")
+                .conflicts_with("sort_files_by_path")
+                .help(
+                    "Write the files in the pack in the same order as they were read,\n\
+                so the output differs as little as possible from the input.",
+                ),
+        )
+        .arg(
+            Arg::with_name("write_md5")
+                .long("write-md5")
+                .help("Store the md5 hash of every file in the pack, instead of leaving them zeroed"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .takes_value(true)
+                .default_value("4")
+                .help("How many files to hash at the same time, with `--write-md5`"),
+        )
+        .arg(
+            Arg::with_name("align")
+                .long("align")
+                .takes_value(true)
+                .value_name("N")
+                .help("Start every file in the pack at a multiple of N bytes, like Godot 4 does"),
+        )
+        .arg(
+            Arg::with_name("embed_tags")
+                .long("embed-tags")
+                .help(
+                    "Store the tags in pack.json instead of in a separate tags file.\n\
+                Only for tools that expect this, Dungeondraft does not read tags from there.",
+                ),
+        )
+        .arg(
+            Arg::with_name("share_metadata_offset")
+                .long("share-metadata-offset")
+                .help(
+                    "Store the pack metadata once, with both of its file entries pointing at \
+                the same bytes, to make the pack a little smaller.\n\
+                Any other file with the same contents as the file before it is stored once as well.",
+                ),
+        )
+        .arg(
+            Arg::with_name("compress")
+                .long("compress")
+                .help(
+                    "Store the files zlib-compressed, when that makes them smaller.\n\
+                Only for Godot 4 packs, and only this program can read the result.",
+                ),
+        )
+        .arg(
+            Arg::with_name("sort_tag_members")
+                .long("sort-tag-members")
+                .help(
+                    "Write the tags file in the pack with the tags, tag sets and their contents \
+                sorted by name, for clean diffs in version control",
+                ),
+        )
+        .arg(Arg::with_name("pretty").long("pretty").help(
+            "Write the `pack.json` and tags file in the pack indented, like Dungeondraft does",
         ))
-        .get_matches();
+        .arg(
+            Arg::with_name("compact")
+                .long("compact")
+                .conflicts_with("pretty")
+                .help("Write the `pack.json` and tags file in the pack on a single line (default)"),
+        )
+        .arg(
+            Arg::with_name("object_prefix")
+                .long("object-prefix")
+                .value_name("PREFIX")
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Also treat files starting with this path as object files, \
+                so their tags are kept.\n\
+                For example: `textures/materials/`. Can be given multiple times.",
+                ),
+        )
+        .arg(
+            Arg::with_name("tag_pattern")
+                .long("tag-pattern")
+                .value_name("GLOB=TAG")
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Add the tag to all object files matching the glob.\n\
+                For example: `textures/objects/furniture/**=Furniture`. Can be given multiple times.",
+                ),
+        )
+        .arg(
+            Arg::with_name("ensure_pack_json_consistency")
+                .long("ensure-pack-json-consistency")
+                .help(
+                    "Warn about every field in which the two copies of the pack metadata differ.\n\
+                The root json file is used, unless `--prefer-pack-json` is given.\n\
+                Both copies are always written the same.",
+                ),
+        )
+        .arg(
+            Arg::with_name("prefer_pack_json")
+                .long("prefer-pack-json")
+                .requires("ensure_pack_json_consistency")
+                .help("Use the `pack.json` copy of the metadata when the two copies differ"),
+        )
+        .arg(
+            Arg::with_name("assume_magic")
+                .long("assume-magic")
+                .value_name("HEADER_LEN")
+                .help(
+                    "Skip this many bytes of header instead of expecting the `GDPC` magic number.\n\
+                Use 0 for packs whose header was stripped off.",
+                ),
+        )
+        .arg(
+            Arg::with_name("summary_only")
+                .long("summary-only")
+                .help("Don't print the details of each pack, only the totals at the end"),
+        )
+        .arg(
+            Arg::with_name("clean_report")
+                .long("clean-report")
+                .value_name("FILE")
+                .conflicts_with("no_clean")
+                .help("Write the tags, tag sets and file references that were removed to this json file"),
+        )
+        .arg(
+            Arg::with_name("tag_prefix")
+                .long("tag-prefix")
+                .value_name("PREFIX")
+                .help(
+                    "Put this in front of every tag name, and update the tag sets to match.\n\
+                Keeps the tags apart when the pack is used next to a pack with the same tag names.",
+                ),
+        )
+        .arg(
+            Arg::with_name("auto_tag_from_folders")
+                .long("auto-tag-from-folders")
+                .help(
+                    "Tag the object files in each directory under `textures/objects/` \
+                with the name of that directory",
+                ),
+        )
+        .arg(
+            Arg::with_name("clear_tags")
+                .long("clear-tags")
+                .help(
+                    "Remove all tags and tag sets, keeping the object files.\n\
+                Tags added by other options, like `--tag-pattern`, are kept.",
+                ),
+        )
+        .arg(
+            Arg::with_name("rename_set")
+                .long("rename-set")
+                .value_name("OLD=NEW")
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Rename the tag set OLD to NEW, keeping its tags.\n\
+                Can be given multiple times, they are applied in order.",
+                ),
+        )
+        .arg(
+            Arg::with_name("auto_set")
+                .long("auto-set")
+                .value_name("NAME")
+                .help("Add all tags that are in no tag set to this set, so they are easier to find in Dungeondraft"),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("Skip a pack instead of only warning when a rename does not apply to it"),
+        )
+        .arg(
+            Arg::with_name("exclude_tag")
+                .long("exclude-tag")
+                .value_name("NAME")
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Remove this tag, and the object files that are in no other tag.\n\
+                Can be given multiple times.",
+                ),
+        )
+        .arg(
+            Arg::with_name("keep_external_refs")
+                .long("keep-external-refs")
+                .help(
+                    "Keep tag references to object files that are not in the pack,\n\
+                if they look like object files of another pack. Obviously broken ones are removed.",
+                ),
+        )
+        .arg(
+            Arg::with_name("ref_check_external")
+                .long("ref-check-external")
+                .help("List every tag reference to an object file that is not in the pack"),
+        )
+        .arg(
+            Arg::with_name("strict_tags")
+                .long("strict-tags")
+                .help(
+                    "Refuse to write a pack in which a tag references an object file \
+                the pack does not have, and list those references",
+                ),
+        )
+        .arg(
+            Arg::with_name("no_clean")
+                .long("no-clean")
+                .help("Don't remove empty tags and tag sets, only apply the other options"),
+        )
+        .arg(
+            Arg::with_name("prune_unreferenced")
+                .long("prune-unreferenced")
+                .help(
+                    "Remove wall, tileset and path textures that none of the data files refer to.\n\
+                Nothing is removed if one of the data files can't be parsed.",
+                ),
+        )
+        .arg(
+            Arg::with_name("dedupe_objects")
+                .long("dedupe-objects")
+                .help(
+                    "Keep only one of every group of object files with the same contents, \
+                and point the tags to that one",
+                ),
+        )
+        .arg(
+            Arg::with_name("strip_thumbnails")
+                .long("strip-thumbnails")
+                .help("Remove the thumbnails, Dungeondraft generates them again when needed"),
+        )
+        .arg(
+            Arg::with_name("enforce_name_match")
+                .long("enforce-name-match")
+                .help("Warn when an output file name contains neither the pack id nor the pack name"),
+        )
+        .arg(
+            Arg::with_name("repair_names")
+                .long("repair-names")
+                .help("Rename files with spaces or non-ASCII characters in their path"),
+        )
+        .arg(Arg::with_name("bump_version").long("bump-version").help(
+            "Increase the version of each pack.\n\
+                `1` becomes `2`, `1.2.3` becomes `1.2.4` and anything else gets `.1` appended.",
+        ))
+        .arg(
+            Arg::with_name("reset_color_overrides")
+                .long("reset-color-overrides")
+                .help("Set the color overrides of each pack to the Dungeondraft defaults, which leave the colors alone"),
+        )
+        .arg(
+            Arg::with_name("stamp")
+                .long("stamp")
+                .help("Write the current time and the version of this program into pack.json, as `build_timestamp` and `tool_version`"),
+        )
+        .arg(
+            Arg::with_name("no_stamp")
+                .long("no-stamp")
+                .conflicts_with("stamp")
+                .help("Remove the `build_timestamp` and `tool_version` from pack.json, so the output is the same every time"),
+        )
+        .arg(
+            Arg::with_name("output_format")
+                .long("output-format")
+                .takes_value(true)
+                .possible_values(&["pack", "dir"])
+                .default_value("pack")
+                .help(
+                    "Write each cleaned pack as a `*.dungeondraft_pack` file,\n\
+                or extract it into a directory named after the pack file.",
+                ),
+        )
+        .arg(
+            Arg::with_name("buffer_size")
+                .long("buffer-size")
+                .value_name("BYTES")
+                .default_value(DEFAULT_BUFFER_SIZE)
+                .global(true)
+                .help("Size of the buffers used when reading and writing pack files"),
+        )
+        .arg(
+            Arg::with_name("max_memory")
+                .long("max-memory")
+                .value_name("BYTES")
+                .global(true)
+                .help("Refuse to read packs whose files add up to more than this many bytes"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .global(true)
+                .help(
+                    "Don't write any files, only print how many files and bytes would be written.\n\
+                For `merge`, only report the paths, tags and pack ids that collide.",
+                ),
+        )
+        .arg(
+            Arg::with_name("v")
+                .short("v")
+                .multiple(true)
+                .global(true)
+                .help(
+                    "Print extra info.\n\
+        Put in -vv, or -vvv for even more info.",
+                ),
+        )
+        .subcommand(commands::unpack::subcommand())
+        .subcommand(commands::audit::subcommand())
+        .subcommand(commands::pack::subcommand())
+        .subcommand(commands::validate::subcommand())
+        .subcommand(commands::split::subcommand())
+        .subcommand(commands::search::subcommand())
+        .subcommand(commands::tags::subcommand())
+        .subcommand(commands::merge::subcommand())
+        .subcommand(commands::export_meta::subcommand())
+        .subcommand(commands::export_coverage::subcommand())
+        .subcommand(commands::export_dot::subcommand())
+        .subcommand(commands::reorganize::subcommand())
+        .subcommand(commands::stats::subcommand())
+        .subcommand(commands::schema::subcommand())
+        .subcommand(commands::list::subcommand())
+        .subcommand(commands::list_tags::subcommand())
+        .subcommand(commands::zip::export_subcommand())
+        .subcommand(commands::zip::import_subcommand())
+        .subcommand(commands::info::subcommand())
+        .subcommand(commands::changelog::subcommand())
+        .subcommand(commands::check_manifest::subcommand())
+        .subcommand(commands::extract_file::subcommand())
+        .subcommand(commands::sample::subcommand())
+        .subcommand(commands::lint::subcommand());
+
+    #[cfg(all(feature = "mmap", unix))]
+    let app = app.arg(
+        Arg::with_name("mmap")
+            .long("mmap")
+            .global(true)
+            .help("Read pack files through a memory map, instead of through a buffer"),
+    );
+
+    #[cfg(feature = "image-codec")]
+    let app = app.arg(
+        Arg::with_name("convert_objects_to")
+            .long("convert-objects-to")
+            .value_name("FORMAT")
+            .possible_values(&["webp", "png"])
+            .help(
+                "Convert the object images to this format, and update the tags referring to them.\n\
+                WebP images are written lossless. JPEG images are left as they are.",
+            ),
+    );
+
+    app
+}
+
+fn main() {
+    let matches = app().get_matches();
 
     let verbosity = match matches.occurrences_of("v") {
         0 => LevelFilter::Warn,
@@ -62,41 +554,206 @@ fn main() {
     )
     .unwrap();
 
+    match matches.subcommand() {
+        (commands::unpack::NAME, Some(sub_matches)) => {
+            commands::unpack::run(sub_matches);
+            return;
+        }
+        (commands::audit::NAME, Some(sub_matches)) => {
+            commands::audit::run(sub_matches);
+            return;
+        }
+        (commands::pack::NAME, Some(sub_matches)) => {
+            commands::pack::run(sub_matches);
+            return;
+        }
+        (commands::validate::NAME, Some(sub_matches)) => {
+            commands::validate::run(sub_matches);
+            return;
+        }
+        (commands::split::NAME, Some(sub_matches)) => {
+            commands::split::run(sub_matches);
+            return;
+        }
+        (commands::search::NAME, Some(sub_matches)) => {
+            commands::search::run(sub_matches);
+            return;
+        }
+        (commands::tags::NAME, Some(sub_matches)) => {
+            commands::tags::run(sub_matches);
+            return;
+        }
+        (commands::merge::NAME, Some(sub_matches)) => {
+            commands::merge::run(sub_matches);
+            return;
+        }
+        (commands::export_meta::NAME, Some(sub_matches)) => {
+            commands::export_meta::run(sub_matches);
+            return;
+        }
+        (commands::reorganize::NAME, Some(sub_matches)) => {
+            commands::reorganize::run(sub_matches);
+            return;
+        }
+        (commands::stats::NAME, Some(sub_matches)) => {
+            commands::stats::run(sub_matches);
+            return;
+        }
+        (commands::schema::NAME, Some(sub_matches)) => {
+            commands::schema::run(sub_matches);
+            return;
+        }
+        (commands::check_manifest::NAME, Some(sub_matches)) => {
+            commands::check_manifest::run(sub_matches);
+            return;
+        }
+        (commands::export_coverage::NAME, Some(sub_matches)) => {
+            commands::export_coverage::run(sub_matches);
+            return;
+        }
+        (commands::export_dot::NAME, Some(sub_matches)) => {
+            commands::export_dot::run(sub_matches);
+            return;
+        }
+        (commands::sample::NAME, Some(sub_matches)) => {
+            commands::sample::run(sub_matches);
+            return;
+        }
+        (commands::list::NAME, Some(sub_matches)) => {
+            commands::list::run(sub_matches);
+            return;
+        }
+        (commands::list_tags::NAME, Some(sub_matches)) => {
+            commands::list_tags::run(sub_matches);
+            return;
+        }
+        (commands::zip::EXPORT_NAME, Some(sub_matches)) => {
+            commands::zip::run_export(sub_matches);
+            return;
+        }
+        (commands::zip::IMPORT_NAME, Some(sub_matches)) => {
+            commands::zip::run_import(sub_matches);
+            return;
+        }
+        (commands::info::NAME, Some(sub_matches)) => {
+            commands::info::run(sub_matches);
+            return;
+        }
+        (commands::changelog::NAME, Some(sub_matches)) => {
+            commands::changelog::run(sub_matches);
+            return;
+        }
+        (commands::extract_file::NAME, Some(sub_matches)) => {
+            commands::extract_file::run(sub_matches);
+            return;
+        }
+        (commands::lint::NAME, Some(sub_matches)) => {
+            commands::lint::run(sub_matches);
+            return;
+        }
+        _ => {}
+    }
+
     let input_dir = PathBuf::from(matches.value_of("INPUT_DIR").unwrap());
     input_dir_valid_or_exit(&input_dir);
 
     let output_dir = PathBuf::from(matches.value_of("OUTPUT_DIR").unwrap());
     output_dir_valid_or_exit(&input_dir, &output_dir);
 
-    let overwrite_allowed = matches.is_present("force_overwrite");
+    let mut overwrite = if matches.is_present("force_overwrite") {
+        Overwrite::Always
+    } else if matches.is_present("interactive") {
+        Overwrite::Ask
+    } else {
+        Overwrite::Never
+    };
+
+    let options = process_options_or_exit(&matches);
 
     if let Err(e) = fs::create_dir_all(&output_dir) {
         error!("Could not create the output directory:\n{}", e);
     }
 
+    let packs = find_packs(&input_dir);
+    let result = handle_packs(&packs, &output_dir, &options, &mut overwrite);
+
+    println!("{} packs encountered", packs.len());
+
+    if options.summary_only {
+        let totals = &result.totals;
+        println!("Packs processed: {}", result.processed_count);
+        println!("Tags removed: {}", totals.tags_removed);
+        println!("Tag sets removed: {}", totals.sets_removed);
+        println!("Files written: {}", totals.files_written);
+        println!("Bytes written: {}", totals.bytes_written);
+    }
+
+    if let Some(report_path) = &options.clean_report {
+        if let Err(e) = write_clean_report(report_path, &result.clean_reports) {
+            error!(
+                "Could not write the clean report '{}':\n{}",
+                report_path.display(),
+                e
+            );
+        }
+    }
+
+    if matches.is_present("watch") {
+        watch_packs(&input_dir, &output_dir, &options, &mut overwrite);
+    }
+
+    println!("Done");
+}
+
+/// Processes the packs in `input_dir` again whenever they are added or changed,
+/// until the program is stopped. Packs written to `output_dir` are not processed again,
+/// even if it is inside `input_dir`.
+fn watch_packs(
+    input_dir: &Path,
+    output_dir: &Path,
+    options: &ProcessOptions,
+    overwrite: &mut Overwrite,
+) {
+    let mut watcher = PackWatcher::new(file_states(&find_packs(input_dir)));
+    watcher.record_written(file_states(&find_packs(output_dir)));
+
+    println!(
+        "Watching '{}' for changed packs, press Ctrl+C to stop",
+        input_dir.display()
+    );
+
+    while *overwrite != Overwrite::Abort {
+        thread::sleep(WATCH_INTERVAL);
+
+        let changed = watcher.poll(file_states(&find_packs(input_dir)));
+        if changed.is_empty() {
+            continue;
+        }
+
+        let result = handle_packs(&changed, output_dir, options, overwrite);
+        println!("{} changed packs processed", result.processed_count);
+
+        watcher.record_written(file_states(&find_packs(output_dir)));
+    }
+}
+
+/// Recursively finds all the `*.dungeondraft_pack` files in the directory.
+pub fn find_packs(input_dir: &Path) -> Vec<PathBuf> {
     let input_glob = String::new() + input_dir.to_str().unwrap() + "/**/*" + ASSET_PACK_EXTENSION;
 
-    let mut pack_count = 0;
+    let mut packs = vec![];
 
     for entry in glob(&input_glob).expect("Glob pattern could not be parsed") {
         match entry {
-            Ok(path) => {
-                pack_count += 1;
-                handle_pack(&path, &output_dir, overwrite_allowed);
-
-                info!("");
-                info!("---------------------------------------------");
-                info!("");
-            }
+            Ok(path) => packs.push(path),
             Err(e) => warn!("{}", e),
         }
     }
 
-    println!("{} packs encountered", pack_count);
-    println!("Done");
+    packs
 }
 
-fn output_dir_valid_or_exit(input_dir: &PathBuf, output_dir: &PathBuf) {
+fn output_dir_valid_or_exit(input_dir: &Path, output_dir: &Path) {
     if input_dir.exists() && output_dir.exists() {
         let canonical_input = input_dir.canonicalize().unwrap();
         let canonical_output = output_dir.canonicalize().unwrap();
@@ -111,57 +768,595 @@ fn output_dir_valid_or_exit(input_dir: &PathBuf, output_dir: &PathBuf) {
     }
 }
 
-fn input_dir_valid_or_exit(input_dir: &PathBuf) {
+/// The options of the main flow, which processes every pack in the input directory.
+fn process_options_or_exit(matches: &ArgMatches) -> ProcessOptions {
+    ProcessOptions {
+        no_clean: matches.is_present("no_clean"),
+        clean_options: CleanOptions {
+            keep_external_refs: matches.is_present("keep_external_refs"),
+        },
+        ref_check_external: matches.is_present("ref_check_external"),
+        prune_unreferenced: matches.is_present("prune_unreferenced"),
+        dedupe_objects: matches.is_present("dedupe_objects"),
+        strip_thumbnails: matches.is_present("strip_thumbnails"),
+        enforce_name_match: matches.is_present("enforce_name_match"),
+        repair_names: matches.is_present("repair_names"),
+        bump_version: matches.is_present("bump_version"),
+        reset_color_overrides: matches.is_present("reset_color_overrides"),
+        stamp: matches.is_present("stamp"),
+        no_stamp: matches.is_present("no_stamp"),
+        output_format: match matches.value_of("output_format") {
+            Some("dir") => OutputFormat::Dir,
+            _ => OutputFormat::Pack,
+        },
+        write_options: WriteOptions {
+            sort_files_by_path: matches.is_present("sort_files_by_path"),
+            preserve_file_order: matches.is_present("preserve_file_order"),
+            pretty_json: matches.is_present("pretty"),
+            sort_tags: matches.is_present("sort_tag_members"),
+            md5_threads: if matches.is_present("write_md5") {
+                Some(threads_or_exit(matches))
+            } else {
+                None
+            },
+            alignment: alignment_or_exit(matches),
+            embed_tags: matches.is_present("embed_tags"),
+            share_metadata_offset: matches.is_present("share_metadata_offset"),
+            strict_tags: matches.is_present("strict_tags"),
+            compress: matches.is_present("compress"),
+        },
+        read_options: read_options_or_exit(matches),
+        buffer_size: buffer_size_or_exit(matches),
+        mmap: matches.is_present("mmap"),
+        tag_patterns: tag_patterns_or_exit(matches),
+        clear_tags: matches.is_present("clear_tags"),
+        rename_sets: renames_or_exit(matches, "rename_set"),
+        auto_set: matches.value_of("auto_set").map(str::to_owned),
+        #[cfg(feature = "image-codec")]
+        convert_objects_to: matches.value_of("convert_objects_to").map(str::to_owned),
+        strict: matches.is_present("strict"),
+        tag_prefix: matches.value_of("tag_prefix").map(str::to_owned),
+        auto_tag_from_folders: matches.is_present("auto_tag_from_folders"),
+        exclude_tags: matches
+            .values_of("exclude_tag")
+            .map_or(vec![], |tags| tags.map(str::to_owned).collect()),
+        summary_only: matches.is_present("summary_only"),
+        dry_run: matches.is_present("dry_run"),
+        clean_report: matches.value_of("clean_report").map(PathBuf::from),
+    }
+}
+
+/// How to read packs. Of these options, only `--max-memory` is global, so the
+/// subcommands get the defaults for the rest.
+fn read_options_or_exit(matches: &ArgMatches) -> ReadOptions {
+    ReadOptions {
+        classification_rules: ClassificationRules::with_object_prefixes(
+            matches
+                .values_of("object_prefix")
+                .into_iter()
+                .flatten()
+                .map(str::to_owned),
+        ),
+        check_meta_consistency: matches.is_present("ensure_pack_json_consistency"),
+        prefer_pack_json: matches.is_present("prefer_pack_json"),
+        header_len: header_len_or_exit(matches),
+        max_memory: max_memory_or_exit(matches),
+    }
+}
+
+pub fn buffer_size_or_exit(matches: &ArgMatches) -> usize {
+    match matches.value_of("buffer_size").unwrap().parse::<usize>() {
+        Ok(size) if size > 0 => size,
+        _ => {
+            error!("The buffer size should be a positive number.");
+            exit(1);
+        }
+    }
+}
+
+/// Parses the value of `--max-memory`: the most bytes the files of a pack may add up to.
+fn max_memory_or_exit(matches: &ArgMatches) -> Option<u64> {
+    let value = matches.value_of("max_memory")?;
+
+    match value.parse::<u64>() {
+        Ok(max_memory) => Some(max_memory),
+        Err(_) => {
+            error!(
+                "The maximum memory should be a number of bytes, not '{}'.",
+                value
+            );
+            exit(1);
+        }
+    }
+}
+
+pub(crate) fn threads_or_exit(matches: &ArgMatches) -> usize {
+    match matches.value_of("threads").unwrap().parse::<usize>() {
+        Ok(threads) if threads > 0 => threads,
+        _ => {
+            error!("The number of threads should be a positive number.");
+            exit(1);
+        }
+    }
+}
+
+fn alignment_or_exit(matches: &ArgMatches) -> Option<usize> {
+    let value = matches.value_of("align")?;
+
+    match value.parse::<usize>() {
+        Ok(alignment) if alignment > 0 => Some(alignment),
+        _ => {
+            error!(
+                "The alignment should be a positive number, not '{}'.",
+                value
+            );
+            exit(1);
+        }
+    }
+}
+
+/// Parses the value of `--assume-magic`.
+fn header_len_or_exit(matches: &ArgMatches) -> Option<usize> {
+    let value = matches.value_of("assume_magic")?;
+
+    match value.parse::<usize>() {
+        Ok(header_len) => Some(header_len),
+        Err(_) => {
+            error!("The header length should be a number, not '{}'.", value);
+            exit(1);
+        }
+    }
+}
+
+/// Parses the `GLOB=TAG` values of `--tag-pattern`.
+fn tag_patterns_or_exit(matches: &ArgMatches) -> Vec<(String, String)> {
+    let mut patterns = vec![];
+
+    for value in matches.values_of("tag_pattern").into_iter().flatten() {
+        match value.rsplit_once('=') {
+            Some((glob, tag)) if !tag.is_empty() && glob::Pattern::new(glob).is_ok() => {
+                patterns.push((glob.to_owned(), tag.to_owned()))
+            }
+            _ => {
+                error!(
+                    "Invalid tag pattern '{}', it should look like `GLOB=TAG`.",
+                    value
+                );
+                exit(1);
+            }
+        }
+    }
+
+    patterns
+}
+
+/// Parses the `OLD=NEW` values of an option like `--rename-set`.
+fn renames_or_exit(matches: &ArgMatches, name: &str) -> Vec<(String, String)> {
+    let mut renames = vec![];
+
+    for value in matches.values_of(name).into_iter().flatten() {
+        match value.split_once('=') {
+            Some((old, new)) if !old.is_empty() && !new.is_empty() => {
+                renames.push((old.to_owned(), new.to_owned()))
+            }
+            _ => {
+                error!("Invalid rename '{}', it should look like `OLD=NEW`.", value);
+                exit(1);
+            }
+        }
+    }
+
+    renames
+}
+
+pub fn input_dir_valid_or_exit(input_dir: &Path) {
     if !input_dir.exists() {
         error!("Input directory '{}' does not exist.", input_dir.display());
         exit(1);
     }
 }
 
-fn handle_pack(pack_path: &PathBuf, output_dir: &PathBuf, overwrite_allowed: bool) {
-    println!("Handling {}", pack_path.display());
+/// Handles the packs one by one, until the user chooses to stop.
+/// Returns how many packs were processed, their stats added together and what was cleaned.
+fn handle_packs(
+    packs: &[PathBuf],
+    output_dir: &Path,
+    options: &ProcessOptions,
+    overwrite: &mut Overwrite,
+) -> BatchResult {
+    let mut result = BatchResult::default();
+
+    for path in packs {
+        if let Some((stats, clean_report)) = handle_pack(path, output_dir, options, overwrite) {
+            result.processed_count += 1;
+            result.totals.add(&stats);
+            result
+                .clean_reports
+                .insert(path.display().to_string(), clean_report);
+        }
+
+        if *overwrite == Overwrite::Abort {
+            println!("Stopping at user request");
+            break;
+        }
+
+        info!("");
+        info!("---------------------------------------------");
+        info!("");
+    }
+
+    result
+}
+
+/// Writes the clean reports of all packs to a json file.
+fn write_clean_report(path: &Path, reports: &BTreeMap<String, CleanReport>) -> Result<()> {
+    fs::write(path, json5::to_string(reports)?)
+        .context(format!("Could not write '{}'", path.display()))
+}
 
-    let mut pack = match read_pack(&pack_path) {
+/// Prints the references to files that are not in the pack, one per line.
+fn print_external_refs(report: &CleanReport) {
+    for (tag, files) in report.kept_references.iter() {
+        for file in files {
+            println!("Tag '{}' refers to missing '{}' (kept)", tag, file);
+        }
+    }
+    for (tag, files) in report.removed_references.iter() {
+        for file in files {
+            println!("Tag '{}' refers to missing '{}' (removed)", tag, file);
+        }
+    }
+}
+
+/// Returns `None` if the pack could not be read.
+fn handle_pack(
+    pack_path: &Path,
+    output_dir: &Path,
+    options: &ProcessOptions,
+    overwrite: &mut Overwrite,
+) -> Option<(PackStats, CleanReport)> {
+    if !options.summary_only {
+        println!("Handling {}", pack_path.display());
+    }
+
+    let mut pack = match read_pack_maybe_mapped(
+        pack_path,
+        options.buffer_size,
+        options.mmap,
+        &options.read_options,
+    ) {
         Ok(p) => p,
         Err(e) => {
             warn!("Could not read packfile '{}':\n{}", pack_path.display(), e);
-            return;
+            return None;
         }
     };
 
-    info!("Godot package version: {}", pack.godot_version);
-    info!(
-        "Files in package: {}",
-        pack.object_files.len() + pack.other_files.len()
-    );
+    if !options.summary_only {
+        info!("Godot package version: {}", pack.godot_version);
+        info!(
+            "Files in package: {}",
+            pack.object_files.len() + pack.other_files.len()
+        );
+
+        info!("Pack name: {}", pack.meta.name);
+        info!("Pack author: {}", pack.meta.author);
+        info!("Pack version: {}", pack.meta.version);
+        info!("Pack id: {}", pack.meta.id);
+
+        debug!("{}", pack.tags);
+    }
+
+    for tag in options.exclude_tags.iter() {
+        let removed = pack.exclude_tag(tag);
+        info!("Excluded tag '{}', removing {} object files.", tag, removed);
+    }
+
+    let clean_report = if options.no_clean {
+        CleanReport::default()
+    } else {
+        let report = pack.clean_tags_with_options(&options.clean_options);
+
+        if options.ref_check_external {
+            print_external_refs(&report);
+        }
+
+        if !options.summary_only {
+            debug!("After cleaning\n{}", pack.tags);
+        }
+
+        report
+    };
+
+    let mut stats = PackStats {
+        tags_removed: clean_report.removed_tags.len(),
+        sets_removed: clean_report.removed_sets.len(),
+        ..Default::default()
+    };
+
+    if options.prune_unreferenced {
+        if let Err(e) = pack.remove_unreferenced_files() {
+            warn!(
+                "Not pruning the unreferenced files of '{}':\n{:#}",
+                pack.meta.name, e
+            );
+        }
+    }
 
-    info!("Pack name: {}", pack.meta.name);
-    info!("Pack author: {}", pack.meta.author);
-    info!("Pack version: {}", pack.meta.version);
-    info!("Pack id: {}", pack.meta.id);
+    if options.dedupe_objects {
+        pack.dedupe_objects();
+    }
+
+    if options.strip_thumbnails {
+        pack.strip_thumbnails();
+    }
+
+    if options.repair_names {
+        pack.repair_names();
+    }
+
+    if options.clear_tags {
+        pack.tags.clear();
+        info!("Removed all tags and tag sets.");
+    }
+
+    if let Some(prefix) = &options.tag_prefix {
+        pack.prefix_tags(prefix);
+    }
 
-    debug!("{}", pack.tags);
+    if options.auto_tag_from_folders {
+        let tagged = pack.tag_from_folders();
+        info!("Tagged {} files after their directory.", tagged);
+    }
 
-    pack.clean_tags();
+    for (glob, tag) in options.tag_patterns.iter() {
+        let tagged = pack.tag_by_pattern(glob, tag);
+        info!("Tagged {} files matching '{}' as '{}'.", tagged, glob, tag);
+    }
 
-    debug!("After cleaning\n{}", pack.tags);
+    for (old, new) in options.rename_sets.iter() {
+        match pack.tags.rename_set(old, new) {
+            Ok(true) => info!("Renamed tag set '{}' to '{}'.", old, new),
+            Ok(false) if options.strict => {
+                warn!(
+                    "Skipping '{}', it has no tag set named '{}'.",
+                    pack_path.display(),
+                    old
+                );
+                return None;
+            }
+            Ok(false) => warn!("There is no tag set named '{}' to rename.", old),
+            Err(e) => {
+                warn!("Skipping '{}': {}", pack_path.display(), e);
+                return None;
+            }
+        }
+    }
 
-    let mut output_path = output_dir.clone();
-    output_path.push(pack_path.file_name().unwrap());
+    if let Some(set) = &options.auto_set {
+        pack.add_setless_tags_to_set(set);
+    }
 
-    write_pack(&pack, &output_path, overwrite_allowed);
+    #[cfg(feature = "image-codec")]
+    if let Some(format) = &options.convert_objects_to {
+        match pack.convert_objects(format, &ImageCodec) {
+            Ok(converted) => info!("Converted {} object files to {}.", converted, format),
+            Err(e) => {
+                warn!("Skipping '{}': {:#}", pack_path.display(), e);
+                return None;
+            }
+        }
+    }
+
+    if options.reset_color_overrides {
+        pack.meta.reset_color_overrides();
+        info!("Reset the color overrides to the defaults.");
+    }
+
+    if options.bump_version {
+        pack.meta.bump_version();
+        info!("Bumped pack version to: {}", pack.meta.version);
+    }
+
+    if options.stamp {
+        pack.meta.stamp(
+            SystemTime::now(),
+            concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")),
+        );
+    } else if options.no_stamp {
+        pack.meta.remove_stamp();
+    }
+
+    match options.output_format {
+        OutputFormat::Pack => {
+            let mut output_path = output_dir.to_path_buf();
+            output_path.push(pack_path.file_name().unwrap());
+
+            if options.enforce_name_match {
+                if let Some(warning) = name_mismatch_warning(&pack, &output_path) {
+                    warn!("{}", warning);
+                }
+            }
+
+            if let Some(bytes) = write_pack(
+                &pack,
+                &output_path,
+                &options.write_options,
+                options.buffer_size,
+                overwrite,
+                options.dry_run,
+            ) {
+                stats.files_written = 1;
+                stats.bytes_written = bytes;
+            }
+        }
+        OutputFormat::Dir => {
+            let mut output_path = output_dir.to_path_buf();
+            output_path.push(pack_path.file_stem().unwrap());
+
+            if let Some(written) = unpack_pack(&pack, &output_path, overwrite, options.dry_run) {
+                stats.files_written = written.files;
+                stats.bytes_written = written.bytes;
+            }
+        }
+    }
+
+    Some((stats, clean_report))
+}
+
+/// Returns what was written, or `None` if nothing was.
+fn unpack_pack(
+    pack: &AssetPack,
+    output_path: &Path,
+    overwrite: &mut Overwrite,
+    dry_run: bool,
+) -> Option<UnpackStats> {
+    if dry_run {
+        let files = pack.object_files.len() + pack.other_files.len() + 2;
+        println!(
+            "Would unpack {} files to '{}'",
+            files,
+            output_path.display()
+        );
+        return None;
+    }
+
+    if output_path.exists()
+        && !overwrite.allows(output_path, &mut io::stdin().lock(), &mut io::stdout())
+    {
+        if *overwrite == Overwrite::Never {
+            warn!(
+                "Output directory '{}' already exists. If you want to overwrite, call again with the `-F` argument.",
+                output_path.display()
+            );
+        }
+        return None;
+    }
+
+    let options = UnpackOptions {
+        overwrite: true,
+        ..Default::default()
+    };
+
+    match pack.unpack_to_dir(output_path, &options) {
+        Ok(written) => Some(written),
+        Err(e) => {
+            warn!(
+                "Something went wrong while unpacking to '{}':\n{}",
+                output_path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Reads a pack the way the global options ask: with `--buffer-size`,
+/// through a memory map with `--mmap`, and up to `--max-memory`.
+fn read_pack(path: &Path, matches: &ArgMatches) -> Result<AssetPack> {
+    read_pack_maybe_mapped(
+        path,
+        buffer_size_or_exit(matches),
+        matches.is_present("mmap"),
+        &read_options_or_exit(matches),
+    )
 }
 
-fn read_pack(path: &PathBuf) -> Result<AssetPack> {
+fn read_pack_with_options(
+    path: &Path,
+    buffer_size: usize,
+    options: &ReadOptions,
+) -> Result<AssetPack> {
     info!("Reading pack file '{}'", path.display());
 
-    let mut file =
-        File::open(&path).context(format!("Could not open pack file '{}'", path.display()))?;
+    let file =
+        File::open(path).context(format!("Could not open pack file '{}'", path.display()))?;
+
+    AssetPack::from_read_with_options(&mut BufReader::with_capacity(buffer_size, file), options)
+}
+
+/// Like `read_pack_with_options`, but reads through a memory map if `mmap` is set
+/// and this was built with the `mmap` feature.
+fn read_pack_maybe_mapped(
+    path: &Path,
+    buffer_size: usize,
+    mmap: bool,
+    options: &ReadOptions,
+) -> Result<AssetPack> {
+    #[cfg(all(feature = "mmap", unix))]
+    if mmap {
+        info!("Mapping pack file '{}'", path.display());
 
-    asset_pack::asset_pack::AssetPack::from_read(&mut file)
+        let mapped = MappedFile::open(path)
+            .context(format!("Could not map pack file '{}'", path.display()))?;
+
+        return AssetPack::from_read_with_options(&mut Cursor::new(&*mapped), options);
+    }
+    #[cfg(not(all(feature = "mmap", unix)))]
+    let _ = mmap;
+
+    read_pack_with_options(path, buffer_size, options)
+}
+
+/// Returns a warning if the file name contains neither the pack id nor the pack name,
+/// see `--enforce-name-match`. The name only has to match in its letters and digits,
+/// so `My Pack` matches `my_pack_v2.dungeondraft_pack`.
+fn name_mismatch_warning(pack: &AssetPack, output_path: &Path) -> Option<String> {
+    let file_name = sanitize_for_name_match(&output_path.file_stem()?.to_string_lossy());
+    let pack_id = sanitize_for_name_match(&pack.meta.id);
+    let pack_name = sanitize_for_name_match(&pack.meta.name);
+
+    let matches_id = !pack_id.is_empty() && file_name.contains(&pack_id);
+    let matches_name = !pack_name.is_empty() && file_name.contains(&pack_name);
+
+    if matches_id || matches_name {
+        None
+    } else {
+        Some(format!(
+            "Output file '{}' contains neither the pack id '{}' nor the pack name '{}'.",
+            output_path.display(),
+            pack.meta.id,
+            pack.meta.name
+        ))
+    }
+}
+
+fn sanitize_for_name_match(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
 }
 
-fn write_pack(pack: &AssetPack, output_path: &PathBuf, overwrite_allowed: bool) {
+/// Writes the pack to `output_path`, or only prints what would be written if `dry_run`.
+/// Returns the size of the pack file, also when it was not written because of `dry_run`.
+pub fn write_pack(
+    pack: &AssetPack,
+    output_path: &Path,
+    write_options: &WriteOptions,
+    buffer_size: usize,
+    overwrite: &mut Overwrite,
+    dry_run: bool,
+) -> Option<u64> {
+    if dry_run {
+        return match pack.change_set(write_options) {
+            Ok(change_set) => {
+                println!("Would write {} to '{}'", change_set, output_path.display());
+                Some(change_set.bytes)
+            }
+            Err(e) => {
+                warn!("Could not serialize pack '{}':\n{}", pack.meta.name, e);
+                None
+            }
+        };
+    }
+
+    // Checked before the output file is created, so a refused pack leaves nothing behind.
+    if let Err(e) = pack.check_write_options(write_options) {
+        warn!("Not writing pack '{}':\n{}", output_path.display(), e);
+        return None;
+    }
+
     info!(
         "Saving pack '{}' to '{}",
         pack.meta.name,
@@ -169,37 +1364,550 @@ fn write_pack(pack: &AssetPack, output_path: &PathBuf, overwrite_allowed: bool)
     );
 
     if output_path.exists() {
-        if overwrite_allowed {
+        if overwrite.allows(output_path, &mut io::stdin().lock(), &mut io::stdout()) {
             info!("Overwriting '{}'.", output_path.display())
         } else {
-            warn!(
-                "Output file '{}' already exists. If you want to overwrite, call again with the `-F` argument.",
-                output_path.display()
-            );
-            return;
+            if *overwrite == Overwrite::Never {
+                warn!(
+                    "Output file '{}' already exists. If you want to overwrite, call again with the `-F` argument.",
+                    output_path.display()
+                );
+            }
+            return None;
         }
     }
 
-    let mut file = match File::create(&output_path) {
-        Ok(f) => f,
+    let mut file = match File::create(output_path) {
+        Ok(f) => BufWriter::with_capacity(buffer_size, f),
         Err(e) => {
             warn!(
                 "Could not create the output file '{}':\n{}",
                 output_path.display(),
                 e
             );
-            return;
+            return None;
         }
     };
 
-    match pack.to_write(&mut file) {
-        Ok(_) => {}
+    match pack
+        .to_write_with_options(&mut file, write_options)
+        .and_then(|_| Ok(file.stream_position()?))
+    {
+        Ok(bytes) => Some(bytes),
         Err(e) => {
             warn!(
                 "Something went wrong while writing the pack file '{}':\n{}",
                 output_path.display(),
                 e
             );
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashSet};
+    use std::fs;
+    use std::fs::File;
+    use std::iter::FromIterator;
+    use std::path::Path;
+
+    use dd_asset_tools::asset_pack::{merge_packs, AssetPack, CleanReport};
+
+    use crate::overwrite::Overwrite;
+    use crate::{
+        app, commands, handle_pack, handle_packs, name_mismatch_warning, process_options_or_exit,
+        read_pack, write_clean_report, write_pack, OutputFormat, PackStats, ProcessOptions,
+    };
+
+    fn write_example_pack(dir: &Path) -> std::path::PathBuf {
+        let source_dir = dir.join("source");
+        fs::create_dir_all(source_dir.join("textures/objects")).unwrap();
+        fs::write(
+            source_dir.join("pack.json"),
+            r#"{"name": "example", "id": "12345678", "version": "1", "author": "me"}"#,
+        )
+        .unwrap();
+        fs::write(source_dir.join("textures/objects/rock.png"), [1, 2, 3]).unwrap();
+        fs::create_dir_all(source_dir.join("data")).unwrap();
+        fs::write(
+            source_dir.join("data/default.dungeondraft_tags"),
+            r#"{
+                "tags": {
+                    "Rocks": ["textures/objects/rock.png"],
+                    "Gone": ["textures/objects/gone.png"],
+                    "Placeholder": []
+                },
+                "sets": {"Missing": ["Gone"], "Example Set": ["Rocks"]}
+            }"#,
+        )
+        .unwrap();
+
+        let pack = AssetPack::from_directory(&source_dir).unwrap();
+        let pack_path = dir.join("example.dungeondraft_pack");
+        pack.to_write(&mut File::create(&pack_path).unwrap())
+            .unwrap();
+
+        pack_path
+    }
+
+    fn options(output_format: OutputFormat) -> ProcessOptions {
+        ProcessOptions {
+            no_clean: false,
+            clean_options: Default::default(),
+            ref_check_external: false,
+            prune_unreferenced: false,
+            dedupe_objects: false,
+            strip_thumbnails: false,
+            repair_names: false,
+            bump_version: false,
+            reset_color_overrides: false,
+            stamp: false,
+            no_stamp: false,
+            output_format,
+            write_options: Default::default(),
+            read_options: Default::default(),
+            buffer_size: 1024,
+            mmap: false,
+            tag_patterns: vec![],
+            tag_prefix: None,
+            auto_tag_from_folders: false,
+            exclude_tags: vec![],
+            clear_tags: false,
+            rename_sets: vec![],
+            auto_set: None,
+            #[cfg(feature = "image-codec")]
+            convert_objects_to: None,
+            strict: false,
+            enforce_name_match: false,
+            summary_only: false,
+            dry_run: false,
+            clean_report: None,
+        }
+    }
+
+    #[test]
+    fn process_options_repair_names() {
+        let parse = |args: &[&str]| {
+            let matches = app()
+                .get_matches_from_safe(["dd_asset_tools", "input", "output"].iter().chain(args))
+                .unwrap();
+            process_options_or_exit(&matches)
+        };
+
+        assert!(!parse(&[]).repair_names);
+        assert!(parse(&["--repair-names"]).repair_names);
+    }
+
+    #[test]
+    fn handle_pack_output_formats() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+
+        let pack_output = dir.path().join("pack_output");
+        fs::create_dir(&pack_output).unwrap();
+        handle_pack(
+            &pack_path,
+            &pack_output,
+            &options(OutputFormat::Pack),
+            &mut Overwrite::Never,
+        );
+        assert!(pack_output.join("example.dungeondraft_pack").is_file());
+
+        let dir_output = dir.path().join("dir_output");
+        handle_pack(
+            &pack_path,
+            &dir_output,
+            &options(OutputFormat::Dir),
+            &mut Overwrite::Never,
+        );
+        assert!(dir_output.join("example/pack.json").is_file());
+        assert!(dir_output
+            .join("example/textures/objects/rock.png")
+            .is_file());
+    }
+
+    #[test]
+    fn handle_pack_no_clean_keeps_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+
+        let mut options = options(OutputFormat::Pack);
+        options.no_clean = true;
+
+        let output = dir.path().join("output");
+        fs::create_dir(&output).unwrap();
+        let (stats, _) = handle_pack(&pack_path, &output, &options, &mut Overwrite::Never).unwrap();
+
+        assert_eq!(stats.tags_removed, 0);
+        assert_eq!(stats.sets_removed, 0);
+
+        let written = AssetPack::from_read(
+            &mut File::open(output.join("example.dungeondraft_pack")).unwrap(),
+        )
+        .unwrap();
+        assert!(written.tags.tags.contains_key("Gone"));
+        assert!(written.tags.tags["Placeholder"].is_empty());
+        assert!(written.tags.sets.contains_key("Missing"));
+    }
+
+    #[test]
+    fn handle_pack_compress_refuses_godot_3_packs() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+
+        let mut options = options(OutputFormat::Pack);
+        options.write_options.compress = true;
+
+        let output = dir.path().join("output");
+        fs::create_dir(&output).unwrap();
+        let (stats, _) = handle_pack(&pack_path, &output, &options, &mut Overwrite::Never).unwrap();
+
+        assert_eq!(stats.files_written, 0);
+        assert!(!output.join("example.dungeondraft_pack").exists());
+    }
+
+    #[test]
+    fn handle_pack_strict_tags_refuses_dangling_references() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+
+        let mut options = options(OutputFormat::Pack);
+        options.no_clean = true;
+        options.write_options.strict_tags = true;
+
+        let output = dir.path().join("output");
+        fs::create_dir(&output).unwrap();
+        let (stats, _) = handle_pack(&pack_path, &output, &options, &mut Overwrite::Never).unwrap();
+
+        assert_eq!(stats.files_written, 0);
+        assert!(!output.join("example.dungeondraft_pack").exists());
+
+        let pack = AssetPack::from_read(&mut File::open(&pack_path).unwrap()).unwrap();
+        let error = pack
+            .to_write_with_options(&mut vec![], &options.write_options)
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("'textures/objects/gone.png' in tag 'Gone'"));
+
+        // Cleaning removes the dangling reference, so then it is written.
+        options.no_clean = false;
+        let (stats, _) = handle_pack(&pack_path, &output, &options, &mut Overwrite::Never).unwrap();
+        assert_eq!(stats.files_written, 1);
+    }
+
+    #[cfg(feature = "image-codec")]
+    #[test]
+    fn handle_pack_convert_objects_skips_unreadable_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+        let matches = app()
+            .get_matches_from_safe(
+                [
+                    "dd_asset_tools",
+                    "input",
+                    "output",
+                    "--convert-objects-to",
+                    "webp",
+                ]
+                .iter(),
+            )
+            .unwrap();
+        let options = process_options_or_exit(&matches);
+        assert_eq!(options.convert_objects_to, Some("webp".to_string()));
+
+        // The rock is no real png.
+        let output = dir.path().join("output");
+        fs::create_dir(&output).unwrap();
+        assert!(handle_pack(&pack_path, &output, &options, &mut Overwrite::Never).is_none());
+        assert!(!output.join("example.dungeondraft_pack").exists());
+    }
+
+    #[test]
+    fn handle_pack_reset_color_overrides() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+
+        let mut options = options(OutputFormat::Pack);
+        options.reset_color_overrides = true;
+
+        let output = dir.path().join("output");
+        fs::create_dir(&output).unwrap();
+        handle_pack(&pack_path, &output, &options, &mut Overwrite::Never).unwrap();
+
+        let output_path = output.join("example.dungeondraft_pack");
+        let pack_json =
+            AssetPack::read_single_file(&mut File::open(&output_path).unwrap(), "pack.json")
+                .unwrap()
+                .unwrap();
+        assert!(String::from_utf8(pack_json)
+            .unwrap()
+            .contains(r#""custom_color_overrides":{"enabled":false,"#));
+
+        let written = AssetPack::from_read(&mut File::open(&output_path).unwrap()).unwrap();
+        let overrides = written.meta.custom_color_overrides.unwrap();
+        assert!(!overrides.enabled);
+        assert_eq!(overrides.min_redness, 0.1);
+        assert_eq!(overrides.min_saturation, 0.0);
+        assert_eq!(overrides.red_tolerance, 0.04);
+    }
+
+    #[test]
+    fn read_pack_honors_global_max_memory() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+        let pack_arg = pack_path.to_str().unwrap();
+
+        let read_with = |args: &[&str]| {
+            let matches = app()
+                .get_matches_from_safe(["dd_asset_tools", "stats", pack_arg].iter().chain(args))
+                .unwrap();
+            read_pack(&pack_path, matches.subcommand_matches("stats").unwrap())
+        };
+
+        assert!(read_with(&[]).is_ok());
+        assert!(read_with(&["--max-memory", "1"]).is_err());
+    }
+
+    #[test]
+    fn write_pack_dry_run_merge() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+
+        let first = AssetPack::from_read(&mut File::open(&pack_path).unwrap()).unwrap();
+        let mut second = AssetPack::from_read(&mut File::open(&pack_path).unwrap()).unwrap();
+        second
+            .move_file("textures/objects/rock.png", "textures/objects/stone.png")
+            .unwrap();
+        let merged = merge_packs(vec![first, second]).unwrap();
+
+        let output_path = dir.path().join("merged.dungeondraft_pack");
+        let bytes = write_pack(
+            &merged,
+            &output_path,
+            &Default::default(),
+            1024,
+            &mut Overwrite::Never,
+            true,
+        );
+
+        assert!(!output_path.exists());
+        let change_set = merged.change_set(&Default::default()).unwrap();
+        assert_eq!(change_set.files, 5);
+        assert_eq!(bytes, Some(change_set.bytes));
+
+        // The merge command itself stops after the collision report.
+        let first = AssetPack::from_read(&mut File::open(&pack_path).unwrap()).unwrap();
+        let second = AssetPack::from_read(&mut File::open(&pack_path).unwrap()).unwrap();
+        assert!(commands::merge::merge_unless_dry_run(vec![first, second], true).is_none());
+
+        let pack_arg = pack_path.to_str().unwrap();
+        let output_arg = output_path.to_str().unwrap();
+        let matches = app()
+            .get_matches_from_safe(vec![
+                "dd_asset_tools",
+                "merge",
+                pack_arg,
+                pack_arg,
+                "--output",
+                output_arg,
+                "--dry-run",
+            ])
+            .unwrap();
+        commands::merge::run(matches.subcommand_matches("merge").unwrap());
+        assert!(!output_path.exists());
+    }
+
+    #[test]
+    fn handle_pack_clear_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+
+        let mut options = options(OutputFormat::Pack);
+        options.clear_tags = true;
+
+        let output = dir.path().join("output");
+        fs::create_dir(&output).unwrap();
+        handle_pack(&pack_path, &output, &options, &mut Overwrite::Never).unwrap();
+
+        let output_path = output.join("example.dungeondraft_pack");
+        let tags_file = AssetPack::read_single_file(
+            &mut File::open(&output_path).unwrap(),
+            "data/default.dungeondraft_tags",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(tags_file).unwrap(),
+            r#"{"tags":{},"sets":{}}"#
+        );
+
+        let written = AssetPack::from_read(&mut File::open(&output_path).unwrap()).unwrap();
+        assert!(written.tags.tags.is_empty());
+        assert!(written.tags.sets.is_empty());
+        assert_eq!(
+            written.object_files["textures/objects/rock.png"],
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn handle_pack_rename_set() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+
+        let mut options = options(OutputFormat::Pack);
+        options.rename_sets = vec![("Example Set".to_string(), "Renamed".to_string())];
+
+        let output = dir.path().join("output");
+        fs::create_dir(&output).unwrap();
+        handle_pack(&pack_path, &output, &options, &mut Overwrite::Never).unwrap();
+
+        let written = AssetPack::from_read(
+            &mut File::open(output.join("example.dungeondraft_pack")).unwrap(),
+        )
+        .unwrap();
+        assert!(!written.tags.sets.contains_key("Example Set"));
+        assert_eq!(
+            written.tags.sets["Renamed"],
+            HashSet::from_iter(vec!["Rocks".to_string()])
+        );
+
+        options.rename_sets = vec![("Missing".to_string(), "Renamed".to_string())];
+        options.strict = true;
+        let strict_output = dir.path().join("strict_output");
+        fs::create_dir(&strict_output).unwrap();
+        assert!(handle_pack(&pack_path, &strict_output, &options, &mut Overwrite::Never).is_none());
+    }
+
+    #[test]
+    fn handle_pack_stamp() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+
+        let mut options = options(OutputFormat::Pack);
+        options.stamp = true;
+
+        let output = dir.path().join("output");
+        fs::create_dir(&output).unwrap();
+        handle_pack(&pack_path, &output, &options, &mut Overwrite::Never).unwrap();
+
+        let output_path = output.join("example.dungeondraft_pack");
+        let pack_json =
+            AssetPack::read_single_file(&mut File::open(&output_path).unwrap(), "pack.json")
+                .unwrap()
+                .unwrap();
+        let pack_json = String::from_utf8(pack_json).unwrap();
+        assert!(pack_json.contains(r#""build_timestamp":"#));
+        assert!(pack_json.contains(&format!(
+            r#""tool_version":"{} {}""#,
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        )));
+
+        options.stamp = false;
+        options.no_stamp = true;
+        let unstamped = dir.path().join("unstamped");
+        fs::create_dir(&unstamped).unwrap();
+        handle_pack(&output_path, &unstamped, &options, &mut Overwrite::Never).unwrap();
+
+        let written = AssetPack::from_read(
+            &mut File::open(unstamped.join("example.dungeondraft_pack")).unwrap(),
+        )
+        .unwrap();
+        assert!(written.meta.extra.is_empty());
+    }
+
+    #[test]
+    fn handle_packs_adds_up_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+        let other_pack_path = dir.path().join("other.dungeondraft_pack");
+        fs::copy(&pack_path, &other_pack_path).unwrap();
+        let packs = vec![pack_path, other_pack_path];
+
+        let mut options = options(OutputFormat::Pack);
+        options.summary_only = true;
+
+        let single_output = dir.path().join("single_output");
+        fs::create_dir(&single_output).unwrap();
+        let mut expected = PackStats::default();
+        for path in packs.iter() {
+            let (stats, _) =
+                handle_pack(path, &single_output, &options, &mut Overwrite::Never).unwrap();
+            assert_eq!(stats.tags_removed, 2);
+            assert_eq!(stats.sets_removed, 1);
+            assert_eq!(stats.files_written, 1);
+            assert!(stats.bytes_written > 0);
+            expected.add(&stats);
         }
+
+        let output = dir.path().join("output");
+        fs::create_dir(&output).unwrap();
+        let result = handle_packs(&packs, &output, &options, &mut Overwrite::Never);
+        let totals = result.totals;
+
+        assert_eq!(result.processed_count, 2);
+        assert_eq!(totals, expected);
+        assert_eq!(totals.tags_removed, 4);
+        assert_eq!(
+            totals.bytes_written,
+            fs::metadata(output.join("example.dungeondraft_pack"))
+                .unwrap()
+                .len()
+                + fs::metadata(output.join("other.dungeondraft_pack"))
+                    .unwrap()
+                    .len()
+        );
+    }
+
+    #[test]
+    fn write_clean_report_lists_removed_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+        let output = dir.path().join("output");
+        fs::create_dir(&output).unwrap();
+
+        let result = handle_packs(
+            std::slice::from_ref(&pack_path),
+            &output,
+            &options(OutputFormat::Pack),
+            &mut Overwrite::Never,
+        );
+
+        let report_path = dir.path().join("report.json");
+        write_clean_report(&report_path, &result.clean_reports).unwrap();
+
+        let report: BTreeMap<String, CleanReport> =
+            json5::from_str(&fs::read_to_string(&report_path).unwrap()).unwrap();
+        let pack_report = &report[&pack_path.display().to_string()];
+
+        assert_eq!(pack_report.removed_tags, vec!["Gone", "Placeholder"]);
+        assert_eq!(pack_report.removed_sets, vec!["Missing"]);
+        assert_eq!(
+            pack_report.removed_references["Gone"],
+            vec!["textures/objects/gone.png"]
+        );
+    }
+
+    #[test]
+    fn name_mismatch_warning_fires_without_id_or_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let pack_path = write_example_pack(dir.path());
+        let pack = AssetPack::from_read(&mut File::open(&pack_path).unwrap()).unwrap();
+
+        let warning = name_mismatch_warning(&pack, Path::new("out/rocks.dungeondraft_pack"));
+        assert!(warning.unwrap().contains("'12345678'"));
+
+        assert_eq!(
+            name_mismatch_warning(&pack, Path::new("out/rocks_12345678.dungeondraft_pack")),
+            None
+        );
+        assert_eq!(
+            name_mismatch_warning(&pack, Path::new("out/Example-v2.dungeondraft_pack")),
+            None
+        );
     }
 }
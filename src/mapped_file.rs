@@ -0,0 +1,103 @@
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+/// A file mapped into memory read-only. Wrap it in a `Cursor` to read a pack from it
+/// without copying the file through a buffer first. The file should not be changed
+/// while it is mapped.
+pub struct MappedFile {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl MappedFile {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+
+        // Mapping zero bytes is an error, and there is nothing to map anyway.
+        if len == 0 {
+            return Ok(MappedFile {
+                ptr: ptr::null_mut(),
+                len,
+            });
+        }
+
+        // Safety: the file descriptor is valid for the duration of the call, and the mapping
+        // stays valid after the file is closed. It is only unmapped in `drop`.
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(MappedFile { ptr, len })
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+
+        // Safety: `ptr` points to a read-only mapping of `len` bytes, which lives as long as `self`.
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            // Safety: `ptr` and `len` describe a mapping created in `open`, which is not used
+            // after this.
+            unsafe {
+                libc::munmap(self.ptr, self.len);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use std::io::Cursor;
+
+    use crate::asset_pack::test_asset_pack_serialization::create_raw_test_pack;
+    use crate::asset_pack::AssetPack;
+    use crate::mapped_file::MappedFile;
+
+    #[test]
+    fn mapped_read_matches_regular_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("example.dungeondraft_pack");
+        fs::write(&path, create_raw_test_pack().unwrap()).unwrap();
+
+        let regular = AssetPack::from_read(&mut fs::File::open(&path).unwrap()).unwrap();
+
+        let mapped_file = MappedFile::open(&path).unwrap();
+        let mapped = AssetPack::from_read(&mut Cursor::new(&*mapped_file)).unwrap();
+
+        assert_eq!(mapped.godot_version, regular.godot_version);
+        assert_eq!(mapped.layout, regular.layout);
+        assert_eq!(mapped.meta, regular.meta);
+        assert_eq!(mapped.tags, regular.tags);
+        assert_eq!(mapped.object_files, regular.object_files);
+        assert_eq!(mapped.other_files, regular.other_files);
+    }
+}
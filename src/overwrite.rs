@@ -0,0 +1,106 @@
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+/// What to do when an output file already exists.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Overwrite {
+    Never,
+    Always,
+    /// Ask the user for each file.
+    Ask,
+    /// The user chose to quit, nothing should be written anymore.
+    Abort,
+}
+
+impl Overwrite {
+    /// Decides whether the existing file at `path` may be overwritten.
+    /// In `Ask` mode the user is prompted on `output` and the answer is read from `input`.
+    /// Answering `a` or `q` changes the mode for all following files.
+    pub fn allows<R: BufRead, W: Write>(
+        &mut self,
+        path: &Path,
+        input: &mut R,
+        output: &mut W,
+    ) -> bool {
+        match self {
+            Overwrite::Never | Overwrite::Abort => false,
+            Overwrite::Always => true,
+            Overwrite::Ask => {
+                // If the prompt can't be shown or answered, skipping the file is the safe choice.
+                let _ = write!(
+                    output,
+                    "Output file '{}' already exists. Overwrite? [y/N/a/q] ",
+                    path.display()
+                );
+                let _ = output.flush();
+
+                let mut answer = String::new();
+                if input.read_line(&mut answer).is_err() {
+                    return false;
+                }
+
+                match answer.trim().to_lowercase().as_str() {
+                    "y" | "yes" => true,
+                    "a" | "all" => {
+                        *self = Overwrite::Always;
+                        true
+                    }
+                    "q" | "quit" => {
+                        *self = Overwrite::Abort;
+                        false
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+    use std::path::Path;
+
+    use crate::overwrite::Overwrite;
+
+    #[test]
+    fn interactive_prompt_answers() {
+        let path = Path::new("pack.dungeondraft_pack");
+        let mut input = Cursor::new("y\n\nn\na\n");
+        let mut output = vec![];
+
+        let mut overwrite = Overwrite::Ask;
+
+        assert!(overwrite.allows(path, &mut input, &mut output));
+        // Empty answer defaults to skipping.
+        assert!(!overwrite.allows(path, &mut input, &mut output));
+        assert!(!overwrite.allows(path, &mut input, &mut output));
+        assert_eq!(overwrite, Overwrite::Ask);
+
+        assert!(overwrite.allows(path, &mut input, &mut output));
+        assert_eq!(overwrite, Overwrite::Always);
+
+        // No more prompting once `all` was chosen.
+        assert!(overwrite.allows(path, &mut input, &mut output));
+        assert_eq!(
+            String::from_utf8(output)
+                .unwrap()
+                .matches("[y/N/a/q]")
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn interactive_prompt_quit() {
+        let path = Path::new("pack.dungeondraft_pack");
+        let mut input = Cursor::new("q\ny\n");
+        let mut output = vec![];
+
+        let mut overwrite = Overwrite::Ask;
+
+        assert!(!overwrite.allows(path, &mut input, &mut output));
+        assert_eq!(overwrite, Overwrite::Abort);
+        assert!(!overwrite.allows(path, &mut input, &mut output));
+    }
+}
@@ -0,0 +1,62 @@
+use clap::{App, Arg, ArgMatches};
+use log::error;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::process::exit;
+
+use crate::buffer_size_or_exit;
+
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Adds the `PACK` argument to a read-only command, and with the `http` feature also `--url`
+/// to read the pack from the web instead. Use `open_pack_or_exit` to read the pack.
+pub fn pack_input_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    let pack = Arg::with_name("PACK")
+        .help("The `*.dungeondraft_pack` file to read")
+        .index(1);
+
+    #[cfg(feature = "http")]
+    let app = app.arg(pack.required_unless("url")).arg(
+        Arg::with_name("url")
+            .long("url")
+            .value_name("URL")
+            .conflicts_with("PACK")
+            .help(
+                "Download the pack from this `http://` or `https://` url instead of reading a file",
+            ),
+    );
+    #[cfg(not(feature = "http"))]
+    let app = app.arg(pack.required(true));
+
+    app
+}
+
+/// Opens the pack given with `pack_input_args`.
+/// Returns the name to show in messages, and the pack data.
+pub fn open_pack_or_exit(matches: &ArgMatches) -> (String, Box<dyn ReadSeek>) {
+    #[cfg(feature = "http")]
+    if let Some(url) = matches.value_of("url") {
+        return match dd_asset_tools::http::fetch(url) {
+            Ok(data) => (url.to_owned(), Box::new(std::io::Cursor::new(data))),
+            Err(e) => {
+                error!("Could not download pack '{}':\n{}", url, e);
+                exit(1);
+            }
+        };
+    }
+
+    let pack_path = matches.value_of("PACK").unwrap();
+
+    match File::open(pack_path) {
+        Ok(file) => (
+            pack_path.to_owned(),
+            Box::new(BufReader::with_capacity(buffer_size_or_exit(matches), file)),
+        ),
+        Err(e) => {
+            error!("Could not open packfile '{}':\n{}", pack_path, e);
+            exit(1);
+        }
+    }
+}
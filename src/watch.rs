@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Size and modification time of a file. A write changes at least one of them.
+pub type FileState = (u64, SystemTime);
+
+/// Decides which packs in the input directory to process again, see `--watch`.
+/// The directory is polled, and each poll's file states are passed to `poll`.
+#[derive(Debug, Default)]
+pub struct PackWatcher {
+    /// The state of each pack when it was last processed, or found to need no processing.
+    seen: HashMap<PathBuf, FileState>,
+    /// Packs that changed since the previous poll, so might still be being written.
+    pending: HashMap<PathBuf, FileState>,
+    /// Packs this program wrote itself, which should not be processed again.
+    written: HashMap<PathBuf, FileState>,
+}
+
+impl PackWatcher {
+    /// Starts watching with the packs in `states` counted as already processed.
+    pub fn new(states: HashMap<PathBuf, FileState>) -> Self {
+        PackWatcher {
+            seen: states,
+            ..Default::default()
+        }
+    }
+
+    /// Remembers that these packs were written by this program, so writing them
+    /// into the input directory does not trigger processing them again.
+    pub fn record_written(&mut self, states: HashMap<PathBuf, FileState>) {
+        self.written.extend(states);
+    }
+
+    /// Returns the packs that are new or changed, sorted by path. A change is only reported
+    /// once the pack stays the same for a whole poll, so a pack that is still being written
+    /// is not read halfway.
+    pub fn poll(&mut self, states: HashMap<PathBuf, FileState>) -> Vec<PathBuf> {
+        let mut ready = vec![];
+
+        for (path, state) in states.iter() {
+            if self.seen.get(path) == Some(state) {
+                self.pending.remove(path);
+                continue;
+            }
+
+            if self.written.get(path) == Some(state) {
+                self.seen.insert(path.clone(), *state);
+                continue;
+            }
+
+            if self.pending.get(path) == Some(state) {
+                self.pending.remove(path);
+                self.seen.insert(path.clone(), *state);
+                ready.push(path.clone());
+            } else {
+                self.pending.insert(path.clone(), *state);
+            }
+        }
+
+        self.seen.retain(|path, _| states.contains_key(path));
+        self.pending.retain(|path, _| states.contains_key(path));
+
+        ready.sort();
+        ready
+    }
+}
+
+/// The states of the files that still exist.
+pub fn file_states(paths: &[PathBuf]) -> HashMap<PathBuf, FileState> {
+    paths
+        .iter()
+        .filter_map(|path| Some((path.clone(), file_state(path)?)))
+        .collect()
+}
+
+fn file_state(path: &Path) -> Option<FileState> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    use crate::watch::{FileState, PackWatcher};
+
+    fn states(entries: &[(&str, u64, u64)]) -> HashMap<PathBuf, FileState> {
+        entries
+            .iter()
+            .map(|(path, size, seconds)| {
+                (
+                    PathBuf::from(path),
+                    (
+                        *size,
+                        SystemTime::UNIX_EPOCH + Duration::from_secs(*seconds),
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn changed_pack_is_processed_once_settled() {
+        let mut watcher = PackWatcher::new(states(&[("in/a.dungeondraft_pack", 10, 1)]));
+
+        assert!(watcher
+            .poll(states(&[("in/a.dungeondraft_pack", 10, 1)]))
+            .is_empty());
+
+        // Still being written.
+        assert!(watcher
+            .poll(states(&[("in/a.dungeondraft_pack", 20, 2)]))
+            .is_empty());
+        assert!(watcher
+            .poll(states(&[("in/a.dungeondraft_pack", 30, 3)]))
+            .is_empty());
+
+        assert_eq!(
+            watcher.poll(states(&[
+                ("in/a.dungeondraft_pack", 30, 3),
+                ("in/b.dungeondraft_pack", 5, 3)
+            ])),
+            vec![PathBuf::from("in/a.dungeondraft_pack")]
+        );
+        assert_eq!(
+            watcher.poll(states(&[
+                ("in/a.dungeondraft_pack", 30, 3),
+                ("in/b.dungeondraft_pack", 5, 3)
+            ])),
+            vec![PathBuf::from("in/b.dungeondraft_pack")]
+        );
+        assert!(watcher
+            .poll(states(&[
+                ("in/a.dungeondraft_pack", 30, 3),
+                ("in/b.dungeondraft_pack", 5, 3)
+            ]))
+            .is_empty());
+    }
+
+    #[test]
+    fn written_packs_are_ignored() {
+        let mut watcher = PackWatcher::new(HashMap::new());
+        watcher.record_written(states(&[("in/out/a.dungeondraft_pack", 10, 1)]));
+
+        for _ in 0..3 {
+            assert!(watcher
+                .poll(states(&[("in/out/a.dungeondraft_pack", 10, 1)]))
+                .is_empty());
+        }
+
+        // Changed by someone else after it was written.
+        watcher.poll(states(&[("in/out/a.dungeondraft_pack", 11, 2)]));
+        assert_eq!(
+            watcher.poll(states(&[("in/out/a.dungeondraft_pack", 11, 2)])),
+            vec![PathBuf::from("in/out/a.dungeondraft_pack")]
+        );
+    }
+}